@@ -1,7 +1,12 @@
 #![no_std]
 #![no_main]
+#![feature(abi_avr_interrupt)]
 
-use keyboard_hal::{pins, usb_bus, Keyboard, UsbBus, UsbBusAllocator};
+use keyboard_hal::backlight::Backlight;
+use keyboard_hal::hal::simple_pwm::{IntoPwmPin, Prescaler, Timer1Pwm};
+use keyboard_hal::rgb::RgbLight;
+use keyboard_hal::ws2812::Ws2812;
+use keyboard_hal::{matrix, pins, usb_bus, Keyboard, UsbBus, UsbBusAllocator};
 use panic_halt as _;
 
 #[keyboard_hal::entry]
@@ -12,10 +17,28 @@ fn main() -> ! {
     // Get the USB bus via our macro
     let usb_bus = usb_bus!(dp);
 
+    // Millisecond timer for animations
+    keyboard_hal::timer::init(dp.TC0);
+
+    let timer1 = Timer1Pwm::new(dp.TC1, Prescaler::Prescale64);
+    let backlight = Backlight::new(pins.backlight.into_output().into_pwm(&timer1));
+
+    let rgb = RgbLight::new(Ws2812::new(pins.rgb.into_output()));
+
     // Create our keyboard instance
-    let mut keyboard = Keyboard::new(pins, usb_bus);
+    let eeprom = keyboard_hal::hal::Eeprom::new(dp.EEPROM);
+    let mut keyboard = Keyboard::new(matrix!(pins), usb_bus, eeprom)
+        .with_backlight(backlight)
+        .with_rgb(rgb);
+
+    unsafe { avr_device::interrupt::enable() };
 
     loop {
         keyboard.poll();
     }
 }
+
+#[avr_device::interrupt(atmega32u4)]
+fn TIMER0_COMPA() {
+    keyboard_hal::timer::tick();
+}
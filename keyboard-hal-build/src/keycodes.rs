@@ -0,0 +1,276 @@
+//! Translation of QMK keycodes to `keyboard_hal::keycodes` expressions.
+
+/// QMK names (including their long and short aliases) and the matching keycode.
+const KEYCODES: &[(&[&str], &str)] = &[
+    (&["KC_NO", "XXXXXXX"], "No"),
+    (&["KC_TRNS", "KC_TRANSPARENT", "_______"], "Trans"),
+    (&["KC_A"], "A"),
+    (&["KC_B"], "B"),
+    (&["KC_C"], "C"),
+    (&["KC_D"], "D"),
+    (&["KC_E"], "E"),
+    (&["KC_F"], "F"),
+    (&["KC_G"], "G"),
+    (&["KC_H"], "H"),
+    (&["KC_I"], "I"),
+    (&["KC_J"], "J"),
+    (&["KC_K"], "K"),
+    (&["KC_L"], "L"),
+    (&["KC_M"], "M"),
+    (&["KC_N"], "N"),
+    (&["KC_O"], "O"),
+    (&["KC_P"], "P"),
+    (&["KC_Q"], "Q"),
+    (&["KC_R"], "R"),
+    (&["KC_S"], "S"),
+    (&["KC_T"], "T"),
+    (&["KC_U"], "U"),
+    (&["KC_V"], "V"),
+    (&["KC_W"], "W"),
+    (&["KC_X"], "X"),
+    (&["KC_Y"], "Y"),
+    (&["KC_Z"], "Z"),
+    (&["KC_1"], "Num1"),
+    (&["KC_2"], "Num2"),
+    (&["KC_3"], "Num3"),
+    (&["KC_4"], "Num4"),
+    (&["KC_5"], "Num5"),
+    (&["KC_6"], "Num6"),
+    (&["KC_7"], "Num7"),
+    (&["KC_8"], "Num8"),
+    (&["KC_9"], "Num9"),
+    (&["KC_0"], "Num0"),
+    (&["KC_ENT", "KC_ENTER"], "Enter"),
+    (&["KC_ESC", "KC_ESCAPE"], "Escape"),
+    (&["KC_BSPC", "KC_BACKSPACE"], "BSpace"),
+    (&["KC_TAB"], "Tab"),
+    (&["KC_SPC", "KC_SPACE"], "Space"),
+    (&["KC_MINS", "KC_MINUS"], "Minus"),
+    (&["KC_EQL", "KC_EQUAL"], "Equal"),
+    (&["KC_LBRC", "KC_LEFT_BRACKET"], "LBracket"),
+    (&["KC_RBRC", "KC_RIGHT_BRACKET"], "RBracket"),
+    (&["KC_BSLS", "KC_BACKSLASH"], "BSlash"),
+    (&["KC_SCLN", "KC_SEMICOLON"], "Semicolon"),
+    (&["KC_QUOT", "KC_QUOTE"], "Quote"),
+    (&["KC_GRV", "KC_GRAVE"], "Grave"),
+    (&["KC_COMM", "KC_COMMA"], "Comma"),
+    (&["KC_DOT"], "Dot"),
+    (&["KC_SLSH", "KC_SLASH"], "Slash"),
+    (&["KC_CAPS", "KC_CAPS_LOCK"], "Caps"),
+    (&["KC_F1"], "F1"),
+    (&["KC_F2"], "F2"),
+    (&["KC_F3"], "F3"),
+    (&["KC_F4"], "F4"),
+    (&["KC_F5"], "F5"),
+    (&["KC_F6"], "F6"),
+    (&["KC_F7"], "F7"),
+    (&["KC_F8"], "F8"),
+    (&["KC_F9"], "F9"),
+    (&["KC_F10"], "F10"),
+    (&["KC_F11"], "F11"),
+    (&["KC_F12"], "F12"),
+    (&["KC_PSCR", "KC_PRINT_SCREEN"], "PScreen"),
+    (&["KC_SCRL", "KC_SLCK", "KC_SCROLL_LOCK"], "ScrollLock"),
+    (&["KC_PAUS", "KC_BRK", "KC_PAUSE"], "Pause"),
+    (&["KC_INS", "KC_INSERT"], "Insert"),
+    (&["KC_HOME"], "Home"),
+    (&["KC_PGUP", "KC_PAGE_UP"], "PgUp"),
+    (&["KC_DEL", "KC_DELETE"], "Delete"),
+    (&["KC_END"], "End"),
+    (&["KC_PGDN", "KC_PAGE_DOWN"], "PgDown"),
+    (&["KC_RGHT", "KC_RIGHT"], "Right"),
+    (&["KC_LEFT"], "Left"),
+    (&["KC_DOWN"], "Down"),
+    (&["KC_UP"], "Up"),
+    (&["KC_KB_MUTE"], "KbMute"),
+    (&["KC_KB_VOLUME_UP"], "KbVolumeUp"),
+    (&["KC_KB_VOLUME_DOWN"], "KbVolumeDown"),
+    (&["KC_NUHS", "KC_NONUS_HASH"], "NonUsHash"),
+    (&["KC_NUBS", "KC_NONUS_BACKSLASH"], "NonUsBSlash"),
+    (&["KC_APP", "KC_APPLICATION"], "Application"),
+    (&["KC_MENU"], "Menu"),
+    (&["KC_NUM", "KC_NLCK", "KC_NUM_LOCK"], "NumLock"),
+    (&["KC_PSLS", "KC_KP_SLASH"], "KpSlash"),
+    (&["KC_PAST", "KC_KP_ASTERISK"], "KpAsterisk"),
+    (&["KC_PMNS", "KC_KP_MINUS"], "KpMinus"),
+    (&["KC_PPLS", "KC_KP_PLUS"], "KpPlus"),
+    (&["KC_PENT", "KC_KP_ENTER"], "KpEnter"),
+    (&["KC_P1", "KC_KP_1"], "Kp1"),
+    (&["KC_P2", "KC_KP_2"], "Kp2"),
+    (&["KC_P3", "KC_KP_3"], "Kp3"),
+    (&["KC_P4", "KC_KP_4"], "Kp4"),
+    (&["KC_P5", "KC_KP_5"], "Kp5"),
+    (&["KC_P6", "KC_KP_6"], "Kp6"),
+    (&["KC_P7", "KC_KP_7"], "Kp7"),
+    (&["KC_P8", "KC_KP_8"], "Kp8"),
+    (&["KC_P9", "KC_KP_9"], "Kp9"),
+    (&["KC_P0", "KC_KP_0"], "Kp0"),
+    (&["KC_PDOT", "KC_KP_DOT"], "KpDot"),
+    (&["KC_PEQL", "KC_KP_EQUAL"], "KpEqual"),
+    (&["KC_PCMM", "KC_KP_COMMA"], "KpComma"),
+    (&["KC_F13"], "F13"),
+    (&["KC_F14"], "F14"),
+    (&["KC_F15"], "F15"),
+    (&["KC_F16"], "F16"),
+    (&["KC_F17"], "F17"),
+    (&["KC_F18"], "F18"),
+    (&["KC_F19"], "F19"),
+    (&["KC_F20"], "F20"),
+    (&["KC_F21"], "F21"),
+    (&["KC_F22"], "F22"),
+    (&["KC_F23"], "F23"),
+    (&["KC_F24"], "F24"),
+    (&["KC_PWR", "KC_KB_POWER"], "Power"),
+    (&["KC_EXEC", "KC_EXECUTE"], "Execute"),
+    (&["KC_HELP"], "Help"),
+    (&["KC_SLCT", "KC_SELECT"], "Select"),
+    (&["KC_STOP"], "Stop"),
+    (&["KC_AGIN", "KC_AGAIN"], "Again"),
+    (&["KC_UNDO"], "Undo"),
+    (&["KC_CUT"], "Cut"),
+    (&["KC_COPY"], "Copy"),
+    (&["KC_PSTE", "KC_PASTE"], "Paste"),
+    (&["KC_FIND"], "Find"),
+    (&["KC_INT1", "KC_INTERNATIONAL_1"], "Int1"),
+    (&["KC_INT2", "KC_INTERNATIONAL_2"], "Int2"),
+    (&["KC_INT3", "KC_INTERNATIONAL_3"], "Int3"),
+    (&["KC_INT4", "KC_INTERNATIONAL_4"], "Int4"),
+    (&["KC_INT5", "KC_INTERNATIONAL_5"], "Int5"),
+    (&["KC_INT6", "KC_INTERNATIONAL_6"], "Int6"),
+    (&["KC_INT7", "KC_INTERNATIONAL_7"], "Int7"),
+    (&["KC_INT8", "KC_INTERNATIONAL_8"], "Int8"),
+    (&["KC_INT9", "KC_INTERNATIONAL_9"], "Int9"),
+    (&["KC_LNG1", "KC_LANGUAGE_1"], "Lang1"),
+    (&["KC_LNG2", "KC_LANGUAGE_2"], "Lang2"),
+    (&["KC_LNG3", "KC_LANGUAGE_3"], "Lang3"),
+    (&["KC_LNG4", "KC_LANGUAGE_4"], "Lang4"),
+    (&["KC_LNG5", "KC_LANGUAGE_5"], "Lang5"),
+    (&["KC_LNG6", "KC_LANGUAGE_6"], "Lang6"),
+    (&["KC_LNG7", "KC_LANGUAGE_7"], "Lang7"),
+    (&["KC_LNG8", "KC_LANGUAGE_8"], "Lang8"),
+    (&["KC_LNG9", "KC_LANGUAGE_9"], "Lang9"),
+    (&["KC_ERAS", "KC_ALTERNATE_ERASE"], "AltErase"),
+    (&["KC_SYRQ", "KC_SYSTEM_REQUEST"], "SysReq"),
+    (&["KC_CNCL", "KC_CANCEL"], "Cancel"),
+    (&["KC_CLR", "KC_CLEAR"], "Clear"),
+    (&["KC_PRIR", "KC_PRIOR"], "Prior"),
+    (&["KC_RETN", "KC_RETURN"], "Return"),
+    (&["KC_SEPR", "KC_SEPARATOR"], "Separator"),
+    (&["KC_OUT"], "Out"),
+    (&["KC_OPER"], "Oper"),
+    (&["KC_CLAG", "KC_CLEAR_AGAIN"], "ClearAgain"),
+    (&["KC_CRSL", "KC_CRSEL"], "CrSel"),
+    (&["KC_EXSL", "KC_EXSEL"], "ExSel"),
+    (&["KC_MUTE", "KC_AUDIO_MUTE"], "Mute"),
+    (&["KC_VOLU", "KC_AUDIO_VOL_UP"], "VolumeUp"),
+    (&["KC_VOLD", "KC_AUDIO_VOL_DOWN"], "VolumeDown"),
+    (&["KC_MNXT", "KC_MEDIA_NEXT_TRACK"], "MediaNext"),
+    (&["KC_MPRV", "KC_MEDIA_PREV_TRACK"], "MediaPrevious"),
+    (&["KC_MSTP", "KC_MEDIA_STOP"], "MediaStop"),
+    (&["KC_MPLY", "KC_MEDIA_PLAY_PAUSE"], "MediaPlayPause"),
+    // Shifted symbols of the US layout
+    (&["KC_TILD", "KC_TILDE"], "lsft(Grave)"),
+    (&["KC_EXLM", "KC_EXCLAIM"], "lsft(Num1)"),
+    (&["KC_AT"], "lsft(Num2)"),
+    (&["KC_HASH"], "lsft(Num3)"),
+    (&["KC_DLR", "KC_DOLLAR"], "lsft(Num4)"),
+    (&["KC_PERC", "KC_PERCENT"], "lsft(Num5)"),
+    (&["KC_CIRC", "KC_CIRCUMFLEX"], "lsft(Num6)"),
+    (&["KC_AMPR", "KC_AMPERSAND"], "lsft(Num7)"),
+    (&["KC_ASTR", "KC_ASTERISK"], "lsft(Num8)"),
+    (&["KC_LPRN", "KC_LEFT_PAREN"], "lsft(Num9)"),
+    (&["KC_RPRN", "KC_RIGHT_PAREN"], "lsft(Num0)"),
+    (&["KC_UNDS", "KC_UNDERSCORE"], "lsft(Minus)"),
+    (&["KC_PLUS"], "lsft(Equal)"),
+    (&["KC_LCBR", "KC_LEFT_CURLY_BRACE"], "lsft(LBracket)"),
+    (&["KC_RCBR", "KC_RIGHT_CURLY_BRACE"], "lsft(RBracket)"),
+    (&["KC_PIPE"], "lsft(BSlash)"),
+    (&["KC_COLN", "KC_COLON"], "lsft(Semicolon)"),
+    (&["KC_DQUO", "KC_DQT", "KC_DOUBLE_QUOTE"], "lsft(Quote)"),
+    (
+        &["KC_LABK", "KC_LT", "KC_LEFT_ANGLE_BRACKET"],
+        "lsft(Comma)",
+    ),
+    (&["KC_RABK", "KC_GT", "KC_RIGHT_ANGLE_BRACKET"], "lsft(Dot)"),
+    (&["KC_QUES", "KC_QUESTION"], "lsft(Slash)"),
+    (&["KC_LCTL", "KC_LEFT_CTRL"], "LCtrl"),
+    (&["KC_LSFT", "KC_LEFT_SHIFT"], "LShift"),
+    (&["KC_LALT", "KC_LOPT", "KC_LEFT_ALT"], "LAlt"),
+    (&["KC_LGUI", "KC_LCMD", "KC_LWIN", "KC_LEFT_GUI"], "LGui"),
+    (&["KC_RCTL", "KC_RIGHT_CTRL"], "RCtrl"),
+    (&["KC_RSFT", "KC_RIGHT_SHIFT"], "RShift"),
+    (&["KC_RALT", "KC_ROPT", "KC_ALGR", "KC_RIGHT_ALT"], "RAlt"),
+    (&["KC_RGUI", "KC_RCMD", "KC_RWIN", "KC_RIGHT_GUI"], "RGui"),
+    (&["QK_GESC", "KC_GESC", "QK_GRAVE_ESCAPE"], "GraveEsc"),
+    (&["QK_BOOT", "QK_BOOTLOADER", "RESET"], "Reset"),
+    (&["DB_TOGG", "QK_DEBUG_TOGGLE", "DEBUG"], "Diagnostics"),
+    (&["BL_TOGG", "BL_TOGGLE"], "BacklightToggle"),
+    (&["BL_STEP"], "BacklightStep"),
+    (&["BL_UP", "BL_INC"], "BacklightUp"),
+    (&["BL_DOWN", "BL_DEC"], "BacklightDown"),
+    (&["BL_BRTG", "BL_BREATH"], "BacklightBreathing"),
+    (&["RGB_TOG", "UG_TOGG"], "RgbToggle"),
+    (&["RGB_MOD", "RGB_MODE_FORWARD", "UG_NEXT"], "RgbModeNext"),
+    (
+        &["RGB_RMOD", "RGB_MODE_REVERSE", "UG_PREV"],
+        "RgbModePrevious",
+    ),
+    (&["RGB_HUI", "UG_HUEU"], "RgbHueUp"),
+    (&["RGB_HUD", "UG_HUED"], "RgbHueDown"),
+    (&["RGB_SAI", "UG_SATU"], "RgbSatUp"),
+    (&["RGB_SAD", "UG_SATD"], "RgbSatDown"),
+    (&["RGB_VAI", "UG_VALU"], "RgbValUp"),
+    (&["RGB_VAD", "UG_VALD"], "RgbValDown"),
+    (&["RGB_SPI", "UG_SPDU"], "RgbSpeedUp"),
+    (&["RGB_SPD", "UG_SPDD"], "RgbSpeedDown"),
+    (&["AS_TOGG"], "AutoShiftToggle"),
+    (&["QK_LEAD", "QK_LEADER", "KC_LEAD"], "Leader"),
+];
+
+/// QMK modifier wrappers like `LSFT(kc)` and the matching builder function.
+const MOD_WRAPPERS: &[(&[&str], &str)] = &[
+    (&["LCTL", "C"], "lctl"),
+    (&["LSFT", "S"], "lsft"),
+    (&["LALT", "LOPT", "A"], "lalt"),
+    (&["LGUI", "LCMD", "LWIN", "G"], "lgui"),
+    (&["RCTL"], "rctl"),
+    (&["RSFT"], "rsft"),
+    (&["RALT", "ROPT", "ALGR"], "ralt"),
+    (&["RGUI", "RCMD", "RWIN"], "rgui"),
+];
+
+/// QMK layer keycodes like `MO(1)` and the matching builder function.
+const LAYER_ACTIONS: &[(&str, &str)] = &[("MO", "mo"), ("TG", "tg"), ("TO", "to")];
+
+/// Layers which can be addressed by layer keycodes, see `keyboard_hal::keycodes::MAX_LAYERS`.
+const MAX_LAYERS: u8 = 32;
+
+/// Translate a QMK keycode into a Rust expression for the `keyboard_hal::keycodes::prelude`,
+/// e.g. `KC_A` to `A` and `LSFT(KC_1)` to `lsft(Num1)`.
+pub fn lookup(qmk: &str) -> Option<String> {
+    // QMK accepts both `MO(1)` and `MO( 1 )`
+    let qmk: String = qmk.chars().filter(|c| !c.is_whitespace()).collect();
+    translate(&qmk)
+}
+
+fn translate(qmk: &str) -> Option<String> {
+    if let Some((function, argument)) = qmk.strip_suffix(')').and_then(|call| call.split_once('('))
+    {
+        if let Some(&(_, builder)) = MOD_WRAPPERS
+            .iter()
+            .find(|(names, _)| names.contains(&function))
+        {
+            return Some(format!("{builder}({})", translate(argument)?));
+        }
+        if let Some(&(_, builder)) = LAYER_ACTIONS.iter().find(|(name, _)| *name == function) {
+            let layer: u8 = argument.parse().ok().filter(|&layer| layer < MAX_LAYERS)?;
+            return Some(format!("{builder}({layer})"));
+        }
+        return None;
+    }
+
+    KEYCODES
+        .iter()
+        .find(|(names, _)| names.contains(&qmk))
+        .map(|&(_, keycode)| keycode.to_owned())
+}
@@ -0,0 +1,494 @@
+//! Build-script helper which turns a QMK `keymap.json` and `info.json` into a `keyboard-hal`
+//! keymap.
+//!
+//! In the firmware crate, add this crate as a build-dependency and call it from `build.rs`:
+//!
+//! ```ignore
+//! fn main() {
+//!     keyboard_hal_build::Builder::new("keymap.json", "info.json").build();
+//! }
+//! ```
+//!
+//! Then include the generated code and use it when setting up the keyboard:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/keymap.rs"));
+//!
+//! let pins = keyboard_hal::hal::pins!(dp);
+//! let keyboard = Keyboard::new(qmk_matrix!(pins), usb_bus, eeprom).with_layers(layers());
+//! ```
+//!
+//! The generated file contains
+//!
+//! - `MATRIX_ROWS`/`MATRIX_COLS` from the `matrix_pins` of `info.json`, checked against the board
+//!   selected for `keyboard-hal`,
+//! - `KEYMAPS`, the layers of `keymap.json` in matrix order (missing layers are transparent),
+//! - `layers()`, which builds [`Layers`] from `KEYMAPS` and the board's encoder mapping,
+//! - `qmk_matrix!(pins)`, which sets up the [`Matrix`] on the MCU pins from `matrix_pins`.
+//!
+//! [`Layers`]: https://docs.rs/keyboard-hal/latest/keyboard_hal/layers/struct.Layers.html
+//! [`Matrix`]: https://docs.rs/keyboard-hal/latest/keyboard_hal/struct.Matrix.html
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+mod keycodes;
+
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Json {
+        file: String,
+        source: serde_json::Error,
+    },
+    /// The layout used by `keymap.json` is not defined in `info.json`.
+    UnknownLayout(String),
+    UnknownKeycode {
+        keycode: String,
+        layer: usize,
+        /// Index of the key in the layout.
+        index: usize,
+        row: usize,
+        col: usize,
+    },
+    LayerSize {
+        layer: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A key of the layout is outside of the matrix.
+    MatrixPosition {
+        index: usize,
+        row: usize,
+        col: usize,
+    },
+    InvalidPin(String),
+    DiodeDirection(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => {
+                write!(f, "failed reading {}: {}", path.display(), source)
+            }
+            Error::Json { file, source } => write!(f, "{file}: invalid JSON: {source}"),
+            Error::UnknownLayout(layout) => write!(
+                f,
+                "keymap.json: layout `{layout}` is not defined in the `layouts` of info.json"
+            ),
+            Error::UnknownKeycode {
+                keycode,
+                layer,
+                index,
+                row,
+                col,
+            } => write!(
+                f,
+                "keymap.json: unknown keycode `{keycode}` in layer {layer}, key {index} \
+                 (matrix row {row}, col {col})"
+            ),
+            Error::LayerSize {
+                layer,
+                expected,
+                found,
+            } => write!(
+                f,
+                "keymap.json: layer {layer} has {found} keys, but the layout has {expected}"
+            ),
+            Error::MatrixPosition { index, row, col } => write!(
+                f,
+                "info.json: key {index} of the layout is at matrix row {row}, col {col}, which \
+                 is outside of `matrix_pins`"
+            ),
+            Error::InvalidPin(pin) => write!(
+                f,
+                "info.json: `{pin}` in `matrix_pins` is not a pin of the ATmega32U4"
+            ),
+            Error::DiodeDirection(direction) => write!(
+                f,
+                "info.json: diode_direction `{direction}` is not supported, only `COL2ROW` is"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Json { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct KeymapJson {
+    layout: String,
+    layers: Vec<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct InfoJson {
+    matrix_pins: MatrixPins,
+    diode_direction: Option<String>,
+    #[serde(default)]
+    layout_aliases: BTreeMap<String, String>,
+    layouts: BTreeMap<String, Layout>,
+}
+
+#[derive(Deserialize)]
+struct MatrixPins {
+    rows: Vec<String>,
+    cols: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Layout {
+    layout: Vec<LayoutKey>,
+}
+
+#[derive(Deserialize)]
+struct LayoutKey {
+    matrix: (usize, usize),
+}
+
+/// Generates the keymap code from within a build script.
+pub struct Builder {
+    keymap: PathBuf,
+    info: PathBuf,
+    out_file: String,
+}
+
+impl Builder {
+    pub fn new(keymap: impl Into<PathBuf>, info: impl Into<PathBuf>) -> Self {
+        Builder {
+            keymap: keymap.into(),
+            info: info.into(),
+            out_file: "keymap.rs".to_owned(),
+        }
+    }
+
+    /// Name of the generated file in `OUT_DIR` (default: `keymap.rs`).
+    pub fn out_file(mut self, name: impl Into<String>) -> Self {
+        self.out_file = name.into();
+        self
+    }
+
+    /// Generate the keymap, returning the path of the generated file.
+    pub fn generate(&self) -> Result<PathBuf, Error> {
+        println!("cargo:rerun-if-changed={}", self.keymap.display());
+        println!("cargo:rerun-if-changed={}", self.info.display());
+
+        let code = generate(&read(&self.keymap)?, &read(&self.info)?)?;
+
+        let out_dir = std::env::var_os("OUT_DIR").expect("OUT_DIR is only set in build scripts");
+        let path = Path::new(&out_dir).join(&self.out_file);
+        std::fs::write(&path, code).map_err(|source| Error::Io {
+            path: path.clone(),
+            source,
+        })?;
+        Ok(path)
+    }
+
+    /// Generate the keymap and fail the build with a readable message on errors.
+    pub fn build(&self) {
+        if let Err(e) = self.generate() {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    std::fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Generate the Rust code for the given `keymap.json` and `info.json` contents.
+pub fn generate(keymap_json: &str, info_json: &str) -> Result<String, Error> {
+    let keymap: KeymapJson = serde_json::from_str(keymap_json).map_err(|source| Error::Json {
+        file: "keymap.json".to_owned(),
+        source,
+    })?;
+    let info: InfoJson = serde_json::from_str(info_json).map_err(|source| Error::Json {
+        file: "info.json".to_owned(),
+        source,
+    })?;
+
+    if let Some(direction) = info.diode_direction.as_deref() {
+        if direction != "COL2ROW" {
+            return Err(Error::DiodeDirection(direction.to_owned()));
+        }
+    }
+
+    let rows = info
+        .matrix_pins
+        .rows
+        .iter()
+        .map(|pin| pin_field(pin))
+        .collect::<Result<Vec<_>, _>>()?;
+    let cols = info
+        .matrix_pins
+        .cols
+        .iter()
+        .map(|pin| pin_field(pin))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let layout_name = info
+        .layout_aliases
+        .get(&keymap.layout)
+        .unwrap_or(&keymap.layout);
+    let layout = info
+        .layouts
+        .get(layout_name)
+        .ok_or_else(|| Error::UnknownLayout(keymap.layout.clone()))?;
+
+    for (index, key) in layout.layout.iter().enumerate() {
+        let (row, col) = key.matrix;
+        if row >= rows.len() || col >= cols.len() {
+            return Err(Error::MatrixPosition { index, row, col });
+        }
+    }
+
+    let mut layers = Vec::new();
+    for (layer_idx, layer) in keymap.layers.iter().enumerate() {
+        if layer.len() != layout.layout.len() {
+            return Err(Error::LayerSize {
+                layer: layer_idx,
+                expected: layout.layout.len(),
+                found: layer.len(),
+            });
+        }
+
+        // Positions which are not part of the layout stay empty
+        let mut matrix = vec![vec!["No".to_owned(); cols.len()]; rows.len()];
+        for (index, (keycode, key)) in layer.iter().zip(&layout.layout).enumerate() {
+            let (row, col) = key.matrix;
+            matrix[row][col] = keycodes::lookup(keycode).ok_or_else(|| Error::UnknownKeycode {
+                keycode: keycode.clone(),
+                layer: layer_idx,
+                index,
+                row,
+                col,
+            })?;
+        }
+        layers.push(matrix);
+    }
+
+    Ok(render(&rows, &cols, &layers))
+}
+
+/// Field name of a QMK pin (e.g. `D5`) in `atmega_hal::Pins`.
+fn pin_field(pin: &str) -> Result<String, Error> {
+    let mut chars = pin.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(port @ ('B' | 'D')), Some(bit @ '0'..='7'), None)
+        | (Some(port @ 'C'), Some(bit @ ('6' | '7')), None)
+        | (Some(port @ 'E'), Some(bit @ ('2' | '6')), None)
+        | (Some(port @ 'F'), Some(bit @ ('0' | '1' | '4'..='7')), None) => {
+            Ok(format!("p{}{}", port.to_ascii_lowercase(), bit))
+        }
+        _ => Err(Error::InvalidPin(pin.to_owned())),
+    }
+}
+
+fn render(rows: &[String], cols: &[String], layers: &[Vec<Vec<String>>]) -> String {
+    let mut out = String::new();
+    let (num_rows, num_cols, num_layers) = (rows.len(), cols.len(), layers.len());
+
+    writeln!(
+        out,
+        "// @generated by keyboard-hal-build from keymap.json and info.json.  Do not edit.\n"
+    )
+    .unwrap();
+    writeln!(out, "pub const MATRIX_ROWS: usize = {num_rows};").unwrap();
+    writeln!(out, "pub const MATRIX_COLS: usize = {num_cols};\n").unwrap();
+
+    writeln!(
+        out,
+        "const _: () = assert!(\n    \
+             MATRIX_ROWS == keyboard_hal::keyboard_config::MATRIX_ROWS\n        \
+             && MATRIX_COLS == keyboard_hal::keyboard_config::MATRIX_COLS,\n    \
+             \"the matrix in info.json ({num_rows}x{num_cols}) does not match the board selected \
+             for keyboard-hal\"\n);"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "const _: () = assert!(\n    \
+             {num_layers} <= keyboard_hal::keyboard_config::NUM_LAYERS,\n    \
+             \"keymap.json has more layers than the board selected for keyboard-hal supports\"\n\
+         );\n"
+    )
+    .unwrap();
+
+    writeln!(
+        out,
+        "pub const KEYMAPS: [[[keyboard_hal::keycodes::Keycode; MATRIX_COLS]; MATRIX_ROWS]; \
+         keyboard_hal::keyboard_config::NUM_LAYERS] = {{\n    \
+             use keyboard_hal::keycodes::prelude::*;\n\n    \
+             let mut keymaps = [[[Trans; MATRIX_COLS]; MATRIX_ROWS]; \
+             keyboard_hal::keyboard_config::NUM_LAYERS];"
+    )
+    .unwrap();
+    for (index, layer) in layers.iter().enumerate() {
+        writeln!(out, "    keymaps[{index}] = [").unwrap();
+        for row in layer {
+            writeln!(out, "        [{}],", row.join(", ")).unwrap();
+        }
+        writeln!(out, "    ];").unwrap();
+    }
+    writeln!(out, "    keymaps\n}};\n").unwrap();
+
+    writeln!(
+        out,
+        "/// Layers with the keymap from keymap.json and the encoder mapping of the board.\n\
+         pub fn layers() -> keyboard_hal::layers::Layers {{\n    \
+             keyboard_hal::layers::Layers::from_keymaps(KEYMAPS, keyboard_hal::keymaps::ENCODER_MAPS)\n\
+         }}\n"
+    )
+    .unwrap();
+
+    writeln!(
+        out,
+        "/// Set up the key matrix on the `matrix_pins` from info.json.  Takes the MCU pins\n\
+         /// (`keyboard_hal::hal::pins!(dp)`).\n\
+         #[allow(unused_macros)]\n\
+         macro_rules! qmk_matrix {{\n    \
+             ($pins:expr) => {{\n        \
+                 keyboard_hal::Matrix::new(\n            ["
+    )
+    .unwrap();
+    for row in rows {
+        writeln!(
+            out,
+            "                $pins.{row}.into_output_high().downgrade(),"
+        )
+        .unwrap();
+    }
+    writeln!(out, "            ],\n            [").unwrap();
+    for col in cols {
+        writeln!(
+            out,
+            "                $pins.{col}.into_pull_up_input().downgrade().forget_imode(),"
+        )
+        .unwrap();
+    }
+    writeln!(out, "            ],\n        )\n    }};\n}}").unwrap();
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INFO: &str = r#"{
+        "keyboard_name": "test",
+        "diode_direction": "COL2ROW",
+        "matrix_pins": { "rows": ["D0", "D1"], "cols": ["F0", "F1", "B7"] },
+        "layout_aliases": { "LAYOUT_all": "LAYOUT" },
+        "layouts": {
+            "LAYOUT": {
+                "layout": [
+                    { "matrix": [0, 0], "x": 0, "y": 0 },
+                    { "matrix": [0, 1], "x": 1, "y": 0 },
+                    { "matrix": [1, 2], "x": 0, "y": 1, "w": 2 }
+                ]
+            }
+        }
+    }"#;
+
+    fn keymap(layers: &str) -> String {
+        format!(r#"{{ "keyboard": "test", "layout": "LAYOUT_all", "layers": {layers} }}"#)
+    }
+
+    #[test]
+    fn generates_keymap_and_matrix() {
+        let code = generate(
+            &keymap(r#"[["KC_ESC", "MO(1)", "KC_SPC"], ["_______", "LCTL(S(KC_1))", "RGB_TOG"]]"#),
+            INFO,
+        )
+        .unwrap();
+
+        assert!(code.contains("pub const MATRIX_ROWS: usize = 2;"));
+        assert!(code.contains("pub const MATRIX_COLS: usize = 3;"));
+        assert!(code.contains(
+            "keymaps[0] = [\n        [Escape, mo(1), No],\n        [No, No, Space],\n    ];"
+        ));
+        assert!(code.contains(
+            "keymaps[1] = [\n        [Trans, lctl(lsft(Num1)), No],\n        [No, No, RgbToggle],\n    ];"
+        ));
+        assert!(code.contains("$pins.pd1.into_output_high().downgrade(),"));
+        assert!(code.contains("$pins.pb7.into_pull_up_input().downgrade().forget_imode(),"));
+    }
+
+    #[test]
+    fn unknown_keycode_names_key_and_position() {
+        let err = generate(&keymap(r#"[["KC_ESC", "KC_A", "KC_FOO"]]"#), INFO).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "keymap.json: unknown keycode `KC_FOO` in layer 0, key 2 (matrix row 1, col 2)"
+        );
+    }
+
+    #[test]
+    fn unknown_keycode_in_wrapper() {
+        let err = generate(&keymap(r#"[["LSFT(KC_FOO)", "MO(40)", "KC_B"]]"#), INFO).unwrap_err();
+        assert!(
+            matches!(err, Error::UnknownKeycode { keycode, index: 0, .. } if keycode == "LSFT(KC_FOO)")
+        );
+
+        let err = generate(&keymap(r#"[["KC_A", "MO(40)", "KC_B"]]"#), INFO).unwrap_err();
+        assert!(
+            matches!(err, Error::UnknownKeycode { keycode, index: 1, .. } if keycode == "MO(40)")
+        );
+    }
+
+    #[test]
+    fn layer_size_mismatch() {
+        let err = generate(&keymap(r#"[["KC_ESC", "KC_A"]]"#), INFO).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::LayerSize {
+                layer: 0,
+                expected: 3,
+                found: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn invalid_pin() {
+        let info = INFO.replace("\"B7\"", "\"GP7\"");
+        let err = generate(&keymap(r#"[["KC_ESC", "KC_A", "KC_B"]]"#), &info).unwrap_err();
+        assert!(matches!(err, Error::InvalidPin(pin) if pin == "GP7"));
+    }
+
+    #[test]
+    fn only_atmega32u4_pins() {
+        for pin in [
+            "B0", "B7", "C6", "C7", "D0", "D7", "E2", "E6", "F0", "F1", "F4", "F7",
+        ] {
+            assert_eq!(
+                pin_field(pin).unwrap(),
+                format!("p{}", pin.to_ascii_lowercase())
+            );
+        }
+        for pin in ["A0", "C0", "C5", "E0", "E7", "F2", "F3", "B8", "D", "D10"] {
+            assert!(
+                matches!(pin_field(pin), Err(Error::InvalidPin(p)) if p == pin),
+                "{pin}"
+            );
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Auto-shift: holding a key past a timeout sends its shifted version, a short tap sends the key
+//! as usual.
+//!
+//! A press of an eligible key is held back until the key is released (tapped unshifted), the
+//! timeout expires (tapped shifted) or another key is pressed (tapped unshifted).  Keys are
+//! never held down towards the host, so there is no OS key repeat for them.  Auto-shift is
+//! skipped while a modifier is held.
+//!
+//! ```ignore
+//! let keyboard = Keyboard::new(matrix!(pins), usb_bus, eeprom).with_auto_shift(AutoShiftConfig {
+//!     numbers: false,
+//!     ..AutoShiftConfig::default()
+//! });
+//! ```
+use crate::keyboard_config::{MATRIX_COLS, MATRIX_ROWS};
+use crate::keycodes::Keycode;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AutoShiftConfig {
+    /// Toggled at runtime with [`Keycode::AutoShiftToggle`].
+    pub enabled: bool,
+    /// How long a key has to be held to be shifted, in milliseconds.
+    pub timeout: u16,
+    /// `A`-`Z`
+    pub alphas: bool,
+    /// `1`-`0`
+    pub numbers: bool,
+    /// `-`, `=`, `[`, `]`, `\`, `;`, `'`, `` ` ``, `,`, `.` and `/`
+    pub symbols: bool,
+}
+
+impl Default for AutoShiftConfig {
+    fn default() -> Self {
+        AutoShiftConfig {
+            enabled: true,
+            timeout: 175,
+            alphas: true,
+            numbers: true,
+            symbols: true,
+        }
+    }
+}
+
+/// A held back key press.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Pending {
+    pub row: usize,
+    pub col: usize,
+    pub keycode: Keycode,
+    /// Time of the press in milliseconds.
+    pub time: u32,
+}
+
+pub struct AutoShift {
+    config: AutoShiftConfig,
+    pending: Option<Pending>,
+    /// Keys which were already tapped while still being held, so their release is ignored.
+    tapped: [[bool; MATRIX_COLS]; MATRIX_ROWS],
+}
+
+impl AutoShift {
+    pub fn new(config: AutoShiftConfig) -> Self {
+        AutoShift {
+            config,
+            pending: None,
+            tapped: [[false; MATRIX_COLS]; MATRIX_ROWS],
+        }
+    }
+
+    pub fn config(&self) -> AutoShiftConfig {
+        self.config
+    }
+
+    pub fn toggle(&mut self) {
+        self.config.enabled = !self.config.enabled;
+    }
+
+    /// Whether a press of `keycode` should be held back, given the modifier byte of the report.
+    pub fn applies_to(&self, keycode: Keycode, modifier: u8) -> bool {
+        if !self.config.enabled || modifier != 0 {
+            return false;
+        }
+
+        let code = keycode.code();
+        if (Keycode::A.code()..=Keycode::Z.code()).contains(&code) {
+            self.config.alphas
+        } else if (Keycode::Num1.code()..=Keycode::Num0.code()).contains(&code) {
+            self.config.numbers
+        } else {
+            matches!(
+                keycode,
+                Keycode::Minus
+                    | Keycode::Equal
+                    | Keycode::LBracket
+                    | Keycode::RBracket
+                    | Keycode::BSlash
+                    | Keycode::Semicolon
+                    | Keycode::Quote
+                    | Keycode::Grave
+                    | Keycode::Comma
+                    | Keycode::Dot
+                    | Keycode::Slash
+            ) && self.config.symbols
+        }
+    }
+
+    /// Hold back a key press until it is resolved.
+    pub fn hold(&mut self, pending: Pending) {
+        self.pending = Some(pending);
+    }
+
+    /// Take the pending key press, marking it as tapped if it is still held.
+    pub fn take_pending(&mut self) -> Option<Pending> {
+        let pending = self.pending.take()?;
+        self.tapped[pending.row][pending.col] = true;
+        Some(pending)
+    }
+
+    /// Take the pending key press if it has been held past the timeout.
+    pub fn take_expired(&mut self, now: u32) -> Option<Pending> {
+        match self.pending {
+            Some(pending) if now.wrapping_sub(pending.time) >= self.config.timeout as u32 => {
+                self.take_pending()
+            }
+            _ => None,
+        }
+    }
+
+    /// Handle the release of a key.  Returns the pending press if the released key was held
+    /// back, `Released::Tapped` if it was already tapped and its release must be ignored.
+    pub fn release(&mut self, row: usize, col: usize) -> Released {
+        if core::mem::take(&mut self.tapped[row][col]) {
+            return Released::Tapped;
+        }
+        match self.pending {
+            Some(pending) if pending.row == row && pending.col == col => {
+                self.pending = None;
+                Released::Pending(pending)
+            }
+            _ => Released::Other,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Released {
+    /// The key was held back, tap it unshifted now.
+    Pending(Pending),
+    /// The key was tapped already.
+    Tapped,
+    /// The key is not handled by auto-shift.
+    Other,
+}
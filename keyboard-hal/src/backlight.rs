@@ -0,0 +1,198 @@
+//! In-switch LED backlight driven by a PWM pin.
+//!
+//! Any pin supported by `atmega_hal::simple_pwm` can be used, e.g. `PB5`/`PB6`/`PB7` on
+//! `Timer1Pwm`, `PC6` on `Timer3Pwm` or `PB6`/`PC7`/`PD7` on `Timer4Pwm`:
+//!
+//! ```ignore
+//! let timer1 = Timer1Pwm::new(dp.TC1, Prescaler::Prescale64);
+//! let backlight = Backlight::new(pins.backlight.into_output().into_pwm(&timer1));
+//! ```
+use crate::keycodes::Keycode;
+use atmega_hal::port::{mode::PwmOutput, Pin};
+use avr_hal_generic::simple_pwm::PwmPinOps;
+
+/// Number of brightness steps, not counting "off".
+pub const BACKLIGHT_LEVELS: u8 = 3;
+
+/// Duration of one full breathing cycle.
+const BREATHING_PERIOD_MS: u32 = 4000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BacklightConfig {
+    pub enabled: bool,
+    pub breathing: bool,
+    pub level: u8,
+}
+
+impl BacklightConfig {
+    pub const fn from_byte(byte: u8) -> Self {
+        let level = byte & 0x3F;
+        BacklightConfig {
+            enabled: byte & 0x80 != 0,
+            breathing: byte & 0x40 != 0,
+            level: if level > BACKLIGHT_LEVELS {
+                BACKLIGHT_LEVELS
+            } else {
+                level
+            },
+        }
+    }
+
+    pub const fn to_byte(self) -> u8 {
+        (self.enabled as u8) << 7 | (self.breathing as u8) << 6 | (self.level & 0x3F)
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        if self.enabled && self.level == 0 {
+            self.level = BACKLIGHT_LEVELS;
+        }
+    }
+
+    /// Cycle through all levels, wrapping around to "off".
+    pub fn step(&mut self) {
+        self.level = (self.level + 1) % (BACKLIGHT_LEVELS + 1);
+        self.enabled = self.level != 0;
+    }
+
+    pub fn increase(&mut self) {
+        if self.level < BACKLIGHT_LEVELS {
+            self.level += 1;
+        }
+        self.enabled = true;
+    }
+
+    pub fn decrease(&mut self) {
+        self.level = self.level.saturating_sub(1);
+        self.enabled = self.level != 0;
+    }
+}
+
+impl Default for BacklightConfig {
+    fn default() -> Self {
+        BacklightConfig {
+            enabled: true,
+            breathing: false,
+            level: BACKLIGHT_LEVELS,
+        }
+    }
+}
+
+pub struct Backlight<TC, PIN> {
+    pin: Pin<PwmOutput<TC>, PIN>,
+    config: BacklightConfig,
+    /// Level shown instead of the configured one, see [`Backlight::set_indicator`].
+    indicator: Option<u8>,
+}
+
+impl<TC, PIN: PwmPinOps<TC, Duty = u8>> Backlight<TC, PIN> {
+    pub fn new(mut pin: Pin<PwmOutput<TC>, PIN>) -> Self {
+        pin.set_duty(0);
+        pin.enable();
+
+        Backlight {
+            pin,
+            config: BacklightConfig::default(),
+            indicator: None,
+        }
+    }
+
+    pub fn config(&self) -> BacklightConfig {
+        self.config
+    }
+
+    /// Apply a (stored) configuration, e.g. at boot.
+    pub fn restore(&mut self, config: BacklightConfig) {
+        self.config = config;
+        self.update_duty();
+    }
+
+    /// Show the given level regardless of the configuration, e.g. as a
+    /// [layer indicator](crate::indicators), or go back to the configured level with `None`.
+    /// The configuration is left untouched and nothing is persisted.
+    pub fn set_indicator(&mut self, level: Option<u8>) {
+        self.indicator = level.map(|level| level.min(BACKLIGHT_LEVELS));
+        self.update_duty();
+    }
+
+    pub fn toggle(&mut self) {
+        self.config.toggle();
+        self.update_duty();
+    }
+
+    /// Cycle through all levels, wrapping around to "off".
+    pub fn step(&mut self) {
+        self.config.step();
+        self.update_duty();
+    }
+
+    pub fn increase(&mut self) {
+        self.config.increase();
+        self.update_duty();
+    }
+
+    pub fn decrease(&mut self) {
+        self.config.decrease();
+        self.update_duty();
+    }
+
+    pub fn toggle_breathing(&mut self) {
+        self.config.breathing = !self.config.breathing;
+        self.update_duty();
+    }
+
+    /// Handle the backlight keycodes.  Returns `true` if the configuration was changed and should
+    /// be persisted.
+    pub fn process_keycode(&mut self, keycode: Keycode) -> bool {
+        match keycode {
+            Keycode::BacklightToggle => self.toggle(),
+            Keycode::BacklightStep => self.step(),
+            Keycode::BacklightUp => self.increase(),
+            Keycode::BacklightDown => self.decrease(),
+            Keycode::BacklightBreathing => self.toggle_breathing(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Advance the breathing animation.  Must be called regularly with the current time.
+    pub fn task(&mut self, now: u32) {
+        if !self.config.breathing || !self.is_lit() {
+            return;
+        }
+
+        let intensity = breathing_curve(now, BREATHING_PERIOD_MS) as u32;
+        self.pin
+            .set_duty((intensity * self.level_duty() as u32 / 255) as u8);
+    }
+
+    fn is_lit(&self) -> bool {
+        match self.indicator {
+            Some(level) => level > 0,
+            None => self.config.enabled && self.config.level > 0,
+        }
+    }
+
+    fn level_duty(&self) -> u8 {
+        let level = self.indicator.unwrap_or(self.config.level);
+        (level as u16 * u8::MAX as u16 / BACKLIGHT_LEVELS as u16) as u8
+    }
+
+    fn update_duty(&mut self) {
+        let duty = if self.is_lit() { self.level_duty() } else { 0 };
+        self.pin.set_duty(duty);
+    }
+}
+
+/// Brightness (0-255) of a breathing animation with the given period at time `now`.
+pub fn breathing_curve(now: u32, period: u32) -> u8 {
+    // Triangle wave which is squared to approximate the perceived brightness curve
+    let half_period = period / 2;
+    let phase = now % period;
+    let triangle = if phase < half_period {
+        phase * 255 / half_period
+    } else {
+        (period - phase) * 255 / half_period
+    };
+    (triangle * triangle / 255) as u8
+}
@@ -0,0 +1,102 @@
+//! Jumping into the bootloader from the firmware, for [`Keycode::Reset`] (`QK_BOOT`) and
+//! [bootmagic](crate::bootmagic).
+//!
+//! [`Keycode::Reset`]: crate::keycodes::Keycode::Reset
+use crate::keyboard_config::BOOTLOADER;
+use embedded_hal::delay::DelayNs;
+
+/// Bootloader flashed on the board.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bootloader {
+    /// Atmel DFU in the upper 4 KiB of the flash, as shipped on bare ATmega32U4 chips.
+    AtmelDfu,
+    /// Caterina (Arduino Leonardo, SparkFun Pro Micro), entered with a magic value in RAM and a
+    /// watchdog reset.
+    Caterina,
+}
+
+/// Byte address of a 4 KiB bootloader on the ATmega32U4.
+const BOOTLOADER_START: u16 = 0x7000;
+
+/// Value which makes Caterina stay in the bootloader after a watchdog reset.
+const CATERINA_BOOT_KEY: u16 = 0x7777;
+/// Where older Caterina versions look for the boot key.
+const CATERINA_LEGACY_BOOT_KEY_ADDR: u16 = 0x0800;
+/// Where Caterina versions based on newer LUFA look for the boot key, `RAMEND - 1`.
+const CATERINA_LUFA_BOOT_KEY_ADDR: u16 = 0x0AFE;
+/// Newer versions store this signature in the last word of the flash (`FLASHEND - 1`).
+const CATERINA_LUFA_SIGNATURE_ADDR: u16 = 0x7FFE;
+const CATERINA_LUFA_SIGNATURE: u16 = 0xDCFB;
+
+/// Detach from USB and start the bootloader of the board.
+pub fn jump() -> ! {
+    avr_device::interrupt::disable();
+
+    // SAFETY: Interrupts are disabled and nothing else runs after this point.
+    let dp = unsafe { crate::pac::Peripherals::steal() };
+
+    // Detach, so the host notices the bootloader enumerating as a new device
+    dp.USB_DEVICE.udcon.write(|w| w.detach().set_bit());
+    dp.USB_DEVICE.usbcon.write(|w| w.frzclk().set_bit());
+    atmega_hal::delay::Delay::<crate::DefaultClock>::new().delay_ms(5);
+
+    match BOOTLOADER {
+        Bootloader::AtmelDfu => start_at(BOOTLOADER_START),
+        Bootloader::Caterina => {
+            // Same check as the Arduino core
+            let boot_key_addr =
+                if read_flash_word(CATERINA_LUFA_SIGNATURE_ADDR) == CATERINA_LUFA_SIGNATURE {
+                    CATERINA_LUFA_BOOT_KEY_ADDR
+                } else {
+                    CATERINA_LEGACY_BOOT_KEY_ADDR
+                };
+            // SAFETY: Caterina reserves this address for the boot key.  The newer location is the
+            // top of the stack, which only holds frames this function never returns to.
+            unsafe { core::ptr::write_volatile(boot_key_addr as *mut u16, CATERINA_BOOT_KEY) };
+            let mut wdt = atmega_hal::Wdt::new(dp.WDT, &dp.CPU.mcusr);
+            wdt.start(atmega_hal::wdt::Timeout::Ms16).unwrap();
+            loop {}
+        }
+    }
+}
+
+#[cfg(target_arch = "avr")]
+fn start_at(address: u16) -> ! {
+    // SAFETY: The bootloader takes over the MCU from here.
+    unsafe {
+        core::arch::asm!(
+            "ijmp",
+            // `ijmp` takes a word address
+            in("Z") address / 2,
+            options(noreturn),
+        )
+    }
+}
+
+#[cfg(not(target_arch = "avr"))]
+fn start_at(_address: u16) -> ! {
+    unimplemented!("Implementation is only available for avr targets!")
+}
+
+/// Read a word from the flash at the given byte address.
+#[cfg(target_arch = "avr")]
+fn read_flash_word(address: u16) -> u16 {
+    let (low, high): (u8, u8);
+    // SAFETY: `lpm` only reads the program memory.
+    unsafe {
+        core::arch::asm!(
+            "lpm {low}, Z+",
+            "lpm {high}, Z",
+            low = out(reg) low,
+            high = out(reg) high,
+            inout("Z") address => _,
+            options(nostack, preserves_flags, readonly),
+        )
+    }
+    u16::from_le_bytes([low, high])
+}
+
+#[cfg(not(target_arch = "avr"))]
+fn read_flash_word(_address: u16) -> u16 {
+    unimplemented!("Implementation is only available for avr targets!")
+}
@@ -0,0 +1,29 @@
+//! Bootmagic: recovering the keyboard by holding keys while plugging it in.
+//!
+//! [`Keyboard::new`](crate::Keyboard::new) scans the matrix once at startup.  Holding
+//! [`BOOTMAGIC_KEY`] jumps to the bootloader, holding it together with
+//! [`BOOTMAGIC_EEPROM_CLEAR_KEY`] (if the board has one) resets the settings stored in the EEPROM
+//! instead and boots normally.
+use crate::keyboard_config::{BOOTMAGIC_EEPROM_CLEAR_KEY, BOOTMAGIC_KEY, LOCAL_ROWS, MATRIX_COLS};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    /// Boot normally.
+    None,
+    Bootloader,
+    ClearEeprom,
+}
+
+/// Decide what to do from the matrix state at startup.
+pub fn check(state: &[[bool; MATRIX_COLS]; LOCAL_ROWS]) -> Action {
+    let held =
+        |(row, col): (usize, usize)| row < LOCAL_ROWS && col < MATRIX_COLS && state[row][col];
+
+    if !held(BOOTMAGIC_KEY) {
+        return Action::None;
+    }
+    match BOOTMAGIC_EEPROM_CLEAR_KEY {
+        Some(key) if held(key) => Action::ClearEeprom,
+        _ => Action::Bootloader,
+    }
+}
@@ -0,0 +1,163 @@
+//! Minimal USB CDC-ACM class, which shows up as a serial port (`/dev/ttyACM*`, `COM*`) on the
+//! host.
+//!
+//! Only what a console needs is implemented: the line coding is stored but has no effect, and the
+//! control line state is used to tell whether a terminal has the port open.
+use usb_device::class_prelude::*;
+use usb_device::Result;
+
+const USB_CLASS_CDC: u8 = 0x02;
+const USB_CLASS_CDC_DATA: u8 = 0x0A;
+const CDC_SUBCLASS_ACM: u8 = 0x02;
+const CDC_PROTOCOL_NONE: u8 = 0x00;
+
+const CS_INTERFACE: u8 = 0x24;
+const CDC_TYPE_HEADER: u8 = 0x00;
+const CDC_TYPE_CALL_MANAGEMENT: u8 = 0x01;
+const CDC_TYPE_ACM: u8 = 0x02;
+const CDC_TYPE_UNION: u8 = 0x06;
+
+const REQ_SET_LINE_CODING: u8 = 0x20;
+const REQ_GET_LINE_CODING: u8 = 0x21;
+const REQ_SET_CONTROL_LINE_STATE: u8 = 0x22;
+const REQ_SEND_BREAK: u8 = 0x23;
+
+/// 9600 baud, 1 stop bit, no parity, 8 data bits.
+const DEFAULT_LINE_CODING: [u8; 7] = [0x80, 0x25, 0x00, 0x00, 0x00, 0x00, 0x08];
+
+pub struct CdcAcmClass<'a, B: UsbBus> {
+    comm_if: InterfaceNumber,
+    comm_ep: EndpointIn<'a, B>,
+    data_if: InterfaceNumber,
+    read_ep: EndpointOut<'a, B>,
+    write_ep: EndpointIn<'a, B>,
+    line_coding: [u8; 7],
+    dtr: bool,
+}
+
+impl<'a, B: UsbBus> CdcAcmClass<'a, B> {
+    /// Allocate the interfaces and endpoints, with bulk endpoints of `max_packet_size` bytes.
+    pub fn new(alloc: &'a UsbBusAllocator<B>, max_packet_size: u16) -> Self {
+        CdcAcmClass {
+            comm_if: alloc.interface(),
+            comm_ep: alloc.interrupt(8, 255),
+            data_if: alloc.interface(),
+            read_ep: alloc.bulk(max_packet_size),
+            write_ep: alloc.bulk(max_packet_size),
+            line_coding: DEFAULT_LINE_CODING,
+            dtr: false,
+        }
+    }
+
+    pub fn max_packet_size(&self) -> u16 {
+        self.write_ep.max_packet_size()
+    }
+
+    /// Whether a terminal on the host has the port open (Data Terminal Ready).
+    pub fn dtr(&self) -> bool {
+        self.dtr
+    }
+
+    /// Baud rate last set by the host.  It has no effect on the transfer speed.
+    pub fn baud_rate(&self) -> u32 {
+        u32::from_le_bytes([
+            self.line_coding[0],
+            self.line_coding[1],
+            self.line_coding[2],
+            self.line_coding[3],
+        ])
+    }
+
+    /// Write a single packet of at most [`max_packet_size`](Self::max_packet_size) bytes.
+    pub fn write_packet(&mut self, data: &[u8]) -> Result<usize> {
+        self.write_ep.write(data)
+    }
+
+    /// Read a single packet sent by the host.
+    pub fn read_packet(&mut self, data: &mut [u8]) -> Result<usize> {
+        self.read_ep.read(data)
+    }
+
+    fn is_own_request(&self, req: &control::Request) -> bool {
+        req.request_type == control::RequestType::Class
+            && req.recipient == control::Recipient::Interface
+            && req.index == u8::from(self.comm_if) as u16
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for CdcAcmClass<'_, B> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.iad(
+            self.comm_if,
+            2,
+            USB_CLASS_CDC,
+            CDC_SUBCLASS_ACM,
+            CDC_PROTOCOL_NONE,
+            None,
+        )?;
+
+        writer.interface(
+            self.comm_if,
+            USB_CLASS_CDC,
+            CDC_SUBCLASS_ACM,
+            CDC_PROTOCOL_NONE,
+        )?;
+        // CDC 1.10
+        writer.write(CS_INTERFACE, &[CDC_TYPE_HEADER, 0x10, 0x01])?;
+        writer.write(
+            CS_INTERFACE,
+            &[CDC_TYPE_CALL_MANAGEMENT, 0x00, self.data_if.into()],
+        )?;
+        // Supports Set/Get_Line_Coding and Set_Control_Line_State
+        writer.write(CS_INTERFACE, &[CDC_TYPE_ACM, 0x02])?;
+        writer.write(
+            CS_INTERFACE,
+            &[CDC_TYPE_UNION, self.comm_if.into(), self.data_if.into()],
+        )?;
+        writer.endpoint(&self.comm_ep)?;
+
+        writer.interface(self.data_if, USB_CLASS_CDC_DATA, 0x00, 0x00)?;
+        writer.endpoint(&self.write_ep)?;
+        writer.endpoint(&self.read_ep)?;
+
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.line_coding = DEFAULT_LINE_CODING;
+        self.dtr = false;
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let req = *xfer.request();
+        if !self.is_own_request(&req) {
+            return;
+        }
+
+        match req.request {
+            REQ_GET_LINE_CODING => xfer.accept_with(&self.line_coding).ok(),
+            _ => xfer.reject().ok(),
+        };
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = *xfer.request();
+        if !self.is_own_request(&req) {
+            return;
+        }
+
+        match req.request {
+            REQ_SET_LINE_CODING if xfer.data().len() >= self.line_coding.len() => {
+                self.line_coding
+                    .copy_from_slice(&xfer.data()[..DEFAULT_LINE_CODING.len()]);
+                xfer.accept().ok()
+            }
+            REQ_SET_CONTROL_LINE_STATE => {
+                self.dtr = req.value & 0x0001 != 0;
+                xfer.accept().ok()
+            }
+            REQ_SEND_BREAK => xfer.accept().ok(),
+            _ => xfer.reject().ok(),
+        };
+    }
+}
@@ -0,0 +1,173 @@
+//! Matrix diagnostics for finding bad switches and solder joints, printed on the serial console.
+//!
+//! Toggle it with [`Keycode::Diagnostics`] (`DB_TOGG`), or start the keyboard with it turned on
+//! with [`Keyboard::with_diagnostics`](crate::Keyboard::with_diagnostics) when no key works.
+//! Open the console with a terminal, e.g. `ravedude -c` or `screen /dev/ttyACM0`.
+//!
+//! While active, the whole matrix is printed whenever it changes:
+//!
+//! ```text
+//! r/c 0123456789ABCDE
+//! 00: 000000000000000
+//! 01: 000100000000000
+//! ```
+//!
+//! Once a second the scan rate and the keys which bounced are printed as well.  A bounce is a
+//! change of a key less than [`BOUNCE_WINDOW_MS`] after its previous change.  Typing keeps
+//! working as usual.
+//!
+//! [`Keycode::Diagnostics`]: crate::keycodes::Keycode::Diagnostics
+use crate::keyboard_config::{MATRIX_COLS, MATRIX_ROWS};
+use ufmt::{uWrite, uwrite, uwriteln};
+
+/// Time between two status lines.
+pub const REPORT_INTERVAL_MS: u32 = 1000;
+
+/// Changes of a key within this time of its previous change are counted as bounces.
+pub const BOUNCE_WINDOW_MS: u16 = 5;
+
+pub struct Diagnostics {
+    active: bool,
+    previous: [[bool; MATRIX_COLS]; MATRIX_ROWS],
+    /// Time of the last change of every key, truncated to keep the table small.
+    last_change: [[u16; MATRIX_COLS]; MATRIX_ROWS],
+    bounces: [[u8; MATRIX_COLS]; MATRIX_ROWS],
+    scans: u32,
+    /// Start of the current status interval, `None` before the first scan.
+    interval_start: Option<u32>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics {
+            active: false,
+            previous: [[false; MATRIX_COLS]; MATRIX_ROWS],
+            last_change: [[0; MATRIX_COLS]; MATRIX_ROWS],
+            bounces: [[0; MATRIX_COLS]; MATRIX_ROWS],
+            scans: 0,
+            interval_start: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Turn diagnostics on or off.  The counters start over every time it is turned on.
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        if self.active {
+            *self = Diagnostics {
+                active: true,
+                ..Diagnostics::new()
+            };
+        }
+    }
+
+    /// Bounces counted for a key since diagnostics were turned on.
+    pub fn bounces(&self, row: usize, col: usize) -> u8 {
+        self.bounces[row][col]
+    }
+
+    /// Account for one scan of the matrix and print what changed.
+    pub fn scan<W: uWrite>(
+        &mut self,
+        state: &[[bool; MATRIX_COLS]; MATRIX_ROWS],
+        now: u32,
+        out: &mut W,
+    ) -> Result<(), W::Error> {
+        if !self.active {
+            return Ok(());
+        }
+
+        let interval_start = match self.interval_start {
+            Some(start) => start,
+            None => {
+                // Nothing changed before, so the first change is never a bounce
+                self.last_change =
+                    [[(now as u16).wrapping_sub(BOUNCE_WINDOW_MS); MATRIX_COLS]; MATRIX_ROWS];
+                self.interval_start = Some(now);
+                now
+            }
+        };
+        self.scans += 1;
+
+        let mut changed = false;
+        for (row, keys) in state.iter().enumerate() {
+            for (col, &pressed) in keys.iter().enumerate() {
+                if pressed == self.previous[row][col] {
+                    continue;
+                }
+                changed = true;
+                self.previous[row][col] = pressed;
+
+                let time = now as u16;
+                if time.wrapping_sub(self.last_change[row][col]) < BOUNCE_WINDOW_MS {
+                    self.bounces[row][col] = self.bounces[row][col].saturating_add(1);
+                }
+                self.last_change[row][col] = time;
+            }
+        }
+
+        if changed {
+            self.print_matrix(out)?;
+        }
+
+        let elapsed = now.wrapping_sub(interval_start);
+        if elapsed >= REPORT_INTERVAL_MS {
+            self.print_status(self.scans * 1000 / elapsed, out)?;
+            self.scans = 0;
+            self.interval_start = Some(now);
+        }
+
+        Ok(())
+    }
+
+    fn print_matrix<W: uWrite>(&self, out: &mut W) -> Result<(), W::Error> {
+        uwrite!(out, "r/c ")?;
+        for col in 0..MATRIX_COLS {
+            out.write_char(hex_digit(col))?;
+        }
+        uwriteln!(out, "")?;
+
+        for (row, keys) in self.previous.iter().enumerate() {
+            out.write_char(hex_digit(row / 16))?;
+            out.write_char(hex_digit(row % 16))?;
+            uwrite!(out, ": ")?;
+            for &pressed in keys {
+                out.write_char(if pressed { '1' } else { '0' })?;
+            }
+            uwriteln!(out, "")?;
+        }
+        Ok(())
+    }
+
+    fn print_status<W: uWrite>(&self, rate: u32, out: &mut W) -> Result<(), W::Error> {
+        uwrite!(out, "scan rate: {}/s, bounces:", rate)?;
+        let mut any = false;
+        for (row, counts) in self.bounces.iter().enumerate() {
+            for (col, &count) in counts.iter().enumerate() {
+                if count > 0 {
+                    uwrite!(out, " r{}c{}={}", row, col, count)?;
+                    any = true;
+                }
+            }
+        }
+        if !any {
+            uwrite!(out, " none")?;
+        }
+        uwriteln!(out, "")
+    }
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn hex_digit(value: usize) -> char {
+    char::from_digit(value as u32 % 16, 16)
+        .unwrap_or('?')
+        .to_ascii_uppercase()
+}
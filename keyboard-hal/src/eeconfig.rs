@@ -0,0 +1,63 @@
+//! Persistent keyboard configuration stored in the EEPROM.
+//!
+//! The first two bytes hold a magic number.  If it doesn't match, the EEPROM was never
+//! initialized by this crate (or was cleared) and all settings are reset to their defaults.
+use crate::backlight::BacklightConfig;
+use crate::rgb::RgbConfig;
+use atmega_hal::Eeprom;
+
+const MAGIC: u16 = 0xFEE6;
+
+const ADDR_MAGIC: u16 = 0x00;
+const ADDR_BACKLIGHT: u16 = 0x02;
+const ADDR_RGB: u16 = 0x03;
+
+pub struct EeConfig {
+    eeprom: Eeprom,
+}
+
+impl EeConfig {
+    pub fn new(eeprom: Eeprom) -> Self {
+        let mut eeconfig = EeConfig { eeprom };
+        if !eeconfig.is_valid() {
+            eeconfig.reset();
+        }
+        eeconfig
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let magic = u16::from_le_bytes([
+            self.eeprom.read_byte(ADDR_MAGIC),
+            self.eeprom.read_byte(ADDR_MAGIC + 1),
+        ]);
+        magic == MAGIC
+    }
+
+    /// Restore all settings to their defaults.
+    pub fn reset(&mut self) {
+        self.write_backlight(BacklightConfig::default());
+        self.write_rgb(RgbConfig::default());
+
+        let [lo, hi] = MAGIC.to_le_bytes();
+        self.eeprom.write_byte(ADDR_MAGIC, lo);
+        self.eeprom.write_byte(ADDR_MAGIC + 1, hi);
+    }
+
+    pub fn read_backlight(&self) -> BacklightConfig {
+        BacklightConfig::from_byte(self.eeprom.read_byte(ADDR_BACKLIGHT))
+    }
+
+    pub fn write_backlight(&mut self, config: BacklightConfig) {
+        self.eeprom.write_byte(ADDR_BACKLIGHT, config.to_byte());
+    }
+
+    pub fn read_rgb(&self) -> RgbConfig {
+        let mut bytes = [0; RgbConfig::SIZE];
+        self.eeprom.read(ADDR_RGB, &mut bytes).unwrap();
+        RgbConfig::from_bytes(bytes)
+    }
+
+    pub fn write_rgb(&mut self, config: RgbConfig) {
+        self.eeprom.write(ADDR_RGB, &config.to_bytes()).unwrap();
+    }
+}
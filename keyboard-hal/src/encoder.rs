@@ -0,0 +1,190 @@
+//! Quadrature decoding for rotary encoders (e.g. EC11).
+//!
+//! Encoders are registered with [`attach`] and then sampled by [`scan`].  `Keyboard::poll` scans
+//! all encoders on every iteration, which is enough for slowly turned knobs.  For reliable
+//! decoding of fast turns, additionally call [`scan`] from a pin-change interrupt:
+//!
+//! ```ignore
+//! // Both encoder pins on port B: enable PCINT0 for PB4 and PB5
+//! dp.EXINT.pcicr.write(|w| unsafe { w.bits(0b1) });
+//! dp.EXINT.pcmsk0.write(|w| w.bits(0b0011_0000));
+//!
+//! #[avr_device::interrupt(atmega32u4)]
+//! fn PCINT0() {
+//!     keyboard_hal::encoder::scan();
+//! }
+//! ```
+//!
+//! Each detected step is translated into a keycode through the encoder keymap in
+//! [`Layers`](crate::layers::Layers).
+use crate::keyboard_config::NUM_ENCODERS;
+use atmega_hal::port::{
+    mode::{AnyInput, Input},
+    Pin,
+};
+use avr_device::interrupt::Mutex;
+use core::cell::RefCell;
+use core::cmp::Ordering;
+
+/// Number of quadrature transitions between two detents of an EC11 encoder.
+pub const DEFAULT_RESOLUTION: u8 = 4;
+
+/// Highest supported resolution, as transitions are counted in an `i8`.
+pub const MAX_RESOLUTION: u8 = i8::MAX as u8;
+
+/// Valid transitions of the 2-bit gray code, indexed by `(previous << 2) | current`.
+const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl Direction {
+    /// Index of this direction in the encoder keymap.
+    pub fn index(self) -> usize {
+        match self {
+            Direction::Clockwise => 0,
+            Direction::CounterClockwise => 1,
+        }
+    }
+}
+
+/// Hardware independent part of the encoder: turns pin states into steps.
+pub struct Decoder {
+    state: u8,
+    pulses: i8,
+    resolution: i8,
+}
+
+impl Decoder {
+    /// Decoder which emits one step every `resolution` quadrature transitions.
+    ///
+    /// Panics if `resolution` is 0 or above [`MAX_RESOLUTION`], at compile time when used in a
+    /// constant.
+    pub const fn new(resolution: u8) -> Self {
+        assert!(
+            resolution > 0 && resolution <= MAX_RESOLUTION,
+            "encoder resolution must be between 1 and 127"
+        );
+        Decoder {
+            state: 0,
+            pulses: 0,
+            resolution: resolution as i8,
+        }
+    }
+
+    /// Feed the current level of both encoder pins.  Returns a direction once a full step (as
+    /// configured by the resolution) was completed.
+    pub fn update(&mut self, a: bool, b: bool) -> Option<Direction> {
+        self.state = ((self.state << 2) | ((a as u8) << 1) | b as u8) & 0x0F;
+        self.pulses += TRANSITIONS[self.state as usize];
+
+        if self.pulses >= self.resolution {
+            self.pulses %= self.resolution;
+            Some(Direction::Clockwise)
+        } else if self.pulses <= -self.resolution {
+            self.pulses %= self.resolution;
+            Some(Direction::CounterClockwise)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Encoder {
+    pin_a: Pin<Input<AnyInput>>,
+    pin_b: Pin<Input<AnyInput>>,
+    decoder: Decoder,
+    /// Steps which were decoded but not yet turned into keycodes, positive is clockwise.
+    pending: i8,
+}
+
+impl Encoder {
+    pub fn new(pin_a: Pin<Input<AnyInput>>, pin_b: Pin<Input<AnyInput>>) -> Self {
+        Self::with_resolution(pin_a, pin_b, DEFAULT_RESOLUTION)
+    }
+
+    /// Create an encoder which emits one step every `resolution` quadrature transitions.
+    ///
+    /// Panics if `resolution` is 0 or above [`MAX_RESOLUTION`].
+    pub fn with_resolution(
+        pin_a: Pin<Input<AnyInput>>,
+        pin_b: Pin<Input<AnyInput>>,
+        resolution: u8,
+    ) -> Self {
+        let mut decoder = Decoder::new(resolution);
+        // Prime the decoder with the resting position so the first transition is decoded
+        // correctly.
+        decoder.update(pin_a.is_high(), pin_b.is_high());
+
+        Encoder {
+            pin_a,
+            pin_b,
+            decoder,
+            pending: 0,
+        }
+    }
+
+    fn sample(&mut self) {
+        match self
+            .decoder
+            .update(self.pin_a.is_high(), self.pin_b.is_high())
+        {
+            Some(Direction::Clockwise) => self.pending = self.pending.saturating_add(1),
+            Some(Direction::CounterClockwise) => self.pending = self.pending.saturating_sub(1),
+            None => {}
+        }
+    }
+
+    fn take_step(&mut self) -> Option<Direction> {
+        match self.pending.cmp(&0) {
+            Ordering::Greater => {
+                self.pending -= 1;
+                Some(Direction::Clockwise)
+            }
+            Ordering::Less => {
+                self.pending += 1;
+                Some(Direction::CounterClockwise)
+            }
+            Ordering::Equal => None,
+        }
+    }
+}
+
+const NO_ENCODER: Option<Encoder> = None;
+
+static ENCODERS: Mutex<RefCell<[Option<Encoder>; NUM_ENCODERS]>> =
+    Mutex::new(RefCell::new([NO_ENCODER; NUM_ENCODERS]));
+
+/// Register an encoder at position `index` of the encoder keymap.
+pub fn attach(index: usize, encoder: Encoder) {
+    avr_device::interrupt::free(|cs| {
+        ENCODERS.borrow(cs).borrow_mut()[index] = Some(encoder);
+    });
+}
+
+/// Sample all attached encoders.
+///
+/// This is called from `Keyboard::poll` and may additionally be called from a pin-change
+/// interrupt handler.
+pub fn scan() {
+    avr_device::interrupt::free(|cs| {
+        for encoder in ENCODERS.borrow(cs).borrow_mut().iter_mut().flatten() {
+            encoder.sample();
+        }
+    });
+}
+
+/// Take one pending step of any encoder, together with the index of that encoder.
+pub(crate) fn take_step() -> Option<(usize, Direction)> {
+    avr_device::interrupt::free(|cs| {
+        ENCODERS
+            .borrow(cs)
+            .borrow_mut()
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, encoder)| Some((index, encoder.as_mut()?.take_step()?)))
+    })
+}
@@ -0,0 +1,58 @@
+//! Hooks for adding behaviour to the keyboard from the firmware, similar to the `*_user`
+//! callbacks of QMK.
+//!
+//! Implement [`KeyboardHooks`] on a type of your firmware and hand it to
+//! [`Keyboard::with_hooks`](crate::Keyboard::with_hooks).  All methods have a default which does
+//! nothing, so only the ones of interest need to be implemented:
+//!
+//! ```ignore
+//! struct Hooks {
+//!     caps_word: bool,
+//! }
+//!
+//! impl KeyboardHooks for Hooks {
+//!     fn process_record(&mut self, keycode: Keycode, pressed: bool, _row: usize, _col: usize) -> bool {
+//!         if keycode == Keycode::Caps && pressed {
+//!             self.caps_word = !self.caps_word;
+//!             // Do not send the key to the host
+//!             return false;
+//!         }
+//!         true
+//!     }
+//! }
+//! ```
+use crate::keycodes::Keycode;
+
+pub trait KeyboardHooks {
+    /// Called for every key press and release on the matrix with the keycode it resolved to,
+    /// before the keyboard acts on it.  Return `false` to skip the default handling (layer
+    /// switching, lighting controls and the report to the host).
+    fn process_record(&mut self, keycode: Keycode, pressed: bool, row: usize, col: usize) -> bool {
+        let _ = (keycode, pressed, row, col);
+        true
+    }
+
+    /// Called whenever the active layer changes.
+    fn layer_state_changed(&mut self, layer: usize) {
+        let _ = layer;
+    }
+
+    /// Called after every scan of the matrix.
+    fn matrix_scan(&mut self) {}
+
+    /// Called when a leader sequence with [`LeaderAction::User`] was typed.
+    ///
+    /// [`LeaderAction::User`]: crate::leader::LeaderAction::User
+    fn leader_action(&mut self, id: u16) {
+        let _ = id;
+    }
+
+    /// Called when the host changes the lock key LEDs, see
+    /// [`UsbKeyboard::leds`](crate::usb_keyboard::UsbKeyboard::leds).
+    fn led_state_changed(&mut self, leds: u8) {
+        let _ = leds;
+    }
+}
+
+/// No hooks, the keyboard behaves as configured by its keymap.
+impl KeyboardHooks for () {}
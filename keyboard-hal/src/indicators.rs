@@ -0,0 +1,107 @@
+//! Showing the active layer on GPIO LEDs, the backlight or the RGB underglow, for boards without
+//! a display.
+//!
+//! Every layer can light its own LED and override the backlight level and the underglow colour
+//! while it is active.  Layers without an override show the configured lighting:
+//!
+//! ```ignore
+//! let indicators = LayerIndicators::new()
+//!     .with_led(1, pins.pf0.into_output().downgrade())
+//!     .with_backlight_level(1, 1)
+//!     .with_rgb(2, Hsv::new(170, 255, 255));
+//!
+//! let keyboard = Keyboard::new(matrix!(pins), usb_bus, eeprom).with_layer_indicators(indicators);
+//! ```
+//!
+//! The indicators are updated only when the layer actually changes.  To react on layer changes
+//! in other ways, see [`KeyboardHooks::layer_state_changed`](crate::KeyboardHooks).
+use crate::backlight::Backlight;
+use crate::keyboard_config::NUM_LAYERS;
+use crate::rgb::{Hsv, RgbLight};
+use crate::ws2812::Ws2812Pin;
+use atmega_hal::port::{mode::Output, Pin, PinOps};
+use avr_hal_generic::simple_pwm::PwmPinOps;
+
+pub struct LayerIndicators {
+    leds: [Option<Pin<Output>>; NUM_LAYERS],
+    /// Whether the LEDs light up with the pin driven low.
+    active_low: bool,
+    backlight_levels: [Option<u8>; NUM_LAYERS],
+    rgb_colours: [Option<Hsv>; NUM_LAYERS],
+    /// Layer currently shown, `None` before the first update.
+    shown: Option<usize>,
+}
+
+impl LayerIndicators {
+    pub fn new() -> Self {
+        LayerIndicators {
+            leds: core::array::from_fn(|_| None),
+            active_low: false,
+            backlight_levels: [None; NUM_LAYERS],
+            rgb_colours: [None; NUM_LAYERS],
+            shown: None,
+        }
+    }
+
+    /// Light the LED on `pin` while `layer` is active.
+    pub fn with_led(mut self, layer: usize, pin: Pin<Output>) -> Self {
+        self.leds[layer] = Some(pin);
+        self
+    }
+
+    /// Drive the LED pins low instead of high to light them.
+    pub fn active_low(mut self) -> Self {
+        self.active_low = true;
+        self
+    }
+
+    /// Set the backlight to `level` while `layer` is active.
+    pub fn with_backlight_level(mut self, layer: usize, level: u8) -> Self {
+        self.backlight_levels[layer] = Some(level);
+        self
+    }
+
+    /// Show `hsv` on the RGB underglow while `layer` is active.
+    pub fn with_rgb(mut self, layer: usize, hsv: Hsv) -> Self {
+        self.rgb_colours[layer] = Some(hsv);
+        self
+    }
+
+    /// Show `layer` if it is not shown already.
+    pub fn update<TC, BL, RGB>(
+        &mut self,
+        layer: usize,
+        backlight: Option<&mut Backlight<TC, BL>>,
+        rgb: Option<&mut RgbLight<RGB>>,
+    ) where
+        BL: PwmPinOps<TC, Duty = u8>,
+        RGB: Ws2812Pin + PinOps,
+    {
+        if self.shown == Some(layer) {
+            return;
+        }
+        self.shown = Some(layer);
+
+        for (i, led) in self.leds.iter_mut().enumerate() {
+            if let Some(pin) = led {
+                if (i == layer) != self.active_low {
+                    pin.set_high();
+                } else {
+                    pin.set_low();
+                }
+            }
+        }
+        if let Some(backlight) = backlight {
+            backlight.set_indicator(self.backlight_levels[layer]);
+        }
+        if let Some(rgb) = rgb {
+            rgb.set_indicator(self.rgb_colours[layer]);
+        }
+    }
+}
+
+impl Default for LayerIndicators {
+    fn default() -> Self {
+        Self::new()
+    }
+}
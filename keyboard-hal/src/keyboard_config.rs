@@ -1,3 +1,95 @@
-pub const MATRIX_ROWS: usize = 5;
-pub const MATRIX_COLS: usize = 15;
-pub const NUM_LAYERS: usize = 3;
+//! Matrix size, lighting, USB identity and bootloader of the selected board.
+//!
+//! `LOCAL_ROWS` are the rows scanned by this controller.  Split keyboards scan half of the
+//! `MATRIX_ROWS` on each side, all other boards all of them.
+use crate::bootloader::Bootloader;
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "dz60-rev2")] {
+        pub const MATRIX_ROWS: usize = 5;
+        pub const LOCAL_ROWS: usize = 5;
+        pub const MATRIX_COLS: usize = 15;
+        pub const NUM_LAYERS: usize = 3;
+        pub const NUM_ENCODERS: usize = 1;
+        pub const RGBLED_NUM: usize = 16;
+
+        pub const USB_VID: u16 = 0x445A;
+        pub const USB_PID: u16 = 0x2260;
+        pub const MANUFACTURER: &str = "KBDFans";
+        pub const PRODUCT: &str = "DZ60 rev2";
+        pub const DEVICE_RELEASE: u16 = 0x0002;
+        pub const BOOTLOADER: Bootloader = Bootloader::AtmelDfu;
+    } else if #[cfg(feature = "dz60-rev3")] {
+        pub const MATRIX_ROWS: usize = 5;
+        pub const LOCAL_ROWS: usize = 5;
+        pub const MATRIX_COLS: usize = 15;
+        pub const NUM_LAYERS: usize = 3;
+        pub const NUM_ENCODERS: usize = 1;
+        pub const RGBLED_NUM: usize = 16;
+
+        pub const USB_VID: u16 = 0x445A;
+        pub const USB_PID: u16 = 0x2260;
+        pub const MANUFACTURER: &str = "KBDFans";
+        pub const PRODUCT: &str = "DZ60 rev3";
+        pub const DEVICE_RELEASE: u16 = 0x0003;
+        pub const BOOTLOADER: Bootloader = Bootloader::AtmelDfu;
+    } else if #[cfg(feature = "planck-rev5")] {
+        pub const MATRIX_ROWS: usize = 4;
+        pub const LOCAL_ROWS: usize = 4;
+        pub const MATRIX_COLS: usize = 12;
+        pub const NUM_LAYERS: usize = 3;
+        pub const NUM_ENCODERS: usize = 0;
+        pub const RGBLED_NUM: usize = 9;
+
+        pub const USB_VID: u16 = 0x03A8;
+        pub const USB_PID: u16 = 0xA4F9;
+        pub const MANUFACTURER: &str = "OLKB";
+        pub const PRODUCT: &str = "Planck rev5";
+        pub const DEVICE_RELEASE: u16 = 0x0005;
+        pub const BOOTLOADER: Bootloader = Bootloader::AtmelDfu;
+    } else if #[cfg(feature = "promicro-handwired")] {
+        pub const MATRIX_ROWS: usize = 4;
+        pub const LOCAL_ROWS: usize = 4;
+        pub const MATRIX_COLS: usize = 12;
+        pub const NUM_LAYERS: usize = 3;
+        pub const NUM_ENCODERS: usize = 0;
+        pub const RGBLED_NUM: usize = 12;
+
+        // pid.codes test PID, handwired boards have no vendor of their own
+        pub const USB_VID: u16 = 0x1209;
+        pub const USB_PID: u16 = 0x0001;
+        pub const MANUFACTURER: &str = "Handwired";
+        pub const PRODUCT: &str = "Pro Micro 4x12";
+        pub const DEVICE_RELEASE: u16 = 0x0001;
+        pub const BOOTLOADER: Bootloader = Bootloader::Caterina;
+    } else if #[cfg(feature = "lets-split-rev2")] {
+        // Rows 0-3 are the left half, rows 4-7 the right half.  Each controller only scans
+        // the rows of its own half.
+        pub const MATRIX_ROWS: usize = 8;
+        pub const LOCAL_ROWS: usize = 4;
+        pub const MATRIX_COLS: usize = 6;
+        pub const NUM_LAYERS: usize = 3;
+        pub const NUM_ENCODERS: usize = 0;
+        pub const RGBLED_NUM: usize = 12;
+
+        // pid.codes test PID, the kit has no registered ID
+        pub const USB_VID: u16 = 0x1209;
+        pub const USB_PID: u16 = 0x0002;
+        pub const MANUFACTURER: &str = "Wootpatoot";
+        pub const PRODUCT: &str = "Let's Split rev2";
+        pub const DEVICE_RELEASE: u16 = 0x0002;
+        pub const BOOTLOADER: Bootloader = Bootloader::Caterina;
+    }
+}
+
+#[cfg(not(feature = "split"))]
+const _: () = assert!(
+    LOCAL_ROWS == MATRIX_ROWS,
+    "boards without the `split` feature must scan all rows"
+);
+
+/// Key (row, col) which jumps to the bootloader when held while plugging in the keyboard.
+pub const BOOTMAGIC_KEY: (usize, usize) = (0, 0);
+/// Key which, held together with [`BOOTMAGIC_KEY`], clears the settings stored in the EEPROM
+/// instead.
+pub const BOOTMAGIC_EEPROM_CLEAR_KEY: Option<(usize, usize)> = Some((0, 1));
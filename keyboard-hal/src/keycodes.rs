@@ -1,107 +1,556 @@
-#[repr(u8)]
+//! 16-bit keycodes as used in the keymaps.
+//!
+//! The keycode space follows the one of QMK:
+//!
+//! | Range             | Content                                                         |
+//! |-------------------|-----------------------------------------------------------------|
+//! | `0x0000..=0x00FF` | Basic keys, i.e. the HID keyboard usage page                    |
+//! | `0x00A8..=0x00AE` | Media keys, sent as consumer control usages                     |
+//! | `0x0100..=0x1FFF` | Basic key with modifiers held ([`lctl`], [`lsft`], ...)         |
+//! | `0x5200..=0x527F` | Layer actions ([`to`], [`mo`], [`tg`])                          |
+//! | `0x7800..=0x7DFF` | Firmware features (lighting, bootloader, auto-shift, leader)    |
+//! | `0x7E40..=0x7FFF` | User keycodes ([`user`]) for [`KeyboardHooks`]                  |
+//!
+//! Keymaps import the [`prelude`], which has all named keycodes and the builder functions:
+//!
+//! ```ignore
+//! use keyboard_hal::keycodes::prelude::*;
+//!
+//! const LAYER: [Keycode; 4] = [lsft(Num1), lctl(C), mo(1), user(0)];
+//! ```
+//!
+//! [`KeyboardHooks`]: crate::hooks::KeyboardHooks
+use core::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Keycode(u16);
+
+const QK_BASIC_MAX: u16 = 0x00FF;
+const QK_MODS: u16 = 0x0100;
+const QK_MODS_MAX: u16 = 0x1FFF;
+const QK_TO: u16 = 0x5200;
+const QK_MOMENTARY: u16 = 0x5220;
+const QK_TOGGLE_LAYER: u16 = 0x5260;
+const QK_LAYER_MAX: u16 = 0x527F;
+const QK_USER: u16 = 0x7E40;
+const QK_USER_MAX: u16 = 0x7FFF;
+
+/// Layers which can be addressed by layer actions.
+pub const MAX_LAYERS: u8 = 32;
+
+// Modifier bits of the keycodes in `QK_MODS`
+const MOD_LCTL: u16 = 0x01;
+const MOD_LSFT: u16 = 0x02;
+const MOD_LALT: u16 = 0x04;
+const MOD_LGUI: u16 = 0x08;
+const MOD_RIGHT: u16 = 0x10;
+
+/// What a layer keycode does to the active layer.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum Keycode {
+pub enum LayerAction {
+    /// Switch to the layer (`TO(n)`).
+    To(u8),
+    /// Activate the layer while the key is held (`MO(n)`).
+    Momentary(u8),
+    /// Switch to the layer, or back to layer 0 if it is active already (`TG(n)`).
+    Toggle(u8),
+}
+
+impl Keycode {
+    pub const fn from_code(code: u16) -> Self {
+        Keycode(code)
+    }
+
+    pub const fn code(self) -> u16 {
+        self.0
+    }
+
+    /// The HID usage sent to the host for this key, if any.  Keys wrapped with modifiers return
+    /// the usage of the wrapped key.
+    pub const fn hid_usage(self) -> Option<u8> {
+        if self.0 > QK_MODS_MAX {
+            return None;
+        }
+        match (self.0 & QK_BASIC_MAX) as u8 {
+            usage @ (0x04..=0xA4 | 0xB0..=0xDD | 0xE0..=0xE7) => Some(usage),
+            _ => None,
+        }
+    }
+
+    /// Usage on the HID consumer page of a media key.  Hosts only act on volume and media keys
+    /// reported through a consumer control report, as the keyboard page usages are mostly ignored.
+    pub const fn consumer_usage(self) -> Option<u16> {
+        match self {
+            Keycode::Mute => Some(0x00E2),
+            Keycode::VolumeUp => Some(0x00E9),
+            Keycode::VolumeDown => Some(0x00EA),
+            Keycode::MediaNext => Some(0x00B5),
+            Keycode::MediaPrevious => Some(0x00B6),
+            Keycode::MediaStop => Some(0x00B7),
+            Keycode::MediaPlayPause => Some(0x00CD),
+            _ => None,
+        }
+    }
+
+    /// Modifiers held together with this key, as bits of the HID report modifier byte.
+    pub const fn mods(self) -> u8 {
+        if self.0 < QK_MODS || self.0 > QK_MODS_MAX {
+            return 0;
+        }
+        let mods = (self.0 >> 8) as u8;
+        if mods & MOD_RIGHT as u8 != 0 {
+            (mods & 0x0F) << 4
+        } else {
+            mods
+        }
+    }
+
+    pub const fn layer_action(self) -> Option<LayerAction> {
+        if self.0 < QK_TO || self.0 > QK_LAYER_MAX {
+            return None;
+        }
+        let layer = (self.0 & 0x1F) as u8;
+        match self.0 & !0x1F {
+            QK_TO => Some(LayerAction::To(layer)),
+            QK_MOMENTARY => Some(LayerAction::Momentary(layer)),
+            QK_TOGGLE_LAYER => Some(LayerAction::Toggle(layer)),
+            _ => None,
+        }
+    }
+
+    /// Index of a [`user`] keycode.
+    pub const fn user_index(self) -> Option<u16> {
+        if self.0 >= QK_USER && self.0 <= QK_USER_MAX {
+            Some(self.0 - QK_USER)
+        } else {
+            None
+        }
+    }
+}
+
+const fn with_mods(mods: u16, keycode: Keycode) -> Keycode {
+    // Keeps the modifiers of an already wrapped key, so the wrappers can be nested
+    Keycode(keycode.0 | mods << 8)
+}
+
+/// `LCTL(kc)`: the key with left control held.
+pub const fn lctl(keycode: Keycode) -> Keycode {
+    with_mods(MOD_LCTL, keycode)
+}
+
+/// `LSFT(kc)`: the key with left shift held.
+pub const fn lsft(keycode: Keycode) -> Keycode {
+    with_mods(MOD_LSFT, keycode)
+}
+
+/// `LALT(kc)`: the key with left alt held.
+pub const fn lalt(keycode: Keycode) -> Keycode {
+    with_mods(MOD_LALT, keycode)
+}
+
+/// `LGUI(kc)`: the key with the left GUI key held.
+pub const fn lgui(keycode: Keycode) -> Keycode {
+    with_mods(MOD_LGUI, keycode)
+}
+
+/// `RCTL(kc)`: the key with right control held.
+pub const fn rctl(keycode: Keycode) -> Keycode {
+    with_mods(MOD_RIGHT | MOD_LCTL, keycode)
+}
+
+/// `RSFT(kc)`: the key with right shift held.
+pub const fn rsft(keycode: Keycode) -> Keycode {
+    with_mods(MOD_RIGHT | MOD_LSFT, keycode)
+}
+
+/// `RALT(kc)`: the key with right alt (AltGr) held.
+pub const fn ralt(keycode: Keycode) -> Keycode {
+    with_mods(MOD_RIGHT | MOD_LALT, keycode)
+}
+
+/// `RGUI(kc)`: the key with the right GUI key held.
+pub const fn rgui(keycode: Keycode) -> Keycode {
+    with_mods(MOD_RIGHT | MOD_LGUI, keycode)
+}
+
+/// `TO(n)`: switch to a layer.
+pub const fn to(layer: u8) -> Keycode {
+    assert!(layer < MAX_LAYERS);
+    Keycode(QK_TO | layer as u16)
+}
+
+/// `MO(n)`: activate a layer while the key is held.
+pub const fn mo(layer: u8) -> Keycode {
+    assert!(layer < MAX_LAYERS);
+    Keycode(QK_MOMENTARY | layer as u16)
+}
+
+/// `TG(n)`: toggle a layer.
+pub const fn tg(layer: u8) -> Keycode {
+    assert!(layer < MAX_LAYERS);
+    Keycode(QK_TOGGLE_LAYER | layer as u16)
+}
+
+/// `QK_USER_n`: a keycode without built-in meaning, for [`KeyboardHooks`].
+///
+/// [`KeyboardHooks`]: crate::hooks::KeyboardHooks
+pub const fn user(index: u16) -> Keycode {
+    assert!(index <= QK_USER_MAX - QK_USER);
+    Keycode(QK_USER + index)
+}
+
+impl fmt::Debug for Keycode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = self.name() {
+            return f.write_str(name);
+        }
+        if let Some(action) = self.layer_action() {
+            return match action {
+                LayerAction::To(layer) => write!(f, "to({layer})"),
+                LayerAction::Momentary(layer) => write!(f, "mo({layer})"),
+                LayerAction::Toggle(layer) => write!(f, "tg({layer})"),
+            };
+        }
+        if let Some(index) = self.user_index() {
+            return write!(f, "user({index})");
+        }
+        if self.0 < QK_MODS || self.0 > QK_MODS_MAX {
+            return write!(f, "Keycode({:#06x})", self.0);
+        }
+
+        // Modifier wrapped key, written the way it is built, e.g. `lctl(lsft(A))`
+        let mods = self.0 >> 8;
+        let builders = if mods & MOD_RIGHT != 0 {
+            ["rctl", "rsft", "ralt", "rgui"]
+        } else {
+            ["lctl", "lsft", "lalt", "lgui"]
+        };
+        let mut nesting = 0;
+        for (bit, builder) in builders.iter().enumerate() {
+            if mods & (1 << bit) != 0 {
+                write!(f, "{builder}(")?;
+                nesting += 1;
+            }
+        }
+        write!(f, "{:?}", Keycode(self.0 & QK_BASIC_MAX))?;
+        for _ in 0..nesting {
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! keycodes {
+    ($($name:ident = $code:literal,)*) => {
+        #[allow(non_upper_case_globals)]
+        impl Keycode {
+            $(pub const $name: Keycode = Keycode($code);)*
+
+            /// Name of a named keycode, e.g. `"A"`.
+            pub const fn name(self) -> Option<&'static str> {
+                match self.0 {
+                    $($code => Some(stringify!($name)),)*
+                    _ => None,
+                }
+            }
+        }
+
+        /// All named keycodes and the keycode builders, for glob imports in keymaps.
+        pub mod prelude {
+            #![allow(non_upper_case_globals)]
+            pub use super::{lalt, lctl, lgui, lsft, mo, ralt, rctl, rgui, rsft, tg, to, user};
+            pub use super::Keycode;
+
+            $(pub const $name: Keycode = Keycode::$name;)*
+        }
+    };
+}
+
+keycodes! {
     // Special
-    No = 0x00,
-    Trans = 0x01,           // _______ in QMK
-    MomentaryLayer1 = 0xF1, // Custom code for MO(_FL)
-    MomentaryLayer2 = 0xF2, // Custom code for MO(_CL)
-
-    // Standard Keys
-    A = 0x04,
-    B = 0x05,
-    C = 0x06,
-    D = 0x07,
-    E = 0x08,
-    F = 0x09,
-    G = 0x0A,
-    H = 0x0B,
-    I = 0x0C,
-    J = 0x0D,
-    K = 0x0E,
-    L = 0x0F,
-    M = 0x10,
-    N = 0x11,
-    O = 0x12,
-    P = 0x13,
-    Q = 0x14,
-    R = 0x15,
-    S = 0x16,
-    T = 0x17,
-    U = 0x18,
-    V = 0x19,
-    W = 0x1A,
-    X = 0x1B,
-    Y = 0x1C,
-    Z = 0x1D,
-
-    Num1 = 0x1E,
-    Num2 = 0x1F,
-    Num3 = 0x20,
-    Num4 = 0x21,
-    Num5 = 0x22,
-    Num6 = 0x23,
-    Num7 = 0x24,
-    Num8 = 0x25,
-    Num9 = 0x26,
-    Num0 = 0x27,
-
-    Enter = 0x28,
-    Escape = 0x29,
-    BSpace = 0x2A,
-    Tab = 0x2B,
-    Space = 0x2C,
-    Minus = 0x2D,
-    Equal = 0x2E,
-    LBracket = 0x2F,
-    RBracket = 0x30,
-    BSlash = 0x31,
-    Semicolon = 0x33,
-    Quote = 0x34,
-    Grave = 0x35,
-    Comma = 0x36,
-    Dot = 0x37,
-    Slash = 0x38,
-    Caps = 0x39,
-
-    F1 = 0x3A,
-    F2 = 0x3B,
-    F3 = 0x3C,
-    F4 = 0x3D,
-    F5 = 0x3E,
-    F6 = 0x3F,
-    F7 = 0x40,
-    F8 = 0x41,
-    F9 = 0x42,
-    F10 = 0x43,
-    F11 = 0x44,
-    F12 = 0x45,
-
-    PScreen = 0x46,
-    ScrollLock = 0x47,
-    Pause = 0x48,
-    Insert = 0x49,
-    Home = 0x4A,
-    PgUp = 0x4B,
-    Delete = 0x4C,
-    End = 0x4D,
-    PgDown = 0x4E,
-
-    Right = 0x4F,
-    Left = 0x50,
-    Down = 0x51,
-    Up = 0x52,
-
-    LCtrl = 0xE0,
-    LShift = 0xE1,
-    LAlt = 0xE2,
-    LGui = 0xE3,
-    RCtrl = 0xE4,
-    RShift = 0xE5,
-    RAlt = 0xE6,
-    RGui = 0xE7,
-
-    // Custom function keys
-    GraveEsc = 0xF0, // QK_GESC
-    Reset = 0xF3,    // QK_BOOT
+    No = 0x0000,
+    Trans = 0x0001, // _______ in QMK
+
+    // Letters
+    A = 0x0004,
+    B = 0x0005,
+    C = 0x0006,
+    D = 0x0007,
+    E = 0x0008,
+    F = 0x0009,
+    G = 0x000A,
+    H = 0x000B,
+    I = 0x000C,
+    J = 0x000D,
+    K = 0x000E,
+    L = 0x000F,
+    M = 0x0010,
+    N = 0x0011,
+    O = 0x0012,
+    P = 0x0013,
+    Q = 0x0014,
+    R = 0x0015,
+    S = 0x0016,
+    T = 0x0017,
+    U = 0x0018,
+    V = 0x0019,
+    W = 0x001A,
+    X = 0x001B,
+    Y = 0x001C,
+    Z = 0x001D,
+
+    // Digits
+    Num1 = 0x001E,
+    Num2 = 0x001F,
+    Num3 = 0x0020,
+    Num4 = 0x0021,
+    Num5 = 0x0022,
+    Num6 = 0x0023,
+    Num7 = 0x0024,
+    Num8 = 0x0025,
+    Num9 = 0x0026,
+    Num0 = 0x0027,
+
+    // Editing and punctuation
+    Enter = 0x0028,
+    Escape = 0x0029,
+    BSpace = 0x002A,
+    Tab = 0x002B,
+    Space = 0x002C,
+    Minus = 0x002D,
+    Equal = 0x002E,
+    LBracket = 0x002F,
+    RBracket = 0x0030,
+    BSlash = 0x0031,
+    NonUsHash = 0x0032,
+    Semicolon = 0x0033,
+    Quote = 0x0034,
+    Grave = 0x0035,
+    Comma = 0x0036,
+    Dot = 0x0037,
+    Slash = 0x0038,
+    Caps = 0x0039,
+
+    // F1-F12
+    F1 = 0x003A,
+    F2 = 0x003B,
+    F3 = 0x003C,
+    F4 = 0x003D,
+    F5 = 0x003E,
+    F6 = 0x003F,
+    F7 = 0x0040,
+    F8 = 0x0041,
+    F9 = 0x0042,
+    F10 = 0x0043,
+    F11 = 0x0044,
+    F12 = 0x0045,
+
+    // Navigation
+    PScreen = 0x0046,
+    ScrollLock = 0x0047,
+    Pause = 0x0048,
+    Insert = 0x0049,
+    Home = 0x004A,
+    PgUp = 0x004B,
+    Delete = 0x004C,
+    End = 0x004D,
+    PgDown = 0x004E,
+    Right = 0x004F,
+    Left = 0x0050,
+    Down = 0x0051,
+    Up = 0x0052,
+
+    // Keypad
+    NumLock = 0x0053,
+    KpSlash = 0x0054,
+    KpAsterisk = 0x0055,
+    KpMinus = 0x0056,
+    KpPlus = 0x0057,
+    KpEnter = 0x0058,
+    Kp1 = 0x0059,
+    Kp2 = 0x005A,
+    Kp3 = 0x005B,
+    Kp4 = 0x005C,
+    Kp5 = 0x005D,
+    Kp6 = 0x005E,
+    Kp7 = 0x005F,
+    Kp8 = 0x0060,
+    Kp9 = 0x0061,
+    Kp0 = 0x0062,
+    KpDot = 0x0063,
+
+    // International and extended
+    NonUsBSlash = 0x0064,
+    Application = 0x0065,
+    Power = 0x0066,
+    KpEqual = 0x0067,
+
+    // F13-F24
+    F13 = 0x0068,
+    F14 = 0x0069,
+    F15 = 0x006A,
+    F16 = 0x006B,
+    F17 = 0x006C,
+    F18 = 0x006D,
+    F19 = 0x006E,
+    F20 = 0x006F,
+    F21 = 0x0070,
+    F22 = 0x0071,
+    F23 = 0x0072,
+    F24 = 0x0073,
+
+    // Commands and media
+    Execute = 0x0074,
+    Help = 0x0075,
+    Menu = 0x0076,
+    Select = 0x0077,
+    Stop = 0x0078,
+    Again = 0x0079,
+    Undo = 0x007A,
+    Cut = 0x007B,
+    Copy = 0x007C,
+    Paste = 0x007D,
+    Find = 0x007E,
+    // Volume keys of the keyboard page, which most hosts ignore
+    KbMute = 0x007F,       // KC_KB_MUTE
+    KbVolumeUp = 0x0080,   // KC_KB_VOLUME_UP
+    KbVolumeDown = 0x0081, // KC_KB_VOLUME_DOWN
+    LockingCaps = 0x0082,
+    LockingNum = 0x0083,
+    LockingScroll = 0x0084,
+    KpComma = 0x0085,
+    KpEqualAs400 = 0x0086,
+
+    // International and languages
+    Int1 = 0x0087,
+    Int2 = 0x0088,
+    Int3 = 0x0089,
+    Int4 = 0x008A,
+    Int5 = 0x008B,
+    Int6 = 0x008C,
+    Int7 = 0x008D,
+    Int8 = 0x008E,
+    Int9 = 0x008F,
+    Lang1 = 0x0090,
+    Lang2 = 0x0091,
+    Lang3 = 0x0092,
+    Lang4 = 0x0093,
+    Lang5 = 0x0094,
+    Lang6 = 0x0095,
+    Lang7 = 0x0096,
+    Lang8 = 0x0097,
+    Lang9 = 0x0098,
+
+    // System
+    AltErase = 0x0099,
+    SysReq = 0x009A,
+    Cancel = 0x009B,
+    Clear = 0x009C,
+    Prior = 0x009D,
+    Return = 0x009E,
+    Separator = 0x009F,
+    Out = 0x00A0,
+    Oper = 0x00A1,
+    ClearAgain = 0x00A2,
+    CrSel = 0x00A3,
+    ExSel = 0x00A4,
+
+    // Media keys, sent as consumer control usages
+    Mute = 0x00A8,           // KC_MUTE
+    VolumeUp = 0x00A9,       // KC_VOLU
+    VolumeDown = 0x00AA,     // KC_VOLD
+    MediaNext = 0x00AB,      // KC_MNXT
+    MediaPrevious = 0x00AC,  // KC_MPRV
+    MediaStop = 0x00AD,      // KC_MSTP
+    MediaPlayPause = 0x00AE, // KC_MPLY
+
+    // Extended keypad
+    Kp00 = 0x00B0,
+    Kp000 = 0x00B1,
+    ThousandsSeparator = 0x00B2,
+    DecimalSeparator = 0x00B3,
+    CurrencyUnit = 0x00B4,
+    CurrencySubUnit = 0x00B5,
+    KpLParen = 0x00B6,
+    KpRParen = 0x00B7,
+    KpLBrace = 0x00B8,
+    KpRBrace = 0x00B9,
+    KpTab = 0x00BA,
+    KpBSpace = 0x00BB,
+    KpA = 0x00BC,
+    KpB = 0x00BD,
+    KpC = 0x00BE,
+    KpD = 0x00BF,
+    KpE = 0x00C0,
+    KpF = 0x00C1,
+    KpXor = 0x00C2,
+    KpCaret = 0x00C3,
+    KpPercent = 0x00C4,
+    KpLess = 0x00C5,
+    KpGreater = 0x00C6,
+    KpAmp = 0x00C7,
+    KpAmpAmp = 0x00C8,
+    KpPipe = 0x00C9,
+    KpPipePipe = 0x00CA,
+    KpColon = 0x00CB,
+    KpHash = 0x00CC,
+    KpSpace = 0x00CD,
+    KpAt = 0x00CE,
+    KpBang = 0x00CF,
+    KpMemStore = 0x00D0,
+    KpMemRecall = 0x00D1,
+    KpMemClear = 0x00D2,
+    KpMemAdd = 0x00D3,
+    KpMemSubtract = 0x00D4,
+    KpMemMultiply = 0x00D5,
+    KpMemDivide = 0x00D6,
+    KpPlusMinus = 0x00D7,
+    KpClear = 0x00D8,
+    KpClearEntry = 0x00D9,
+    KpBinary = 0x00DA,
+    KpOctal = 0x00DB,
+    KpDecimal = 0x00DC,
+    KpHexadecimal = 0x00DD,
+
+    // Modifiers
+    LCtrl = 0x00E0,
+    LShift = 0x00E1,
+    LAlt = 0x00E2,
+    LGui = 0x00E3,
+    RCtrl = 0x00E4,
+    RShift = 0x00E5,
+    RAlt = 0x00E6,
+    RGui = 0x00E7,
+
+    // Layers
+    MomentaryLayer1 = 0x5221, // MO(1)
+    MomentaryLayer2 = 0x5222, // MO(2)
+
+    // Backlight
+    BacklightToggle = 0x7802,    // BL_TOGG
+    BacklightDown = 0x7803,      // BL_DOWN
+    BacklightUp = 0x7804,        // BL_UP
+    BacklightStep = 0x7805,      // BL_STEP
+    BacklightBreathing = 0x7806, // BL_BRTG
+
+    // RGB underglow
+    RgbToggle = 0x7820,       // RGB_TOG
+    RgbModeNext = 0x7821,     // RGB_MOD
+    RgbModePrevious = 0x7822, // RGB_RMOD
+    RgbHueUp = 0x7823,        // RGB_HUI
+    RgbHueDown = 0x7824,      // RGB_HUD
+    RgbSatUp = 0x7825,        // RGB_SAI
+    RgbSatDown = 0x7826,      // RGB_SAD
+    RgbValUp = 0x7827,        // RGB_VAI
+    RgbValDown = 0x7828,      // RGB_VAD
+    RgbSpeedUp = 0x7829,      // RGB_SPI
+    RgbSpeedDown = 0x782A,    // RGB_SPD
+
+    // Firmware functions
+    Reset = 0x7C00,           // QK_BOOT
+    Diagnostics = 0x7C02,     // DB_TOGG
+    AutoShiftToggle = 0x7C15, // AS_TOGG
+    GraveEsc = 0x7C16,        // QK_GESC
+    Leader = 0x7C58,          // QK_LEAD
 }
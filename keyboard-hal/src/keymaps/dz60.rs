@@ -0,0 +1,132 @@
+//! Default keymap of the DZ60.
+use crate::keyboard_config::{MATRIX_COLS, MATRIX_ROWS, NUM_ENCODERS, NUM_LAYERS};
+use crate::keycodes::Keycode;
+
+pub const KEYMAPS: [[[Keycode; MATRIX_COLS]; MATRIX_ROWS]; NUM_LAYERS] = {
+    use crate::keycodes::prelude::*;
+
+    const BASE_LAYER: [[Keycode; MATRIX_COLS]; MATRIX_ROWS] = [
+        [
+            GraveEsc, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0, Minus, Equal,
+            BSpace, No,
+        ],
+        [
+            Tab, Q, W, E, R, T, Y, U, I, O, P, LBracket, RBracket, BSlash, No,
+        ],
+        [
+            Caps, A, S, D, F, G, H, J, K, L, Semicolon, Quote, Enter, No, No,
+        ],
+        [
+            LShift,
+            Z,
+            X,
+            C,
+            V,
+            B,
+            N,
+            M,
+            Comma,
+            Dot,
+            Slash,
+            RShift,
+            MomentaryLayer1,
+            No,
+            No,
+        ],
+        [
+            LCtrl, LGui, LAlt, Space, No, No, No, No, No, No, RAlt, RGui, RCtrl, No, No,
+        ],
+    ];
+
+    const FN_LAYER: [[Keycode; MATRIX_COLS]; MATRIX_ROWS] = [
+        [
+            Grave, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, Delete, No,
+        ],
+        [
+            Trans, Trans, Up, Trans, Trans, Trans, Trans, Trans, Trans, Trans, PScreen, ScrollLock,
+            Pause, Reset, No,
+        ],
+        [
+            Trans, Left, Down, Right, Trans, Trans, Trans, Trans, Trans, Insert, Home, PgUp, Trans,
+            No, No,
+        ],
+        [
+            Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, End, PgDown, Trans,
+            Trans, No, No,
+        ],
+        [
+            Trans,
+            Trans,
+            Trans,
+            Trans,
+            No,
+            No,
+            No,
+            No,
+            No,
+            No,
+            Trans,
+            MomentaryLayer2,
+            Trans,
+            No,
+            No,
+        ],
+    ];
+
+    const CTRL_LAYER: [[Keycode; MATRIX_COLS]; MATRIX_ROWS] = [
+        [
+            Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans,
+            Trans, Trans, No,
+        ],
+        [
+            Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans,
+            Trans, Trans, No,
+        ],
+        [
+            Trans,
+            RgbToggle,
+            RgbModeNext,
+            RgbHueUp,
+            RgbHueDown,
+            RgbSatUp,
+            RgbSatDown,
+            RgbValUp,
+            RgbValDown,
+            RgbSpeedUp,
+            RgbSpeedDown,
+            Trans,
+            Trans,
+            No,
+            No,
+        ],
+        [
+            Trans,
+            BacklightToggle,
+            BacklightDown,
+            BacklightUp,
+            BacklightBreathing,
+            BacklightStep,
+            Trans,
+            Trans,
+            Trans,
+            Trans,
+            Trans,
+            Trans,
+            Trans,
+            No,
+            No,
+        ],
+        [
+            Trans, Trans, Trans, Trans, No, No, No, No, No, No, Trans, Trans, Trans, No, No,
+        ],
+    ];
+
+    [BASE_LAYER, FN_LAYER, CTRL_LAYER]
+};
+
+/// Clockwise and counter-clockwise keycode of the encoder on each layer.
+pub const ENCODER_MAPS: [[[Keycode; 2]; NUM_ENCODERS]; NUM_LAYERS] = {
+    use crate::keycodes::prelude::*;
+
+    [[[VolumeUp, VolumeDown]], [[PgDown, PgUp]], [[Trans, Trans]]]
+};
@@ -0,0 +1,18 @@
+//! Default keymaps of the supported boards.
+
+#[cfg(any(feature = "dz60-rev2", feature = "dz60-rev3"))]
+mod dz60;
+#[cfg(any(feature = "dz60-rev2", feature = "dz60-rev3"))]
+pub use dz60::*;
+#[cfg(any(
+    feature = "planck-rev5",
+    feature = "promicro-handwired",
+    feature = "lets-split-rev2"
+))]
+mod ortho_4x12;
+#[cfg(any(
+    feature = "planck-rev5",
+    feature = "promicro-handwired",
+    feature = "lets-split-rev2"
+))]
+pub use ortho_4x12::*;
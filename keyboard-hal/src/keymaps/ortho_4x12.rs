@@ -0,0 +1,120 @@
+//! Default keymap of the 4x12 ortholinear boards (Planck-style, the handwired Pro Micro and the
+//! Let's Split).
+//!
+//! Lower (layer 1) holds numbers and F-keys, Raise (layer 2) the lighting controls.
+#[cfg(feature = "lets-split-rev2")]
+use crate::keyboard_config::LOCAL_ROWS;
+use crate::keyboard_config::{MATRIX_COLS, MATRIX_ROWS, NUM_ENCODERS, NUM_LAYERS};
+use crate::keycodes::Keycode;
+
+/// One layer of the 4x12 grid, as seen from the front.
+type Layer = [[Keycode; 12]; 4];
+
+const LAYERS: [Layer; NUM_LAYERS] = {
+    use crate::keycodes::prelude::*;
+
+    const BASE_LAYER: Layer = [
+        [Tab, Q, W, E, R, T, Y, U, I, O, P, BSpace],
+        [Escape, A, S, D, F, G, H, J, K, L, Semicolon, Quote],
+        [LShift, Z, X, C, V, B, N, M, Comma, Dot, Slash, Enter],
+        [
+            LCtrl,
+            LGui,
+            LAlt,
+            Grave,
+            MomentaryLayer1,
+            Space,
+            Space,
+            MomentaryLayer2,
+            Left,
+            Down,
+            Up,
+            Right,
+        ],
+    ];
+
+    const LOWER_LAYER: Layer = [
+        [
+            Grave, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0, Delete,
+        ],
+        [
+            Trans, F1, F2, F3, F4, F5, F6, Minus, Equal, LBracket, RBracket, BSlash,
+        ],
+        [
+            Trans, F7, F8, F9, F10, F11, F12, Trans, Trans, Home, End, Trans,
+        ],
+        [
+            Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Mute, VolumeDown, VolumeUp,
+            Trans,
+        ],
+    ];
+
+    const RAISE_LAYER: Layer = [
+        [
+            Trans,
+            BacklightToggle,
+            BacklightStep,
+            BacklightDown,
+            BacklightUp,
+            BacklightBreathing,
+            Trans,
+            Trans,
+            Trans,
+            Trans,
+            Trans,
+            Reset,
+        ],
+        [
+            Trans,
+            RgbToggle,
+            RgbModeNext,
+            RgbModePrevious,
+            RgbSpeedUp,
+            RgbSpeedDown,
+            Trans,
+            Trans,
+            Trans,
+            Trans,
+            Trans,
+            Trans,
+        ],
+        [
+            Trans, RgbHueUp, RgbHueDown, RgbSatUp, RgbSatDown, RgbValUp, RgbValDown, Trans, Trans,
+            PgUp, PgDown, Trans,
+        ],
+        [
+            Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans,
+        ],
+    ];
+
+    [BASE_LAYER, LOWER_LAYER, RAISE_LAYER]
+};
+
+#[cfg(not(feature = "lets-split-rev2"))]
+pub const KEYMAPS: [[[Keycode; MATRIX_COLS]; MATRIX_ROWS]; NUM_LAYERS] = LAYERS;
+
+/// The Let's Split has the left half in rows 0-3 and the right half in rows 4-7.  Both halves
+/// are wired the same way, so the columns of the right half are mirrored.
+#[cfg(feature = "lets-split-rev2")]
+pub const KEYMAPS: [[[Keycode; MATRIX_COLS]; MATRIX_ROWS]; NUM_LAYERS] = {
+    let mut keymaps = [[[Keycode::No; MATRIX_COLS]; MATRIX_ROWS]; NUM_LAYERS];
+    let mut layer = 0;
+    while layer < NUM_LAYERS {
+        let mut row = 0;
+        while row < LOCAL_ROWS {
+            let mut col = 0;
+            while col < MATRIX_COLS {
+                keymaps[layer][row][col] = LAYERS[layer][row][col];
+                keymaps[layer][LOCAL_ROWS + row][MATRIX_COLS - 1 - col] =
+                    LAYERS[layer][row][MATRIX_COLS + col];
+                col += 1;
+            }
+            row += 1;
+        }
+        layer += 1;
+    }
+    keymaps
+};
+
+/// These boards have no encoders.
+pub const ENCODER_MAPS: [[[Keycode; 2]; NUM_ENCODERS]; NUM_LAYERS] = [[], [], []];
@@ -1,113 +1,40 @@
 use crate::{
-    keyboard_config::{MATRIX_COLS, MATRIX_ROWS, NUM_LAYERS},
-    keycodes::Keycode,
+    encoder::Direction,
+    keyboard_config::{MATRIX_COLS, MATRIX_ROWS, NUM_ENCODERS, NUM_LAYERS},
+    keycodes::{Keycode, LayerAction},
+    keymaps::{ENCODER_MAPS, KEYMAPS},
 };
 
 pub struct Layers {
     keymaps: [[[Keycode; MATRIX_COLS]; MATRIX_ROWS]; NUM_LAYERS],
+    // Clockwise and counter-clockwise keycode for each encoder
+    encoder_maps: [[[Keycode; 2]; NUM_ENCODERS]; NUM_LAYERS],
     pub current_layer: usize,
+    /// Layer which is active when no momentary layer key is held, set by `TO` and `TG`.
+    base_layer: usize,
 }
 
 impl Layers {
     pub fn new() -> Self {
-        use Keycode::*;
-
-        // Create the layout matrices
-        let base_layer = [
-            [
-                GraveEsc, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0, Minus, Equal,
-                BSpace, No,
-            ],
-            [
-                Tab, Q, W, E, R, T, Y, U, I, O, P, LBracket, RBracket, BSlash, No,
-            ],
-            [
-                Caps, A, S, D, F, G, H, J, K, L, Semicolon, Quote, Enter, No, No,
-            ],
-            [
-                LShift,
-                Z,
-                X,
-                C,
-                V,
-                B,
-                N,
-                M,
-                Comma,
-                Dot,
-                Slash,
-                RShift,
-                MomentaryLayer1,
-                No,
-                No,
-            ],
-            [
-                LCtrl, LGui, LAlt, Space, No, No, No, No, No, No, RAlt, RGui, RCtrl, No, No,
-            ],
-        ];
-
-        let fn_layer = [
-            [
-                Grave, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, Delete, No,
-            ],
-            [
-                Trans, Trans, Up, Trans, Trans, Trans, Trans, Trans, Trans, Trans, PScreen,
-                ScrollLock, Pause, Reset, No,
-            ],
-            [
-                Trans, Left, Down, Right, Trans, Trans, Trans, Trans, Trans, Insert, Home, PgUp,
-                Trans, No, No,
-            ],
-            [
-                Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, End, PgDown, Trans,
-                Trans, No, No,
-            ],
-            [
-                Trans,
-                Trans,
-                Trans,
-                Trans,
-                No,
-                No,
-                No,
-                No,
-                No,
-                No,
-                Trans,
-                MomentaryLayer2,
-                Trans,
-                No,
-                No,
-            ],
-        ];
-
-        let ctrl_layer = [
-            [
-                Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans,
-                Trans, Trans, No,
-            ],
-            [
-                Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans,
-                Trans, Trans, No,
-            ],
-            [
-                Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans,
-                Trans, No, No,
-            ],
-            [
-                Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans, Trans,
-                Trans, No, No,
-            ],
-            [
-                Trans, Trans, Trans, Trans, No, No, No, No, No, No, Trans, Trans, Trans, No, No,
-            ],
-        ];
-
-        let keymaps = [base_layer, fn_layer, ctrl_layer];
+        Layers {
+            keymaps: KEYMAPS,
+            encoder_maps: ENCODER_MAPS,
+            current_layer: 0,
+            base_layer: 0,
+        }
+    }
 
+    /// Layers with a keymap other than the built-in one of the board, e.g. one generated by
+    /// `keyboard-hal-build`.
+    pub const fn from_keymaps(
+        keymaps: [[[Keycode; MATRIX_COLS]; MATRIX_ROWS]; NUM_LAYERS],
+        encoder_maps: [[[Keycode; 2]; NUM_ENCODERS]; NUM_LAYERS],
+    ) -> Self {
         Layers {
             keymaps,
+            encoder_maps,
             current_layer: 0,
+            base_layer: 0,
         }
     }
 
@@ -121,21 +48,41 @@ impl Layers {
         }
     }
 
-    pub fn handle_momentary_layer(&mut self, keycode: Keycode, pressed: bool) {
-        match keycode {
-            Keycode::MomentaryLayer1 => {
-                if pressed {
-                    self.current_layer = 1;
+    pub fn get_encoder_keycode(&self, layer: usize, index: usize, direction: Direction) -> Keycode {
+        let keycode = self.encoder_maps[layer][index][direction.index()];
+        if keycode == Keycode::Trans && layer > 0 {
+            self.get_encoder_keycode(layer - 1, index, direction)
+        } else {
+            keycode
+        }
+    }
+
+    /// Apply the layer actions (`MO`, `TG`, `TO`) of a keycode.  Layers beyond the keymap are
+    /// ignored.
+    pub fn handle_layer_keycode(&mut self, keycode: Keycode, pressed: bool) {
+        let Some(action) = keycode.layer_action() else {
+            return;
+        };
+
+        match action {
+            LayerAction::Momentary(layer) if (layer as usize) < NUM_LAYERS => {
+                self.current_layer = if pressed {
+                    layer as usize
                 } else {
-                    self.current_layer = 0;
-                }
+                    self.base_layer
+                };
             }
-            Keycode::MomentaryLayer2 => {
-                if pressed {
-                    self.current_layer = 2;
+            LayerAction::Toggle(layer) if (layer as usize) < NUM_LAYERS && pressed => {
+                self.base_layer = if self.base_layer == layer as usize {
+                    0
                 } else {
-                    self.current_layer = 0;
-                }
+                    layer as usize
+                };
+                self.current_layer = self.base_layer;
+            }
+            LayerAction::To(layer) if (layer as usize) < NUM_LAYERS && pressed => {
+                self.base_layer = layer as usize;
+                self.current_layer = self.base_layer;
             }
             _ => {}
         }
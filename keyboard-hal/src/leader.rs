@@ -0,0 +1,154 @@
+//! Leader key: [`Keycode::Leader`] followed by a short sequence of keys triggers an action.
+//!
+//! The sequences are static tables in the firmware:
+//!
+//! ```ignore
+//! use keyboard_hal::keycodes::prelude::*;
+//! use keyboard_hal::leader::{LeaderAction, LeaderConfig, LeaderSequence};
+//!
+//! static LEADER_SEQUENCES: &[LeaderSequence] = &[
+//!     // Leader, `e`, `m` types an email address
+//!     LeaderSequence {
+//!         keys: &[E, M],
+//!         action: LeaderAction::Tap(&[M, E, lsft(Num2), E, X, A, M, P, L, E, Dot, C, O, M]),
+//!     },
+//!     // Leader, `l` is handled by `KeyboardHooks::leader_action(0)`
+//!     LeaderSequence {
+//!         keys: &[L],
+//!         action: LeaderAction::User(0),
+//!     },
+//! ];
+//!
+//! let keyboard = Keyboard::new(matrix!(pins), usb_bus, eeprom)
+//!     .with_leader(LeaderConfig::new(LEADER_SEQUENCES));
+//! ```
+//!
+//! While the leader is active, key presses which would be sent to the host are collected into
+//! the sequence instead.  The sequence ends as soon as it matches and no longer sequence starts
+//! with it, when it cannot match anything anymore or when no key was pressed for the timeout.
+//!
+//! [`Keycode::Leader`]: crate::keycodes::Keycode::Leader
+use crate::keycodes::Keycode;
+
+/// Longest supported sequence after the leader key.
+pub const MAX_SEQUENCE: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LeaderAction {
+    /// Tap these keys one after the other.
+    Tap(&'static [Keycode]),
+    /// Call [`KeyboardHooks::leader_action`](crate::hooks::KeyboardHooks::leader_action) with
+    /// this id.
+    User(u16),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LeaderSequence {
+    /// Keys pressed after the leader key, at most [`MAX_SEQUENCE`].
+    pub keys: &'static [Keycode],
+    pub action: LeaderAction,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LeaderConfig {
+    /// How long to wait for the next key of a sequence, in milliseconds.
+    pub timeout: u16,
+    pub sequences: &'static [LeaderSequence],
+}
+
+impl LeaderConfig {
+    pub const fn new(sequences: &'static [LeaderSequence]) -> Self {
+        LeaderConfig {
+            timeout: 300,
+            sequences,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LeaderStep {
+    /// Waiting for more keys, or no sequence active.
+    Continue,
+    /// The sequence is over, with the action it matched, if any.
+    Finished(Option<LeaderAction>),
+}
+
+pub struct Leader {
+    config: LeaderConfig,
+    keys: [Keycode; MAX_SEQUENCE],
+    len: usize,
+    /// Time of the leader key or the last key of the sequence while active.
+    last_key: Option<u32>,
+}
+
+impl Leader {
+    pub fn new(config: LeaderConfig) -> Self {
+        Leader {
+            config,
+            keys: [Keycode::No; MAX_SEQUENCE],
+            len: 0,
+            last_key: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.last_key.is_some()
+    }
+
+    /// Start a new sequence, dropping one in progress.
+    pub fn start(&mut self, time: u32) {
+        self.len = 0;
+        self.last_key = Some(time);
+    }
+
+    /// Add a key to the active sequence.
+    pub fn push(&mut self, keycode: Keycode, time: u32) -> LeaderStep {
+        self.keys[self.len] = keycode;
+        self.len += 1;
+        self.last_key = Some(time);
+
+        let sequence = &self.keys[..self.len];
+        let mut candidates = self
+            .config
+            .sequences
+            .iter()
+            .filter(|s| s.keys.starts_with(sequence));
+        let Some(first) = candidates.next() else {
+            return self.finish(None);
+        };
+
+        let longer = first.keys.len() > self.len || candidates.any(|s| s.keys.len() > self.len);
+        if !longer || self.len == MAX_SEQUENCE {
+            let action = self.matched();
+            self.finish(action)
+        } else {
+            LeaderStep::Continue
+        }
+    }
+
+    /// End the sequence if no key was pressed for the timeout.
+    pub fn tick(&mut self, now: u32) -> LeaderStep {
+        match self.last_key {
+            Some(last) if now.wrapping_sub(last) >= self.config.timeout as u32 => {
+                let action = self.matched();
+                self.finish(action)
+            }
+            _ => LeaderStep::Continue,
+        }
+    }
+
+    fn matched(&self) -> Option<LeaderAction> {
+        let sequence = &self.keys[..self.len];
+        self.config
+            .sequences
+            .iter()
+            .find(|s| s.keys == sequence)
+            .map(|s| s.action)
+    }
+
+    fn finish(&mut self, action: Option<LeaderAction>) -> LeaderStep {
+        self.len = 0;
+        self.last_key = None;
+        LeaderStep::Finished(action)
+    }
+}
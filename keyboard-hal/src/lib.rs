@@ -1,9 +1,28 @@
 #![no_std]
+#![cfg_attr(target_arch = "avr", feature(asm_experimental_arch))]
 
+#[cfg(not(feature = "board-selected"))]
+compile_error!(
+    "This crate requires you to specify your target keyboard as a feature.
+
+    Please select one of the following
+
+    * dz60-rev2
+    * dz60-rev3
+    * planck-rev5
+    * promicro-handwired
+    * lets-split-rev2
+    "
+);
+
+#[cfg(all(feature = "board-selected", feature = "console"))]
+use cdc_acm::CdcAcmClass;
 pub use usb_device::prelude::*;
-use usb_keyboard::UsbKeyboard;
+#[cfg(feature = "board-selected")]
+use usb_keyboard::{Console, UsbKeyboard};
+#[cfg(feature = "board-selected")]
 use usbd_hid::{
-    descriptor::{KeyboardReport, SerializedDescriptor},
+    descriptor::{KeyboardReport, MediaKeyboardReport, SerializedDescriptor},
     hid_class::HIDClass,
 };
 
@@ -19,91 +38,434 @@ pub use usb_device::device::{
 pub use usb_device::LangID;
 pub use usb_device::UsbError;
 
-use keyboard_config::{MATRIX_COLS, MATRIX_ROWS};
+#[cfg(feature = "board-selected")]
+use auto_shift::AutoShiftConfig;
+#[cfg(feature = "board-selected")]
+use backlight::Backlight;
+#[cfg(feature = "board-selected")]
+use diagnostics::Diagnostics;
+#[cfg(feature = "board-selected")]
+use eeconfig::EeConfig;
+#[cfg(feature = "board-selected")]
+use embedded_hal::delay::DelayNs;
+#[cfg(feature = "board-selected")]
+pub use hooks::KeyboardHooks;
+#[cfg(feature = "board-selected")]
+use indicators::LayerIndicators;
+#[cfg(feature = "board-selected")]
+use keyboard_config::{
+    DEVICE_RELEASE, LOCAL_ROWS, MANUFACTURER, MATRIX_COLS, MATRIX_ROWS, PRODUCT, USB_PID, USB_VID,
+};
+#[cfg(feature = "board-selected")]
 use layers::Layers;
-use matrix::Matrix;
-pub use port::pcb1::Pins;
+#[cfg(feature = "board-selected")]
+use leader::LeaderConfig;
+#[cfg(feature = "board-selected")]
+pub use matrix::Matrix;
+#[cfg(feature = "board-selected")]
+use pipeline::{Output, Pipeline, Report};
+#[cfg(feature = "board-selected")]
+pub use port::{BacklightPin, BacklightTimer, Pins, RgbPin};
+#[cfg(feature = "board-selected")]
+use rgb::RgbLight;
+#[cfg(feature = "split")]
+use split::{Role, Split, SyncState};
+#[cfg(feature = "board-selected")]
 pub use usb::UsbBus;
 
+/// Clock frequency of the keyboard controllers.
+pub type DefaultClock = avr_hal_generic::clock::MHz16;
+
+/// Delay between setting up the matrix and the bootmagic scan.
+#[cfg(feature = "board-selected")]
+const BOOTMAGIC_SETTLE_MS: u32 = 10;
+
+/// Size of the bulk endpoints of the serial console.
+#[cfg(all(feature = "board-selected", feature = "console"))]
+const CONSOLE_PACKET_SIZE: u16 = 64;
+
+#[cfg(feature = "board-selected")]
+pub mod auto_shift;
+#[cfg(feature = "board-selected")]
+pub mod backlight;
+#[cfg(feature = "board-selected")]
+pub mod bootloader;
+#[cfg(feature = "board-selected")]
+pub mod bootmagic;
+#[cfg(feature = "board-selected")]
+pub mod cdc_acm;
+#[cfg(feature = "board-selected")]
+pub mod diagnostics;
+#[cfg(feature = "board-selected")]
+pub mod eeconfig;
+#[cfg(feature = "board-selected")]
+pub mod encoder;
+#[cfg(feature = "board-selected")]
+pub mod hooks;
+#[cfg(feature = "board-selected")]
+pub mod indicators;
+#[cfg(feature = "board-selected")]
 pub mod keyboard_config;
+#[cfg(feature = "board-selected")]
 pub mod keycodes;
+#[cfg(feature = "board-selected")]
+pub mod keymaps;
+#[cfg(feature = "board-selected")]
 pub mod layers;
+#[cfg(feature = "board-selected")]
+pub mod leader;
+#[cfg(feature = "board-selected")]
 pub mod matrix;
+#[cfg(feature = "board-selected")]
+pub mod pipeline;
+#[cfg(feature = "board-selected")]
 pub mod port;
+#[cfg(feature = "board-selected")]
+pub mod rgb;
+#[cfg(feature = "split")]
+pub mod split;
+#[cfg(feature = "board-selected")]
+pub mod timer;
+#[cfg(feature = "board-selected")]
 pub mod usb;
+#[cfg(feature = "board-selected")]
 pub mod usb_keyboard;
+#[cfg(feature = "board-selected")]
+pub mod ws2812;
 
-pub struct Keyboard<B: usb_device::bus::UsbBus + 'static> {
+#[cfg(feature = "board-selected")]
+pub struct Keyboard<B: usb_device::bus::UsbBus + 'static, H: KeyboardHooks = ()> {
     matrix: Matrix,
-    layers: Layers,
+    pipeline: Pipeline<H>,
     usb_keyboard: UsbKeyboard<B>,
+    eeconfig: EeConfig,
+    backlight: Option<Backlight<BacklightTimer, BacklightPin>>,
+    rgb: Option<RgbLight<RgbPin>>,
+    layer_indicators: Option<LayerIndicators>,
+    #[cfg(feature = "split")]
+    split: Option<Split>,
+    host_leds: u8,
+    diagnostics: Diagnostics,
 }
 
+#[cfg(feature = "board-selected")]
 impl<B: usb_device::bus::UsbBus + 'static> Keyboard<B> {
-    pub fn new(pins: Pins, usb_bus: &'static UsbBusAllocator<B>) -> Self {
-        // Configure row pins as outputs
-        let rows = [
-            pins.row0.into_output().downgrade(),
-            pins.row1.into_output().downgrade(),
-            pins.row2.into_output().downgrade(),
-            pins.row3.into_output().downgrade(),
-            pins.row4.into_output().downgrade(),
-        ];
-
-        // Configure column pins as pull-up inputs
-        let cols = [
-            pins.col0.into_pull_up_input().downgrade().forget_imode(),
-            pins.col1.into_pull_up_input().downgrade().forget_imode(),
-            pins.col2.into_pull_up_input().downgrade().forget_imode(),
-            pins.col3.into_pull_up_input().downgrade().forget_imode(),
-            pins.col4.into_pull_up_input().downgrade().forget_imode(),
-            pins.col5.into_pull_up_input().downgrade().forget_imode(),
-            pins.col6.into_pull_up_input().downgrade().forget_imode(),
-            pins.col7.into_pull_up_input().downgrade().forget_imode(),
-            pins.col8.into_pull_up_input().downgrade().forget_imode(),
-            pins.col9.into_pull_up_input().downgrade().forget_imode(),
-            pins.col10.into_pull_up_input().downgrade().forget_imode(),
-            pins.col11.into_pull_up_input().downgrade().forget_imode(),
-            pins.col12.into_pull_up_input().downgrade().forget_imode(),
-            pins.col13.into_pull_up_input().downgrade().forget_imode(),
-            pins.col14.into_pull_up_input().downgrade().forget_imode(),
-        ];
-
-        // Initialize the matrix with the configured pins
-        let matrix = Matrix::new(rows, cols);
-        let layers = Layers::new();
+    /// Set up the keyboard.  Before anything else, the matrix is scanned once for
+    /// [`bootmagic`] keys, which may jump to the bootloader right away.
+    pub fn new(
+        mut matrix: Matrix,
+        usb_bus: &'static UsbBusAllocator<B>,
+        eeprom: hal::Eeprom,
+    ) -> Self {
+        let mut eeconfig = EeConfig::new(eeprom);
+
+        // Give the column pull-ups time to settle
+        hal::delay::Delay::<DefaultClock>::new().delay_ms(BOOTMAGIC_SETTLE_MS);
+        match bootmagic::check(&matrix.scan()) {
+            bootmagic::Action::None => {}
+            bootmagic::Action::Bootloader => bootloader::jump(),
+            bootmagic::Action::ClearEeprom => eeconfig.reset(),
+        }
 
         let hid_class = HIDClass::new(&usb_bus, KeyboardReport::desc(), 1);
-        let usb_device = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x445A, 0x2260)).build();
+        let consumer_class = HIDClass::new(&usb_bus, MediaKeyboardReport::desc(), 1);
+        #[cfg(feature = "console")]
+        let serial = Some(CdcAcmClass::new(usb_bus, CONSOLE_PACKET_SIZE));
+        #[cfg(not(feature = "console"))]
+        let serial = None;
+
+        let mut usb_device = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(USB_VID, USB_PID));
+        if serial.is_some() {
+            // The CDC-ACM function spans two interfaces, which needs an association descriptor
+            usb_device = usb_device.composite_with_iads();
+        }
+        let usb_device = usb_device
+            .strings(&[StringDescriptors::new(LangID::EN)
+                .manufacturer(MANUFACTURER)
+                .product(PRODUCT)])
+            .unwrap()
+            .device_release(DEVICE_RELEASE)
+            .build();
 
         Keyboard {
             matrix,
-            layers,
-            usb_keyboard: UsbKeyboard::new(usb_device, hid_class),
+            pipeline: Pipeline::new(Layers::new()),
+            usb_keyboard: UsbKeyboard::new(usb_device, hid_class, consumer_class, serial),
+            eeconfig,
+            backlight: None,
+            rgb: None,
+            layer_indicators: None,
+            #[cfg(feature = "split")]
+            split: None,
+            host_leds: 0,
+            diagnostics: Diagnostics::new(),
         }
     }
+}
+
+#[cfg(feature = "board-selected")]
+impl<B: usb_device::bus::UsbBus + 'static, H: KeyboardHooks> Keyboard<B, H> {
+    /// Add behaviour to this keyboard through the given [`KeyboardHooks`].
+    pub fn with_hooks<H2: KeyboardHooks>(self, hooks: H2) -> Keyboard<B, H2> {
+        Keyboard {
+            matrix: self.matrix,
+            pipeline: self.pipeline.with_hooks(hooks),
+            usb_keyboard: self.usb_keyboard,
+            eeconfig: self.eeconfig,
+            backlight: self.backlight,
+            rgb: self.rgb,
+            layer_indicators: self.layer_indicators,
+            #[cfg(feature = "split")]
+            split: self.split,
+            host_leds: self.host_leds,
+            diagnostics: self.diagnostics,
+        }
+    }
+
+    pub fn hooks(&self) -> &H {
+        self.pipeline.hooks()
+    }
+
+    pub fn hooks_mut(&mut self) -> &mut H {
+        self.pipeline.hooks_mut()
+    }
+
+    /// Use the given layers instead of the built-in keymap of the board.
+    pub fn with_layers(mut self, layers: Layers) -> Self {
+        *self.pipeline.layers_mut() = layers;
+        self
+    }
+
+    /// Enable auto-shift, see [`auto_shift`].
+    pub fn with_auto_shift(mut self, config: AutoShiftConfig) -> Self {
+        self.pipeline = self.pipeline.with_auto_shift(config);
+        self
+    }
+
+    /// Enable the leader key, see [`leader`].
+    pub fn with_leader(mut self, config: LeaderConfig) -> Self {
+        self.pipeline = self.pipeline.with_leader(config);
+        self
+    }
+
+    /// Drive the backlight from this keyboard, restoring the level stored in the EEPROM.
+    pub fn with_backlight(
+        mut self,
+        mut backlight: Backlight<BacklightTimer, BacklightPin>,
+    ) -> Self {
+        backlight.restore(self.eeconfig.read_backlight());
+        self.backlight = Some(backlight);
+        self
+    }
+
+    /// Drive the RGB underglow from this keyboard, restoring the settings stored in the EEPROM.
+    pub fn with_rgb(mut self, mut rgb: RgbLight<RgbPin>) -> Self {
+        rgb.restore(self.eeconfig.read_rgb());
+        self.rgb = Some(rgb);
+        self
+    }
+
+    /// Show the active layer on LEDs, the backlight or the RGB underglow, see [`indicators`].
+    pub fn with_layer_indicators(mut self, indicators: LayerIndicators) -> Self {
+        self.layer_indicators = Some(indicators);
+        self
+    }
+
+    /// Run this controller as one half of a split keyboard.  The half connected to USB merges
+    /// both halves, the other one only reports its keys and mirrors the layer and LED state.
+    #[cfg(feature = "split")]
+    pub fn with_split(mut self, split: Split) -> Self {
+        self.split = Some(split);
+        self
+    }
+
+    /// Start with matrix diagnostics turned on, see [`diagnostics`].
+    pub fn with_diagnostics(mut self) -> Self {
+        if !self.diagnostics.is_active() {
+            self.diagnostics.toggle();
+        }
+        self
+    }
+
+    /// The serial console as a [`ufmt::uWrite`] target for logging:
+    ///
+    /// ```ignore
+    /// ufmt::uwriteln!(keyboard.console(), "layer {}", layer).ok();
+    /// ```
+    ///
+    /// Writes wait until the host picked up the data, but only while a terminal has the console
+    /// open and for a few milliseconds at most, see [`UsbKeyboard::write_serial`].  Without the
+    /// `console` feature everything written is dropped.
+    pub fn console(&mut self) -> Console<'_, B> {
+        self.usb_keyboard.console()
+    }
+
+    /// Lock key LEDs as set by the host, see [`UsbKeyboard::leds`].
+    pub fn host_leds(&self) -> u8 {
+        self.host_leds
+    }
 
     pub fn poll(&mut self) {
-        let new_state = self.matrix.scan();
-        for row in 0..MATRIX_ROWS {
-            for col in 0..MATRIX_COLS {
-                if new_state[row][col] != self.matrix.last_state[row][col] {
-                    let keycode = self.layers.get_keycode(self.layers.current_layer, row, col);
-
-                    // Handle layer switching first
-                    self.layers
-                        .handle_momentary_layer(keycode, new_state[row][col]);
-
-                    // Only send regular keycodes to USB
-                    if keycode as u8 <= 0xE7 {
-                        // Regular HID keycodes only
-                        self.usb_keyboard
-                            .handle_keypress(keycode as u8, new_state[row][col]);
-                    }
+        let local = self.matrix.scan();
+        self.pipeline.hooks_mut().matrix_scan();
+        let mut new_state = [[false; MATRIX_COLS]; MATRIX_ROWS];
+
+        #[cfg(feature = "split")]
+        match self.split.as_mut() {
+            Some(split) if split.role() == Role::Slave => {
+                if let Some(sync) = split.serve(&local) {
+                    self.apply_sync(sync);
                 }
+                self.lighting_task();
+                return;
+            }
+            Some(split) => {
+                let sync = SyncState {
+                    layer: self.pipeline.layers().current_layer as u8,
+                    host_leds: self.host_leds,
+                    backlight: self
+                        .backlight
+                        .as_ref()
+                        .map(|b| b.config())
+                        .unwrap_or_default(),
+                    rgb: self
+                        .rgb
+                        .as_ref()
+                        .map(|rgb| rgb.config())
+                        .unwrap_or_default(),
+                };
+                split.merge(&local, &sync, &mut new_state);
+            }
+            None => new_state[..LOCAL_ROWS].copy_from_slice(&local),
+        }
+        #[cfg(not(feature = "split"))]
+        new_state[..LOCAL_ROWS].copy_from_slice(&local);
+
+        self.usb_keyboard.poll();
+        self.set_host_leds(self.usb_keyboard.leds());
+
+        let now = timer::millis();
+        let mut hardware = Hardware {
+            usb_keyboard: &mut self.usb_keyboard,
+            eeconfig: &mut self.eeconfig,
+            backlight: &mut self.backlight,
+            rgb: &mut self.rgb,
+            diagnostics: &mut self.diagnostics,
+        };
+        self.pipeline.tick(now, &mut hardware);
+        self.pipeline.process_matrix(&new_state, now, &mut hardware);
+
+        encoder::scan();
+        while let Some((index, direction)) = encoder::take_step() {
+            self.pipeline
+                .process_encoder(index, direction, now, &mut hardware);
+        }
+
+        let _ = self
+            .diagnostics
+            .scan(&new_state, now, &mut self.usb_keyboard.console());
+
+        self.lighting_task();
+    }
+
+    /// Mirror the state of the master half on a split slave.
+    #[cfg(feature = "split")]
+    fn apply_sync(&mut self, sync: SyncState) {
+        if (sync.layer as usize) < keyboard_config::NUM_LAYERS {
+            self.pipeline.set_layer(sync.layer as usize);
+        }
+        self.set_host_leds(sync.host_leds);
+
+        if let Some(backlight) = self.backlight.as_mut() {
+            if backlight.config() != sync.backlight {
+                backlight.restore(sync.backlight);
+            }
+        }
+        if let Some(rgb) = self.rgb.as_mut() {
+            if rgb.config() != sync.rgb {
+                rgb.restore(sync.rgb);
+            }
+        }
+    }
+
+    fn set_host_leds(&mut self, leds: u8) {
+        if self.host_leds != leds {
+            self.host_leds = leds;
+            self.pipeline.hooks_mut().led_state_changed(leds);
+        }
+    }
+
+    fn lighting_task(&mut self) {
+        if let Some(indicators) = self.layer_indicators.as_mut() {
+            indicators.update(
+                self.pipeline.layers().current_layer,
+                self.backlight.as_mut(),
+                self.rgb.as_mut(),
+            );
+        }
+
+        let now = timer::millis();
+        if let Some(backlight) = self.backlight.as_mut() {
+            backlight.task(now);
+        }
+        if let Some(rgb) = self.rgb.as_mut() {
+            rgb.task(now);
+        }
+    }
+}
+
+/// Connects the [`Pipeline`] to the USB keyboard and the lighting.
+#[cfg(feature = "board-selected")]
+struct Hardware<'a, B: usb_device::bus::UsbBus + 'static> {
+    usb_keyboard: &'a mut UsbKeyboard<B>,
+    eeconfig: &'a mut EeConfig,
+    backlight: &'a mut Option<Backlight<BacklightTimer, BacklightPin>>,
+    rgb: &'a mut Option<RgbLight<RgbPin>>,
+    diagnostics: &'a mut Diagnostics,
+}
+
+#[cfg(feature = "board-selected")]
+impl<B: usb_device::bus::UsbBus + 'static> Output for Hardware<'_, B> {
+    fn send_report(&mut self, report: &Report) {
+        self.usb_keyboard.send_report(&report.to_hid());
+    }
+
+    fn send_consumer_report(&mut self, usage: u16) {
+        self.usb_keyboard
+            .send_consumer_report(&MediaKeyboardReport { usage_id: usage });
+    }
+
+    fn process_keycode(&mut self, keycode: keycodes::Keycode, pressed: bool, time: u32) {
+        if !pressed {
+            return;
+        }
+
+        if keycode == keycodes::Keycode::Reset {
+            bootloader::jump();
+        }
+
+        if keycode == keycodes::Keycode::Diagnostics {
+            self.diagnostics.toggle();
+            let state = if self.diagnostics.is_active() {
+                "on"
+            } else {
+                "off"
+            };
+            let _ = ufmt::uwriteln!(self.usb_keyboard.console(), "diagnostics {}", state);
+        }
+
+        if let Some(backlight) = self.backlight.as_mut() {
+            if backlight.process_keycode(keycode) {
+                self.eeconfig.write_backlight(backlight.config());
+            }
+        }
+
+        if let Some(rgb) = self.rgb.as_mut() {
+            rgb.keypress(time);
+            if rgb.process_keycode(keycode) {
+                self.eeconfig.write_rgb(rgb.config());
             }
         }
-        self.matrix.last_state = new_state;
     }
 }
 
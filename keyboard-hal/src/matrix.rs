@@ -1,29 +1,23 @@
-use crate::keyboard_config::{MATRIX_COLS, MATRIX_ROWS};
+use crate::keyboard_config::{LOCAL_ROWS, MATRIX_COLS};
 use atmega_hal::port::{
     mode::{AnyInput, Input, Output},
     Pin,
 };
 
 pub struct Matrix {
-    pub last_state: [[bool; MATRIX_COLS]; MATRIX_ROWS],
-    rows: [Pin<Output>; MATRIX_ROWS],
+    rows: [Pin<Output>; LOCAL_ROWS],
     cols: [Pin<Input<AnyInput>>; MATRIX_COLS],
 }
 
 impl Matrix {
-    pub fn new(
-        rows: [Pin<Output>; MATRIX_ROWS],
-        cols: [Pin<Input<AnyInput>>; MATRIX_COLS],
-    ) -> Self {
-        Matrix {
-            last_state: [[false; MATRIX_COLS]; MATRIX_ROWS],
-            rows,
-            cols,
-        }
+    pub fn new(rows: [Pin<Output>; LOCAL_ROWS], cols: [Pin<Input<AnyInput>>; MATRIX_COLS]) -> Self {
+        Matrix { rows, cols }
     }
 
-    pub fn scan(&mut self) -> [[bool; MATRIX_COLS]; MATRIX_ROWS] {
-        let mut new_state = [[false; MATRIX_COLS]; MATRIX_ROWS];
+    /// Scan the rows wired to this controller, which on split keyboards are only those of one
+    /// half.
+    pub fn scan(&mut self) -> [[bool; MATRIX_COLS]; LOCAL_ROWS] {
+        let mut new_state = [[false; MATRIX_COLS]; LOCAL_ROWS];
 
         for (row_idx, row_pin) in self.rows.iter_mut().enumerate() {
             row_pin.set_low();
@@ -0,0 +1,493 @@
+//! Hardware independent part of the keyboard: turns key events into HID reports.
+//!
+//! Nothing in here touches the MCU, so the whole path from matrix state through layer resolution
+//! to the report sent to the host can be driven from tests on the host (see `tests/`).
+//!
+//! [`Keyboard`](crate::Keyboard) feeds the matrix scans into a [`Pipeline`] and implements
+//! [`Output`] on top of the USB keyboard and the lighting.
+use crate::auto_shift::{AutoShift, AutoShiftConfig, Pending, Released};
+use crate::encoder::Direction;
+use crate::hooks::KeyboardHooks;
+use crate::keyboard_config::{MATRIX_COLS, MATRIX_ROWS};
+use crate::keycodes::Keycode;
+use crate::layers::Layers;
+use crate::leader::{Leader, LeaderAction, LeaderConfig, LeaderStep};
+use usbd_hid::descriptor::KeyboardReport;
+
+pub type MatrixState = [[bool; MATRIX_COLS]; MATRIX_ROWS];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyEvent {
+    pub row: usize,
+    pub col: usize,
+    pub pressed: bool,
+    /// Time of the event in milliseconds.
+    pub time: u32,
+}
+
+impl KeyEvent {
+    pub const fn press(row: usize, col: usize, time: u32) -> Self {
+        KeyEvent {
+            row,
+            col,
+            pressed: true,
+            time,
+        }
+    }
+
+    pub const fn release(row: usize, col: usize, time: u32) -> Self {
+        KeyEvent {
+            row,
+            col,
+            pressed: false,
+            time,
+        }
+    }
+}
+
+/// Boot protocol keyboard report: modifier bits and up to six pressed keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Report {
+    pub modifier: u8,
+    pub keycodes: [u8; 6],
+}
+
+impl Report {
+    /// Add a key to the report.  Returns `false` if the report did not change, e.g. because all
+    /// six slots are in use.
+    pub fn press(&mut self, keycode: u8) -> bool {
+        if let Some(bit) = modifier_bit(keycode) {
+            let changed = self.modifier & bit == 0;
+            self.modifier |= bit;
+            return changed;
+        }
+
+        if self.keycodes.contains(&keycode) {
+            return false;
+        }
+        match self.keycodes.iter_mut().find(|slot| **slot == 0) {
+            Some(slot) => {
+                *slot = keycode;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a key from the report.  Returns `false` if it was not part of it.
+    pub fn release(&mut self, keycode: u8) -> bool {
+        if let Some(bit) = modifier_bit(keycode) {
+            let changed = self.modifier & bit != 0;
+            self.modifier &= !bit;
+            return changed;
+        }
+
+        match self.keycodes.iter().position(|&slot| slot == keycode) {
+            Some(index) => {
+                // Keep the held keys in order of their press
+                self.keycodes.copy_within(index + 1.., index);
+                self.keycodes[5] = 0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add modifier bits to the report.  Returns `false` if they were all set already.
+    pub fn press_modifiers(&mut self, bits: u8) -> bool {
+        let changed = self.modifier & bits != bits;
+        self.modifier |= bits;
+        changed
+    }
+
+    /// Remove modifier bits from the report.  Returns `false` if none of them was set.
+    pub fn release_modifiers(&mut self, bits: u8) -> bool {
+        let changed = self.modifier & bits != 0;
+        self.modifier &= !bits;
+        changed
+    }
+
+    pub fn is_pressed(&self, keycode: u8) -> bool {
+        match modifier_bit(keycode) {
+            Some(bit) => self.modifier & bit != 0,
+            None => self.keycodes.contains(&keycode),
+        }
+    }
+
+    pub fn to_hid(self) -> KeyboardReport {
+        KeyboardReport {
+            modifier: self.modifier,
+            reserved: 0,
+            leds: 0,
+            keycodes: self.keycodes,
+        }
+    }
+}
+
+/// Shift and GUI bits of both sides in [`Report::modifier`].
+const SHIFT_OR_GUI: u8 = 0b1010_1010;
+
+fn modifier_bit(keycode: u8) -> Option<u8> {
+    match keycode {
+        0xE0..=0xE7 => Some(1 << (keycode - 0xE0)),
+        _ => None,
+    }
+}
+
+/// Where the results of the pipeline go.
+pub trait Output {
+    /// Send a changed report to the host.
+    fn send_report(&mut self, report: &Report);
+
+    /// Send the pressed media key as consumer control usage to the host, `0` when released.
+    fn send_consumer_report(&mut self, usage: u16);
+
+    /// Called for every resolved key press and release, including keycodes which are not sent to
+    /// the host (e.g. lighting controls).
+    fn process_keycode(&mut self, keycode: Keycode, pressed: bool, time: u32) {
+        let _ = (keycode, pressed, time);
+    }
+}
+
+pub struct Pipeline<H: KeyboardHooks = ()> {
+    layers: Layers,
+    hooks: H,
+    state: MatrixState,
+    /// Keycode each pressed key resolved to, so it is released even if the layer changed since.
+    active: [[Keycode; MATRIX_COLS]; MATRIX_ROWS],
+    report: Report,
+    /// Consumer control usage of the pressed media key, `0` if none.
+    consumer: u16,
+    /// What the last press of [`Keycode::GraveEsc`] sent, so its release matches.
+    grave_esc: Keycode,
+    auto_shift: Option<AutoShift>,
+    leader: Option<Leader>,
+}
+
+impl Pipeline {
+    pub fn new(layers: Layers) -> Self {
+        Pipeline {
+            layers,
+            hooks: (),
+            state: [[false; MATRIX_COLS]; MATRIX_ROWS],
+            active: [[Keycode::No; MATRIX_COLS]; MATRIX_ROWS],
+            report: Report::default(),
+            consumer: 0,
+            grave_esc: Keycode::Escape,
+            auto_shift: None,
+            leader: None,
+        }
+    }
+}
+
+impl<H: KeyboardHooks> Pipeline<H> {
+    /// Replace the user hooks of this pipeline.
+    pub fn with_hooks<H2: KeyboardHooks>(self, hooks: H2) -> Pipeline<H2> {
+        Pipeline {
+            layers: self.layers,
+            hooks,
+            state: self.state,
+            active: self.active,
+            report: self.report,
+            consumer: self.consumer,
+            grave_esc: self.grave_esc,
+            auto_shift: self.auto_shift,
+            leader: self.leader,
+        }
+    }
+
+    /// Enable auto-shift, see [`auto_shift`](crate::auto_shift).
+    pub fn with_auto_shift(mut self, config: AutoShiftConfig) -> Self {
+        self.auto_shift = Some(AutoShift::new(config));
+        self
+    }
+
+    pub fn auto_shift(&self) -> Option<&AutoShift> {
+        self.auto_shift.as_ref()
+    }
+
+    /// Enable the leader key, see [`leader`](crate::leader).
+    pub fn with_leader(mut self, config: LeaderConfig) -> Self {
+        self.leader = Some(Leader::new(config));
+        self
+    }
+
+    pub fn leader(&self) -> Option<&Leader> {
+        self.leader.as_ref()
+    }
+
+    pub fn layers(&self) -> &Layers {
+        &self.layers
+    }
+
+    pub fn layers_mut(&mut self) -> &mut Layers {
+        &mut self.layers
+    }
+
+    pub fn hooks(&self) -> &H {
+        &self.hooks
+    }
+
+    pub fn hooks_mut(&mut self) -> &mut H {
+        &mut self.hooks
+    }
+
+    /// Switch to the given layer, notifying the hooks if it changed.
+    pub fn set_layer(&mut self, layer: usize) {
+        if self.layers.current_layer != layer {
+            self.layers.current_layer = layer;
+            self.hooks.layer_state_changed(layer);
+        }
+    }
+
+    /// Matrix state as of the last processed event.
+    pub fn state(&self) -> &MatrixState {
+        &self.state
+    }
+
+    /// The report last sent to the host.
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    /// Resolve time dependent state like the auto-shift timeout.  Must be called regularly with
+    /// the current time.
+    pub fn tick<O: Output>(&mut self, now: u32, out: &mut O) {
+        if let Some(pending) = self
+            .auto_shift
+            .as_mut()
+            .and_then(|auto_shift| auto_shift.take_expired(now))
+        {
+            self.tap(pending.keycode, true, now, out);
+        }
+        if let Some(LeaderStep::Finished(action)) = self.leader.as_mut().map(|l| l.tick(now)) {
+            self.run_leader_action(action, now, out);
+        }
+    }
+
+    /// Process all keys which changed between the previous and the given matrix scan.
+    pub fn process_matrix<O: Output>(&mut self, state: &MatrixState, time: u32, out: &mut O) {
+        for row in 0..MATRIX_ROWS {
+            for col in 0..MATRIX_COLS {
+                if state[row][col] != self.state[row][col] {
+                    let event = KeyEvent {
+                        row,
+                        col,
+                        pressed: state[row][col],
+                        time,
+                    };
+                    self.process_event(event, out);
+                }
+            }
+        }
+    }
+
+    pub fn process_event<O: Output>(&mut self, event: KeyEvent, out: &mut O) {
+        let KeyEvent {
+            row,
+            col,
+            pressed,
+            time,
+        } = event;
+        if self.state[row][col] == pressed {
+            return;
+        }
+        self.state[row][col] = pressed;
+
+        let keycode = if pressed {
+            let keycode = self.layers.get_keycode(self.layers.current_layer, row, col);
+            self.active[row][col] = keycode;
+            keycode
+        } else {
+            core::mem::replace(&mut self.active[row][col], Keycode::No)
+        };
+
+        if !self.hooks.process_record(keycode, pressed, row, col) {
+            return;
+        }
+        if pressed && self.process_leader(keycode, time, out) {
+            return;
+        }
+        if self.auto_shift.is_some() && self.process_auto_shift(event, keycode, out) {
+            return;
+        }
+        self.process_keycode(keycode, pressed, time, out);
+    }
+
+    /// Tap the keycode mapped to an encoder step.
+    pub fn process_encoder<O: Output>(
+        &mut self,
+        index: usize,
+        direction: Direction,
+        time: u32,
+        out: &mut O,
+    ) {
+        let keycode = self
+            .layers
+            .get_encoder_keycode(self.layers.current_layer, index, direction);
+        self.process_keycode(keycode, true, time, out);
+        self.process_keycode(keycode, false, time, out);
+    }
+
+    /// Collect key presses into an active leader sequence.  Returns `true` if the key was taken.
+    fn process_leader<O: Output>(&mut self, keycode: Keycode, time: u32, out: &mut O) -> bool {
+        let Some(leader) = self.leader.as_mut() else {
+            return false;
+        };
+        // Layer and firmware keys keep working, e.g. to reach keys on other layers
+        if !leader.is_active() || keycode.hid_usage().is_none() {
+            return false;
+        }
+
+        if let LeaderStep::Finished(action) = leader.push(keycode, time) {
+            self.run_leader_action(action, time, out);
+        }
+        true
+    }
+
+    fn run_leader_action<O: Output>(
+        &mut self,
+        action: Option<LeaderAction>,
+        time: u32,
+        out: &mut O,
+    ) {
+        match action {
+            Some(LeaderAction::Tap(keys)) => {
+                for &keycode in keys {
+                    self.tap(keycode, false, time, out);
+                }
+            }
+            Some(LeaderAction::User(id)) => self.hooks.leader_action(id),
+            None => {}
+        }
+    }
+
+    /// Returns `true` if auto-shift took care of the event.
+    fn process_auto_shift<O: Output>(
+        &mut self,
+        event: KeyEvent,
+        keycode: Keycode,
+        out: &mut O,
+    ) -> bool {
+        let Some(auto_shift) = self.auto_shift.as_mut() else {
+            return false;
+        };
+
+        if !event.pressed {
+            return match auto_shift.release(event.row, event.col) {
+                Released::Pending(pending) => {
+                    self.tap(pending.keycode, false, event.time, out);
+                    true
+                }
+                Released::Tapped => true,
+                Released::Other => false,
+            };
+        }
+
+        // Pressing another key resolves the held back one
+        if let Some(pending) = auto_shift.take_pending() {
+            self.tap(pending.keycode, false, event.time, out);
+        }
+
+        let auto_shift = self.auto_shift.as_mut().unwrap();
+        if !auto_shift.applies_to(keycode, self.report.modifier) {
+            return false;
+        }
+        auto_shift.hold(Pending {
+            row: event.row,
+            col: event.col,
+            keycode,
+            time: event.time,
+        });
+        true
+    }
+
+    /// Press and release a key, optionally with shift held around it.
+    fn tap<O: Output>(&mut self, keycode: Keycode, shifted: bool, time: u32, out: &mut O) {
+        if shifted {
+            self.process_keycode(Keycode::LShift, true, time, out);
+        }
+        self.process_keycode(keycode, true, time, out);
+        self.process_keycode(keycode, false, time, out);
+        if shifted {
+            self.process_keycode(Keycode::LShift, false, time, out);
+        }
+    }
+
+    fn process_keycode<O: Output>(
+        &mut self,
+        keycode: Keycode,
+        pressed: bool,
+        time: u32,
+        out: &mut O,
+    ) {
+        let layer = self.layers.current_layer;
+        self.layers.handle_layer_keycode(keycode, pressed);
+        if self.layers.current_layer != layer {
+            self.hooks.layer_state_changed(self.layers.current_layer);
+        }
+        out.process_keycode(keycode, pressed, time);
+
+        if keycode == Keycode::AutoShiftToggle && pressed {
+            if let Some(auto_shift) = self.auto_shift.as_mut() {
+                auto_shift.toggle();
+            }
+        }
+        if keycode == Keycode::Leader && pressed {
+            if let Some(leader) = self.leader.as_mut() {
+                leader.start(time);
+            }
+        }
+
+        let keycode = match keycode {
+            Keycode::GraveEsc => self.resolve_grave_esc(pressed),
+            _ => keycode,
+        };
+        if let Some(usage) = keycode.consumer_usage() {
+            self.process_consumer(usage, pressed, out);
+            return;
+        }
+
+        let mods = keycode.mods();
+        let usage = keycode.hid_usage();
+        if mods == 0 && usage.is_none() {
+            return;
+        }
+
+        // Wrapping modifiers go down before and up after the key
+        let changed = if pressed {
+            let mods_changed = self.report.press_modifiers(mods);
+            usage.map_or(false, |usage| self.report.press(usage)) | mods_changed
+        } else {
+            let usage_changed = usage.map_or(false, |usage| self.report.release(usage));
+            self.report.release_modifiers(mods) | usage_changed
+        };
+        if changed {
+            out.send_report(&self.report);
+        }
+    }
+
+    /// `QK_GESC` sends Grave while Shift or GUI is held and Escape otherwise, as in QMK.
+    fn resolve_grave_esc(&mut self, pressed: bool) -> Keycode {
+        if pressed {
+            self.grave_esc = if self.report.modifier & SHIFT_OR_GUI != 0 {
+                Keycode::Grave
+            } else {
+                Keycode::Escape
+            };
+        }
+        self.grave_esc
+    }
+
+    /// The consumer report holds a single usage, the media key pressed last wins.
+    fn process_consumer<O: Output>(&mut self, usage: u16, pressed: bool, out: &mut O) {
+        if pressed {
+            self.consumer = usage;
+        } else if self.consumer == usage {
+            self.consumer = 0;
+        } else {
+            return;
+        }
+        out.send_consumer_report(self.consumer);
+    }
+}
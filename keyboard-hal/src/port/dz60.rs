@@ -0,0 +1,93 @@
+//! DZ60 (rev2 and rev3 share the same wiring).
+use atmega_hal::port::Pin;
+
+/// The in-switch LEDs are driven by OC1B.
+pub type BacklightTimer = atmega_hal::simple_pwm::Timer1Pwm;
+pub type BacklightPin = atmega_hal::port::PB6;
+
+/// Data line of the WS2812 underglow.
+pub type RgbPin = atmega_hal::port::PE2;
+
+avr_hal_generic::renamed_pins! {
+    pub struct Pins {
+        // Row pins (D0-D3, D5)
+        pub row0: atmega_hal::port::PD0 = pd0,
+        pub row1: atmega_hal::port::PD1 = pd1,
+        pub row2: atmega_hal::port::PD2 = pd2,
+        pub row3: atmega_hal::port::PD3 = pd3,
+        pub row4: atmega_hal::port::PD5 = pd5,
+
+        // Column pins (F0, F1, E6, C7, C6, B7, D4, B1, B0, B5, B4, D7, D6, B3, F4)
+        pub col0: atmega_hal::port::PF0 = pf0,
+        pub col1: atmega_hal::port::PF1 = pf1,
+        pub col2: atmega_hal::port::PE6 = pe6,
+        pub col3: atmega_hal::port::PC7 = pc7,
+        pub col4: atmega_hal::port::PC6 = pc6,
+        pub col5: atmega_hal::port::PB7 = pb7,
+        pub col6: atmega_hal::port::PD4 = pd4,
+        pub col7: atmega_hal::port::PB1 = pb1,
+        pub col8: atmega_hal::port::PB0 = pb0,
+        pub col9: atmega_hal::port::PB5 = pb5,
+        pub col10: atmega_hal::port::PB4 = pb4,
+        pub col11: atmega_hal::port::PD7 = pd7,
+        pub col12: atmega_hal::port::PD6 = pd6,
+        pub col13: atmega_hal::port::PB3 = pb3,
+        pub col14: atmega_hal::port::PF4 = pf4,
+
+        // Backlight (B6)
+        pub backlight: atmega_hal::port::PB6 = pb6,
+
+        // RGB underglow (E2)
+        pub rgb: atmega_hal::port::PE2 = pe2,
+    }
+
+    impl Pins {
+        type Pin = Pin;
+        type McuPins = atmega_hal::Pins;
+    }
+}
+
+/// Set up the key [`Matrix`](crate::Matrix) from the [`Pins`] struct of this board.
+///
+/// Only the row and column pins are moved out of `pins`, so the remaining pins can still be used
+/// for other purposes.
+///
+/// # Example
+/// ```no_run
+/// let dp = keyboard_hal::Peripherals::take().unwrap();
+/// let pins = keyboard_hal::pins!(dp);
+/// let matrix = keyboard_hal::matrix!(pins);
+/// ```
+#[macro_export]
+macro_rules! matrix {
+    ($pins:expr) => {
+        $crate::Matrix::new(
+            // Rows are driven low one at a time while scanning
+            [
+                $pins.row0.into_output_high().downgrade(),
+                $pins.row1.into_output_high().downgrade(),
+                $pins.row2.into_output_high().downgrade(),
+                $pins.row3.into_output_high().downgrade(),
+                $pins.row4.into_output_high().downgrade(),
+            ],
+            // Columns are pulled up and read low when a key is pressed
+            [
+                $pins.col0.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col1.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col2.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col3.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col4.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col5.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col6.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col7.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col8.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col9.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col10.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col11.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col12.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col13.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col14.into_pull_up_input().downgrade().forget_imode(),
+            ],
+        )
+    };
+}
@@ -0,0 +1,88 @@
+//! Let's Split rev2, a split 4x12 board with a Pro Micro in each half.
+//!
+//! Both halves use the same pin map.  The TRRS cable between them carries the TWI lines (`D0`
+//! SCL, `D1` SDA), so the halves are connected with [`Split::i2c`](crate::split::Split::i2c).
+use atmega_hal::port::Pin;
+
+/// Optional LED strip for the switches, driven by OC3A (pin 5).
+pub type BacklightTimer = atmega_hal::simple_pwm::Timer3Pwm;
+pub type BacklightPin = atmega_hal::port::PC6;
+
+/// Data line of the WS2812 underglow (pin TX/1).
+pub type RgbPin = atmega_hal::port::PD3;
+
+avr_hal_generic::renamed_pins! {
+    pub struct Pins {
+        // Row pins (D7, E6, B4, B5)
+        pub row0: atmega_hal::port::PD7 = pd7,
+        pub row1: atmega_hal::port::PE6 = pe6,
+        pub row2: atmega_hal::port::PB4 = pb4,
+        pub row3: atmega_hal::port::PB5 = pb5,
+
+        // Column pins (F6, F7, B1, B3, B2, B6)
+        pub col0: atmega_hal::port::PF6 = pf6,
+        pub col1: atmega_hal::port::PF7 = pf7,
+        pub col2: atmega_hal::port::PB1 = pb1,
+        pub col3: atmega_hal::port::PB3 = pb3,
+        pub col4: atmega_hal::port::PB2 = pb2,
+        pub col5: atmega_hal::port::PB6 = pb6,
+
+        // Link to the other half (D0, D1)
+        pub scl: atmega_hal::port::PD0 = pd0,
+        pub sda: atmega_hal::port::PD1 = pd1,
+
+        // Backlight (C6)
+        pub backlight: atmega_hal::port::PC6 = pc6,
+
+        // RGB underglow (D3)
+        pub rgb: atmega_hal::port::PD3 = pd3,
+    }
+
+    impl Pins {
+        type Pin = Pin;
+        type McuPins = atmega_hal::Pins;
+    }
+}
+
+/// Set up the key [`Matrix`](crate::Matrix) of this half from the [`Pins`] struct of this board.
+///
+/// Only the row and column pins are moved out of `pins`, so the remaining pins can still be used
+/// for other purposes.
+///
+/// # Example
+/// ```no_run
+/// use keyboard_hal::split::{Hand, Split};
+///
+/// let dp = keyboard_hal::Peripherals::take().unwrap();
+/// let pins = keyboard_hal::pins!(dp);
+/// let split = Split::i2c(
+///     dp.TWI,
+///     pins.sda.into_pull_up_input(),
+///     pins.scl.into_pull_up_input(),
+///     Hand::Left,
+/// );
+/// let matrix = keyboard_hal::matrix!(pins);
+/// ```
+#[macro_export]
+macro_rules! matrix {
+    ($pins:expr) => {
+        $crate::Matrix::new(
+            // Rows are driven low one at a time while scanning
+            [
+                $pins.row0.into_output_high().downgrade(),
+                $pins.row1.into_output_high().downgrade(),
+                $pins.row2.into_output_high().downgrade(),
+                $pins.row3.into_output_high().downgrade(),
+            ],
+            // Columns are pulled up and read low when a key is pressed
+            [
+                $pins.col0.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col1.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col2.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col3.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col4.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col5.into_pull_up_input().downgrade().forget_imode(),
+            ],
+        )
+    };
+}
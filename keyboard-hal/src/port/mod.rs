@@ -1 +1,30 @@
-pub mod pcb1;
+//! Pin maps of the supported boards.
+//!
+//! Each board module contains a [`Pins`] struct with the matrix, backlight and RGB pins of the
+//! board, and a matching [`matrix!`](crate::matrix) macro.  The board is selected with a cargo
+//! feature of this crate.
+
+#[cfg(any(feature = "dz60-rev2", feature = "dz60-rev3"))]
+mod dz60;
+#[cfg(any(feature = "dz60-rev2", feature = "dz60-rev3"))]
+pub use dz60::*;
+/// The DZ60 pin map was called `pcb1` before more boards were added.
+#[cfg(any(feature = "dz60-rev2", feature = "dz60-rev3"))]
+#[deprecated(
+    note = "use the `port` module, the pin map of the selected board is re-exported there"
+)]
+pub mod pcb1 {
+    pub use super::dz60::*;
+}
+#[cfg(feature = "planck-rev5")]
+mod planck;
+#[cfg(feature = "planck-rev5")]
+pub use planck::*;
+#[cfg(feature = "promicro-handwired")]
+mod promicro_handwired;
+#[cfg(feature = "promicro-handwired")]
+pub use promicro_handwired::*;
+#[cfg(feature = "lets-split-rev2")]
+mod lets_split;
+#[cfg(feature = "lets-split-rev2")]
+pub use lets_split::*;
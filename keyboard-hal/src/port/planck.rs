@@ -0,0 +1,78 @@
+//! Planck rev5-style 4x12 ortholinear PCB with an ATmega32U4.
+use atmega_hal::port::Pin;
+
+/// The in-switch LEDs are driven by OC1C.
+pub type BacklightTimer = atmega_hal::simple_pwm::Timer1Pwm;
+pub type BacklightPin = atmega_hal::port::PB7;
+
+/// Data line of the WS2812 underglow.
+pub type RgbPin = atmega_hal::port::PB2;
+
+avr_hal_generic::renamed_pins! {
+    pub struct Pins {
+        // Row pins (D0, D5, B5, B6)
+        pub row0: atmega_hal::port::PD0 = pd0,
+        pub row1: atmega_hal::port::PD5 = pd5,
+        pub row2: atmega_hal::port::PB5 = pb5,
+        pub row3: atmega_hal::port::PB6 = pb6,
+
+        // Column pins (F1, F0, B0, C7, F4, F5, F6, F7, D4, D6, B4, D7)
+        pub col0: atmega_hal::port::PF1 = pf1,
+        pub col1: atmega_hal::port::PF0 = pf0,
+        pub col2: atmega_hal::port::PB0 = pb0,
+        pub col3: atmega_hal::port::PC7 = pc7,
+        pub col4: atmega_hal::port::PF4 = pf4,
+        pub col5: atmega_hal::port::PF5 = pf5,
+        pub col6: atmega_hal::port::PF6 = pf6,
+        pub col7: atmega_hal::port::PF7 = pf7,
+        pub col8: atmega_hal::port::PD4 = pd4,
+        pub col9: atmega_hal::port::PD6 = pd6,
+        pub col10: atmega_hal::port::PB4 = pb4,
+        pub col11: atmega_hal::port::PD7 = pd7,
+
+        // Backlight (B7)
+        pub backlight: atmega_hal::port::PB7 = pb7,
+
+        // RGB underglow (B2)
+        pub rgb: atmega_hal::port::PB2 = pb2,
+    }
+
+    impl Pins {
+        type Pin = Pin;
+        type McuPins = atmega_hal::Pins;
+    }
+}
+
+/// Set up the key [`Matrix`](crate::Matrix) from the [`Pins`] struct of this board.
+///
+/// Only the row and column pins are moved out of `pins`, so the remaining pins can still be used
+/// for other purposes.
+#[macro_export]
+macro_rules! matrix {
+    ($pins:expr) => {
+        $crate::Matrix::new(
+            // Rows are driven low one at a time while scanning
+            [
+                $pins.row0.into_output_high().downgrade(),
+                $pins.row1.into_output_high().downgrade(),
+                $pins.row2.into_output_high().downgrade(),
+                $pins.row3.into_output_high().downgrade(),
+            ],
+            // Columns are pulled up and read low when a key is pressed
+            [
+                $pins.col0.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col1.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col2.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col3.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col4.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col5.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col6.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col7.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col8.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col9.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col10.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col11.into_pull_up_input().downgrade().forget_imode(),
+            ],
+        )
+    };
+}
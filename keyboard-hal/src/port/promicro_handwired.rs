@@ -0,0 +1,78 @@
+//! Handwired 4x12 board on a SparkFun Pro Micro.
+use atmega_hal::port::Pin;
+
+/// Optional LED strip for the switches, driven by OC1B (pin 10).
+pub type BacklightTimer = atmega_hal::simple_pwm::Timer1Pwm;
+pub type BacklightPin = atmega_hal::port::PB6;
+
+/// Data line of an optional WS2812 strip (pin TX/1).
+pub type RgbPin = atmega_hal::port::PD3;
+
+avr_hal_generic::renamed_pins! {
+    pub struct Pins {
+        // Row pins (D1, D0, D4, C6)
+        pub row0: atmega_hal::port::PD1 = pd1,
+        pub row1: atmega_hal::port::PD0 = pd0,
+        pub row2: atmega_hal::port::PD4 = pd4,
+        pub row3: atmega_hal::port::PC6 = pc6,
+
+        // Column pins (D7, E6, B4, B5, B2, B3, B1, F7, F6, F5, F4, D2)
+        pub col0: atmega_hal::port::PD7 = pd7,
+        pub col1: atmega_hal::port::PE6 = pe6,
+        pub col2: atmega_hal::port::PB4 = pb4,
+        pub col3: atmega_hal::port::PB5 = pb5,
+        pub col4: atmega_hal::port::PB2 = pb2,
+        pub col5: atmega_hal::port::PB3 = pb3,
+        pub col6: atmega_hal::port::PB1 = pb1,
+        pub col7: atmega_hal::port::PF7 = pf7,
+        pub col8: atmega_hal::port::PF6 = pf6,
+        pub col9: atmega_hal::port::PF5 = pf5,
+        pub col10: atmega_hal::port::PF4 = pf4,
+        pub col11: atmega_hal::port::PD2 = pd2,
+
+        // Backlight (B6)
+        pub backlight: atmega_hal::port::PB6 = pb6,
+
+        // RGB underglow (D3)
+        pub rgb: atmega_hal::port::PD3 = pd3,
+    }
+
+    impl Pins {
+        type Pin = Pin;
+        type McuPins = atmega_hal::Pins;
+    }
+}
+
+/// Set up the key [`Matrix`](crate::Matrix) from the [`Pins`] struct of this board.
+///
+/// Only the row and column pins are moved out of `pins`, so the remaining pins can still be used
+/// for other purposes.
+#[macro_export]
+macro_rules! matrix {
+    ($pins:expr) => {
+        $crate::Matrix::new(
+            // Rows are driven low one at a time while scanning
+            [
+                $pins.row0.into_output_high().downgrade(),
+                $pins.row1.into_output_high().downgrade(),
+                $pins.row2.into_output_high().downgrade(),
+                $pins.row3.into_output_high().downgrade(),
+            ],
+            // Columns are pulled up and read low when a key is pressed
+            [
+                $pins.col0.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col1.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col2.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col3.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col4.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col5.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col6.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col7.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col8.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col9.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col10.into_pull_up_input().downgrade().forget_imode(),
+                $pins.col11.into_pull_up_input().downgrade().forget_imode(),
+            ],
+        )
+    };
+}
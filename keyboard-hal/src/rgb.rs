@@ -0,0 +1,287 @@
+//! RGB underglow effects on top of the [`Ws2812`] driver.
+use crate::backlight::breathing_curve;
+use crate::keyboard_config::RGBLED_NUM;
+use crate::keycodes::Keycode;
+use crate::ws2812::{Rgb, Ws2812, Ws2812Pin};
+use atmega_hal::port::PinOps;
+
+/// Minimum time between two frames sent to the strip.
+const FRAME_INTERVAL_MS: u32 = 20;
+/// Time for a keypress flash to fade out in reactive mode.
+const REACTIVE_FADE_MS: u32 = 500;
+
+const HUE_STEP: u8 = 8;
+const SAT_STEP: u8 = 17;
+const VAL_STEP: u8 = 17;
+const MAX_SPEED: u8 = 3;
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RgbMode {
+    Static = 0,
+    Breathing = 1,
+    Rainbow = 2,
+    Swirl = 3,
+    Reactive = 4,
+}
+
+impl RgbMode {
+    const COUNT: u8 = 5;
+
+    pub const fn from_u8(value: u8) -> Self {
+        match value {
+            1 => RgbMode::Breathing,
+            2 => RgbMode::Rainbow,
+            3 => RgbMode::Swirl,
+            4 => RgbMode::Reactive,
+            _ => RgbMode::Static,
+        }
+    }
+
+    pub const fn next(self) -> Self {
+        Self::from_u8((self as u8 + 1) % Self::COUNT)
+    }
+
+    pub const fn previous(self) -> Self {
+        Self::from_u8((self as u8 + Self::COUNT - 1) % Self::COUNT)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Hsv {
+    pub h: u8,
+    pub s: u8,
+    pub v: u8,
+}
+
+impl Hsv {
+    pub const fn new(h: u8, s: u8, v: u8) -> Self {
+        Hsv { h, s, v }
+    }
+
+    pub fn to_rgb(self) -> Rgb {
+        if self.s == 0 {
+            return Rgb::new(self.v, self.v, self.v);
+        }
+
+        let (h, s, v) = (self.h as u16, self.s as u16, self.v as u16);
+        let region = h * 6 / 256;
+        let remainder = (h * 6) % 256;
+
+        let p = ((v * (255 - s)) >> 8) as u8;
+        let q = ((v * (255 - ((s * remainder) >> 8))) >> 8) as u8;
+        let t = ((v * (255 - ((s * (255 - remainder)) >> 8))) >> 8) as u8;
+        let v = self.v;
+
+        match region {
+            0 => Rgb::new(v, t, p),
+            1 => Rgb::new(q, v, p),
+            2 => Rgb::new(p, v, t),
+            3 => Rgb::new(p, q, v),
+            4 => Rgb::new(t, p, v),
+            _ => Rgb::new(v, p, q),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RgbConfig {
+    pub enabled: bool,
+    pub mode: RgbMode,
+    pub hsv: Hsv,
+    pub speed: u8,
+}
+
+impl RgbConfig {
+    pub const SIZE: usize = 5;
+
+    pub const fn from_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        RgbConfig {
+            enabled: bytes[0] & 0x80 != 0,
+            mode: RgbMode::from_u8(bytes[0] & 0x7F),
+            hsv: Hsv::new(bytes[1], bytes[2], bytes[3]),
+            speed: if bytes[4] > MAX_SPEED {
+                MAX_SPEED
+            } else {
+                bytes[4]
+            },
+        }
+    }
+
+    pub const fn to_bytes(self) -> [u8; Self::SIZE] {
+        [
+            (self.enabled as u8) << 7 | self.mode as u8,
+            self.hsv.h,
+            self.hsv.s,
+            self.hsv.v,
+            self.speed,
+        ]
+    }
+}
+
+impl Default for RgbConfig {
+    fn default() -> Self {
+        RgbConfig {
+            enabled: true,
+            mode: RgbMode::Static,
+            hsv: Hsv::new(0, 255, 255),
+            speed: 1,
+        }
+    }
+}
+
+pub struct RgbLight<PIN> {
+    strip: Ws2812<PIN>,
+    config: RgbConfig,
+    leds: [Rgb; RGBLED_NUM],
+    last_frame: u32,
+    last_keypress: Option<u32>,
+    dirty: bool,
+    /// Colour shown instead of the effect, see [`RgbLight::set_indicator`].
+    indicator: Option<Hsv>,
+}
+
+impl<PIN: Ws2812Pin + PinOps> RgbLight<PIN> {
+    pub fn new(strip: Ws2812<PIN>) -> Self {
+        RgbLight {
+            strip,
+            config: RgbConfig::default(),
+            leds: [Rgb::default(); RGBLED_NUM],
+            last_frame: 0,
+            last_keypress: None,
+            dirty: true,
+            indicator: None,
+        }
+    }
+
+    pub fn config(&self) -> RgbConfig {
+        self.config
+    }
+
+    /// Apply a (stored) configuration, e.g. at boot.
+    pub fn restore(&mut self, config: RgbConfig) {
+        self.config = config;
+        self.dirty = true;
+    }
+
+    /// Show a static colour instead of the configured effect, e.g. as a
+    /// [layer indicator](crate::indicators), or go back to the effect with `None`.  The
+    /// configuration is left untouched and nothing is persisted.
+    pub fn set_indicator(&mut self, hsv: Option<Hsv>) {
+        if self.indicator != hsv {
+            self.indicator = hsv;
+            self.dirty = true;
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.config.enabled = !self.config.enabled;
+        self.dirty = true;
+    }
+
+    pub fn set_mode(&mut self, mode: RgbMode) {
+        self.config.mode = mode;
+        self.dirty = true;
+    }
+
+    pub fn set_hsv(&mut self, hsv: Hsv) {
+        self.config.hsv = hsv;
+        self.dirty = true;
+    }
+
+    /// Handle the RGB keycodes.  Returns `true` if the configuration was changed and should be
+    /// persisted.
+    pub fn process_keycode(&mut self, keycode: Keycode) -> bool {
+        let hsv = &mut self.config.hsv;
+        match keycode {
+            Keycode::RgbToggle => self.config.enabled = !self.config.enabled,
+            Keycode::RgbModeNext => self.config.mode = self.config.mode.next(),
+            Keycode::RgbModePrevious => self.config.mode = self.config.mode.previous(),
+            Keycode::RgbHueUp => hsv.h = hsv.h.wrapping_add(HUE_STEP),
+            Keycode::RgbHueDown => hsv.h = hsv.h.wrapping_sub(HUE_STEP),
+            Keycode::RgbSatUp => hsv.s = hsv.s.saturating_add(SAT_STEP),
+            Keycode::RgbSatDown => hsv.s = hsv.s.saturating_sub(SAT_STEP),
+            Keycode::RgbValUp => hsv.v = hsv.v.saturating_add(VAL_STEP),
+            Keycode::RgbValDown => hsv.v = hsv.v.saturating_sub(VAL_STEP),
+            Keycode::RgbSpeedUp => self.config.speed = (self.config.speed + 1).min(MAX_SPEED),
+            Keycode::RgbSpeedDown => self.config.speed = self.config.speed.saturating_sub(1),
+            _ => return false,
+        }
+        self.dirty = true;
+        true
+    }
+
+    /// Notify the reactive effect about a keypress.
+    pub fn keypress(&mut self, now: u32) {
+        self.last_keypress = Some(now);
+    }
+
+    /// Render the current effect.  Must be called regularly with the current time.
+    pub fn task(&mut self, now: u32) {
+        if now.wrapping_sub(self.last_frame) < FRAME_INTERVAL_MS {
+            return;
+        }
+        self.last_frame = now;
+
+        let animated = self.indicator.is_none() && self.config.mode != RgbMode::Static;
+        if !animated && !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        self.render(now);
+        self.strip.write(&self.leds);
+    }
+
+    fn render(&mut self, now: u32) {
+        if let Some(hsv) = self.indicator {
+            self.fill(hsv);
+            return;
+        }
+
+        let config = self.config;
+        if !config.enabled {
+            self.leds = [Rgb::default(); RGBLED_NUM];
+            return;
+        }
+
+        // Animation time, scaled by the configured speed
+        let t = now << config.speed;
+        let hsv = config.hsv;
+
+        match config.mode {
+            RgbMode::Static => self.fill(hsv),
+            RgbMode::Breathing => {
+                let v = breathing_curve(t, 8000) as u16 * hsv.v as u16 / 255;
+                self.fill(Hsv { v: v as u8, ..hsv });
+            }
+            RgbMode::Rainbow => {
+                let h = hsv.h.wrapping_add((t / 64) as u8);
+                self.fill(Hsv { h, ..hsv });
+            }
+            RgbMode::Swirl => {
+                let offset = (t / 32) as u8;
+                for (i, led) in self.leds.iter_mut().enumerate() {
+                    let h = hsv
+                        .h
+                        .wrapping_add((i * 256 / RGBLED_NUM) as u8)
+                        .wrapping_add(offset);
+                    *led = Hsv { h, ..hsv }.to_rgb();
+                }
+            }
+            RgbMode::Reactive => {
+                let elapsed = self
+                    .last_keypress
+                    .map(|pressed| now.wrapping_sub(pressed))
+                    .unwrap_or(REACTIVE_FADE_MS);
+                let fade = REACTIVE_FADE_MS.saturating_sub(elapsed);
+                let v = fade * hsv.v as u32 / REACTIVE_FADE_MS;
+                self.fill(Hsv { v: v as u8, ..hsv });
+            }
+        }
+    }
+
+    fn fill(&mut self, hsv: Hsv) {
+        self.leds = [hsv.to_rgb(); RGBLED_NUM];
+    }
+}
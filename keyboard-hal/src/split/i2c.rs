@@ -0,0 +1,159 @@
+//! Split link over the TWI (`PD1` SDA, `PD0` SCL).
+//!
+//! The master half uses the `atmega_hal` I2C driver.  As that driver only supports master mode,
+//! the slave side is implemented here on top of the TWI registers.
+use super::{LinkError, REQUEST_LEN, RESPONSE_LEN};
+use crate::pac::TWI;
+use crate::DefaultClock;
+use atmega_hal::port::{
+    mode::{Input, PullUp},
+    Pin, PD0, PD1,
+};
+use avr_hal_generic::i2c::twi_status;
+use embedded_hal::i2c::I2c;
+
+/// Address the slave half answers on.
+pub const SLAVE_ADDRESS: u8 = 0x32;
+
+/// Bus speed of the master half.
+pub const SPEED: u32 = 400_000;
+
+/// Number of polls after which the slave gives up on a transaction the master didn't finish.
+const MAX_IDLE_POLLS: u16 = 2000;
+
+pub(crate) struct I2cMaster {
+    i2c: atmega_hal::I2c<DefaultClock>,
+}
+
+impl I2cMaster {
+    pub fn new(twi: TWI, sda: Pin<Input<PullUp>, PD1>, scl: Pin<Input<PullUp>, PD0>) -> Self {
+        I2cMaster {
+            i2c: atmega_hal::I2c::new(twi, sda, scl, SPEED),
+        }
+    }
+
+    pub fn transfer(
+        &mut self,
+        request: &[u8; REQUEST_LEN],
+    ) -> Result<[u8; RESPONSE_LEN], LinkError> {
+        let mut response = [0; RESPONSE_LEN];
+        self.i2c
+            .write(SLAVE_ADDRESS, request)
+            .map_err(|_| LinkError::Bus)?;
+        self.i2c
+            .read(SLAVE_ADDRESS, &mut response)
+            .map_err(|_| LinkError::Bus)?;
+        Ok(response)
+    }
+}
+
+pub(crate) struct I2cSlave {
+    twi: TWI,
+    _sda: Pin<Input<PullUp>, PD1>,
+    _scl: Pin<Input<PullUp>, PD0>,
+    rx: [u8; REQUEST_LEN],
+    rx_len: usize,
+    tx: [u8; RESPONSE_LEN],
+    tx_idx: usize,
+    busy: bool,
+}
+
+impl I2cSlave {
+    pub fn new(twi: TWI, sda: Pin<Input<PullUp>, PD1>, scl: Pin<Input<PullUp>, PD0>) -> Self {
+        twi.twar.write(|w| w.twa().bits(SLAVE_ADDRESS));
+        let mut slave = I2cSlave {
+            twi,
+            _sda: sda,
+            _scl: scl,
+            rx: [0; REQUEST_LEN],
+            rx_len: 0,
+            tx: [0; RESPONSE_LEN],
+            tx_idx: 0,
+            busy: false,
+        };
+        slave.ack();
+        slave
+    }
+
+    /// Handle pending bus events.  Once addressed by the master, this only returns after the
+    /// transaction was finished, as the master is held up by clock stretching in the meantime.
+    pub fn serve(&mut self, response: &[u8; RESPONSE_LEN]) -> Option<[u8; REQUEST_LEN]> {
+        let mut request = None;
+        let mut idle = 0;
+
+        while idle < MAX_IDLE_POLLS {
+            if self.twi.twcr.read().twint().bit_is_clear() {
+                if !self.busy {
+                    break;
+                }
+                idle += 1;
+                continue;
+            }
+            idle = 0;
+
+            match self.twi.twsr.read().tws().bits() {
+                twi_status::TW_SR_SLA_ACK | twi_status::TW_SR_ARB_LOST_SLA_ACK => {
+                    self.rx_len = 0;
+                    self.busy = true;
+                }
+                twi_status::TW_SR_DATA_ACK => {
+                    let byte = self.twi.twdr.read().bits();
+                    if self.rx_len < REQUEST_LEN {
+                        self.rx[self.rx_len] = byte;
+                        self.rx_len += 1;
+                    }
+                }
+                twi_status::TW_SR_STOP => {
+                    if self.rx_len == REQUEST_LEN {
+                        request = Some(self.rx);
+                    }
+                    self.busy = false;
+                }
+                twi_status::TW_ST_SLA_ACK | twi_status::TW_ST_ARB_LOST_SLA_ACK => {
+                    self.tx = *response;
+                    self.tx_idx = 0;
+                    self.busy = true;
+                    self.send_next();
+                }
+                twi_status::TW_ST_DATA_ACK => self.send_next(),
+                twi_status::TW_ST_DATA_NACK | twi_status::TW_ST_LAST_DATA => self.busy = false,
+                twi_status::TW_BUS_ERROR => {
+                    // Release the bus and recover
+                    self.twi.twcr.write(|w| {
+                        w.twint()
+                            .set_bit()
+                            .twsto()
+                            .set_bit()
+                            .twea()
+                            .set_bit()
+                            .twen()
+                            .set_bit()
+                    });
+                    self.busy = false;
+                    continue;
+                }
+                _ => (),
+            }
+            self.ack();
+        }
+
+        if idle >= MAX_IDLE_POLLS {
+            self.busy = false;
+        }
+        request
+    }
+
+    fn send_next(&mut self) {
+        // Pad with 0xFF if the master reads more than a response
+        let byte = self.tx.get(self.tx_idx).copied().unwrap_or(0xFF);
+        self.tx_idx += 1;
+        self.twi.twdr.write(|w| w.bits(byte));
+    }
+
+    /// Clear the interrupt flag and keep acknowledging our address.
+    fn ack(&mut self) {
+        self.twi
+            .twcr
+            .write(|w| w.twint().set_bit().twea().set_bit().twen().set_bit());
+    }
+}
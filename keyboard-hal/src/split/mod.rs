@@ -0,0 +1,343 @@
+//! Split keyboards with one ATmega32U4 in each half.
+//!
+//! The half which is connected to USB becomes the master, detected through the VBUS pad of the
+//! USB controller.  The other half (the slave) only scans its matrix and answers requests from
+//! the master over a USART or I2C link.
+//!
+//! Every request carries the state the slave should mirror (active layer, host LEDs, backlight
+//! and RGB settings), every response the slave's half of the matrix.  Both are framed by a sync
+//! byte and protected by a CRC-8 so the receiver can drop corrupted frames and resynchronize on
+//! the next sync byte.
+//!
+//! The logical matrix on the master consists of the rows of the left half followed by the rows
+//! of the right half, so [`LOCAL_ROWS`] must be half of [`MATRIX_ROWS`].  This module is only
+//! available for boards with the `split` feature, whose pin maps keep the link pins (`PD0`/`PD1`
+//! or `PD2`/`PD3`) free for the connection to the other half.
+use crate::backlight::BacklightConfig;
+use crate::keyboard_config::{LOCAL_ROWS, MATRIX_COLS, MATRIX_ROWS};
+use crate::rgb::RgbConfig;
+use crate::DefaultClock;
+use atmega_hal::port::{
+    mode::{Input, PullUp},
+    Pin, PD0, PD1,
+};
+use embedded_hal::delay::DelayNs;
+
+pub mod i2c;
+pub mod serial;
+
+/// First byte of every frame.
+pub const SYNC: u8 = 0xA5;
+
+const _: () = assert!(
+    LOCAL_ROWS * 2 == MATRIX_ROWS,
+    "LOCAL_ROWS must be half of MATRIX_ROWS for split keyboards"
+);
+
+const COL_BYTES: usize = (MATRIX_COLS + 7) / 8;
+
+/// Sync byte, layer, host LEDs, backlight, RGB settings and checksum.
+pub const REQUEST_LEN: usize = 4 + RgbConfig::SIZE + 1;
+/// Sync byte, matrix rows of the slave half and checksum.
+pub const RESPONSE_LEN: usize = 1 + LOCAL_ROWS * COL_BYTES + 1;
+
+/// Number of failed transfers after which the keys of the other half are released.
+const MAX_ERRORS: u8 = 10;
+
+pub type LocalState = [[bool; MATRIX_COLS]; LOCAL_ROWS];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    /// Connected to USB, merges both halves and talks to the host.
+    Master,
+    /// Only scans its matrix and mirrors the state of the master.
+    Slave,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LinkError {
+    /// No (complete) response arrived in time.
+    Timeout,
+    /// The response arrived but its checksum did not match.
+    Checksum,
+    /// The underlying bus reported an error.
+    Bus,
+}
+
+/// State which is synchronized from the master to the slave.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SyncState {
+    pub layer: u8,
+    pub host_leds: u8,
+    pub backlight: BacklightConfig,
+    pub rgb: RgbConfig,
+}
+
+impl SyncState {
+    pub fn encode(&self) -> [u8; REQUEST_LEN] {
+        let mut frame = [0; REQUEST_LEN];
+        frame[0] = SYNC;
+        frame[1] = self.layer;
+        frame[2] = self.host_leds;
+        frame[3] = self.backlight.to_byte();
+        frame[4..4 + RgbConfig::SIZE].copy_from_slice(&self.rgb.to_bytes());
+        seal(&mut frame);
+        frame
+    }
+
+    pub fn decode(frame: &[u8; REQUEST_LEN]) -> Option<Self> {
+        if !is_valid(frame) {
+            return None;
+        }
+
+        let mut rgb = [0; RgbConfig::SIZE];
+        rgb.copy_from_slice(&frame[4..4 + RgbConfig::SIZE]);
+
+        Some(SyncState {
+            layer: frame[1],
+            host_leds: frame[2],
+            backlight: BacklightConfig::from_byte(frame[3]),
+            rgb: RgbConfig::from_bytes(rgb),
+        })
+    }
+}
+
+pub fn encode_matrix(state: &LocalState) -> [u8; RESPONSE_LEN] {
+    let mut frame = [0; RESPONSE_LEN];
+    frame[0] = SYNC;
+    for (row_idx, row) in state.iter().enumerate() {
+        for (col_idx, &pressed) in row.iter().enumerate() {
+            if pressed {
+                frame[1 + row_idx * COL_BYTES + col_idx / 8] |= 1 << (col_idx % 8);
+            }
+        }
+    }
+    seal(&mut frame);
+    frame
+}
+
+pub fn decode_matrix(frame: &[u8; RESPONSE_LEN]) -> Option<LocalState> {
+    if !is_valid(frame) {
+        return None;
+    }
+
+    let mut state = [[false; MATRIX_COLS]; LOCAL_ROWS];
+    for (row_idx, row) in state.iter_mut().enumerate() {
+        for (col_idx, pressed) in row.iter_mut().enumerate() {
+            *pressed = frame[1 + row_idx * COL_BYTES + col_idx / 8] & (1 << (col_idx % 8)) != 0;
+        }
+    }
+    Some(state)
+}
+
+/// CRC-8 with polynomial 0x07.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Write the checksum into the last byte of a frame.
+fn seal(frame: &mut [u8]) {
+    let last = frame.len() - 1;
+    frame[last] = crc8(&frame[..last]);
+}
+
+fn is_valid(frame: &[u8]) -> bool {
+    let last = frame.len() - 1;
+    frame[0] == SYNC && frame[last] == crc8(&frame[..last])
+}
+
+/// Reassembles frames of length `N` from a byte stream.
+///
+/// Bytes before a sync byte are dropped.  If a complete frame has a bad checksum, reception
+/// restarts at the next sync byte inside of it.
+pub struct FrameReceiver<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> FrameReceiver<N> {
+    pub const fn new() -> Self {
+        FrameReceiver {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn push(&mut self, byte: u8) -> Option<[u8; N]> {
+        if self.len == 0 && byte != SYNC {
+            return None;
+        }
+
+        self.buf[self.len] = byte;
+        self.len += 1;
+        if self.len < N {
+            return None;
+        }
+
+        if is_valid(&self.buf) {
+            self.len = 0;
+            return Some(self.buf);
+        }
+
+        // Resync on the next sync byte of the corrupted frame
+        match self.buf[1..].iter().position(|&b| b == SYNC) {
+            Some(pos) => {
+                self.buf.copy_within(pos + 1.., 0);
+                self.len = N - pos - 1;
+            }
+            None => self.len = 0,
+        }
+        None
+    }
+}
+
+impl<const N: usize> Default for FrameReceiver<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Detect whether this half is connected to USB.
+///
+/// Must be called after the USB controller was enabled (i.e. after `usb_bus!`).
+pub fn detect_role() -> Role {
+    // SAFETY: Only the VBUS pad is enabled and read, this does not interfere with the USB bus
+    // driver.
+    let usb = unsafe { &*crate::pac::USB_DEVICE::ptr() };
+    usb.usbcon.modify(|_, w| w.otgpade().set_bit());
+
+    // Give the VBUS pad some time to settle
+    atmega_hal::delay::Delay::<DefaultClock>::new().delay_ms(10);
+
+    if usb.usbsta.read().vbus().bit_is_set() {
+        Role::Master
+    } else {
+        Role::Slave
+    }
+}
+
+enum Link {
+    Serial(serial::SerialLink),
+    I2cMaster(i2c::I2cMaster),
+    I2cSlave(i2c::I2cSlave),
+}
+
+pub struct Split {
+    link: Link,
+    role: Role,
+    hand: Hand,
+    remote: LocalState,
+    errors: u8,
+}
+
+impl Split {
+    /// Connect both halves through USART1 (`PD2`/`PD3`), RX and TX crossed over.  The firmware
+    /// must call [`serial::receive`] from the `USART1_RX` interrupt.
+    pub fn serial(usart: serial::SplitUsart, hand: Hand) -> Self {
+        let role = detect_role();
+        Self::new(Link::Serial(serial::SerialLink::new(usart)), role, hand)
+    }
+
+    /// Connect both halves through the TWI (`PD0`/`PD1`).  The master half drives the bus, the
+    /// slave half listens on [`i2c::SLAVE_ADDRESS`].
+    pub fn i2c(
+        twi: crate::pac::TWI,
+        sda: Pin<Input<PullUp>, PD1>,
+        scl: Pin<Input<PullUp>, PD0>,
+        hand: Hand,
+    ) -> Self {
+        let role = detect_role();
+        let link = match role {
+            Role::Master => Link::I2cMaster(i2c::I2cMaster::new(twi, sda, scl)),
+            Role::Slave => Link::I2cSlave(i2c::I2cSlave::new(twi, sda, scl)),
+        };
+        Self::new(link, role, hand)
+    }
+
+    fn new(link: Link, role: Role, hand: Hand) -> Self {
+        Split {
+            link,
+            role,
+            hand,
+            remote: [[false; MATRIX_COLS]; LOCAL_ROWS],
+            errors: 0,
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn hand(&self) -> Hand {
+        self.hand
+    }
+
+    /// Master side: exchange state with the slave and merge both halves into `matrix`.
+    pub fn merge(
+        &mut self,
+        local: &LocalState,
+        sync: &SyncState,
+        matrix: &mut [[bool; MATRIX_COLS]; MATRIX_ROWS],
+    ) {
+        let request = sync.encode();
+        let result = match &mut self.link {
+            Link::Serial(link) => link.transfer(&request),
+            Link::I2cMaster(link) => link.transfer(&request),
+            Link::I2cSlave(_) => unreachable!(),
+        };
+
+        match result.and_then(|frame| decode_matrix(&frame).ok_or(LinkError::Checksum)) {
+            Ok(remote) => {
+                self.remote = remote;
+                self.errors = 0;
+            }
+            Err(_) => {
+                self.errors = self.errors.saturating_add(1);
+                if self.errors >= MAX_ERRORS {
+                    // Don't leave keys of a disconnected half stuck
+                    self.remote = [[false; MATRIX_COLS]; LOCAL_ROWS];
+                }
+            }
+        }
+
+        let (left, right) = match self.hand {
+            Hand::Left => (local, &self.remote),
+            Hand::Right => (&self.remote, local),
+        };
+        matrix[..LOCAL_ROWS].copy_from_slice(left);
+        matrix[LOCAL_ROWS..].copy_from_slice(right);
+    }
+
+    /// Slave side: answer pending requests with the local matrix.  Returns the state sent by the
+    /// master, if a request was received.
+    pub fn serve(&mut self, local: &LocalState) -> Option<SyncState> {
+        let response = encode_matrix(local);
+        let request = match &mut self.link {
+            Link::Serial(link) => link.serve(&response),
+            Link::I2cSlave(link) => link.serve(&response),
+            Link::I2cMaster(_) => unreachable!(),
+        }?;
+        SyncState::decode(&request)
+    }
+}
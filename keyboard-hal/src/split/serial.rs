@@ -0,0 +1,145 @@
+//! Split link over USART1.
+//!
+//! Connect `PD3` (TX) of each half to `PD2` (RX) of the other one.  Received bytes are buffered
+//! by the `USART1_RX` interrupt, as the slave half spends far longer than a few byte times on
+//! scanning and lighting between two polls.  The firmware places the handler and enables
+//! interrupts:
+//!
+//! ```ignore
+//! let pins = atmega_hal::pins!(dp);
+//! let usart = Usart1::new(dp.USART1, pins.pd2, pins.pd3.into_output(), BAUDRATE.into_baudrate());
+//! let keyboard = Keyboard::new(matrix, usb_bus, eeprom).with_split(Split::serial(usart, Hand::Left));
+//! unsafe { avr_device::interrupt::enable() };
+//!
+//! #[avr_device::interrupt(atmega32u4)]
+//! fn USART1_RX() {
+//!     keyboard_hal::split::serial::receive();
+//! }
+//! ```
+use super::{FrameReceiver, LinkError, REQUEST_LEN, RESPONSE_LEN};
+use crate::DefaultClock;
+use atmega_hal::delay::Delay;
+use avr_device::interrupt::Mutex;
+use avr_hal_generic::usart::Event;
+use core::cell::RefCell;
+use embedded_hal::delay::DelayNs;
+
+/// Recommended baudrate, which can be generated without error from a 16 MHz clock.
+pub const BAUDRATE: u32 = 250_000;
+
+/// Time the master waits for a complete response.
+const TIMEOUT_US: u32 = 5000;
+const POLL_INTERVAL_US: u32 = 20;
+
+/// Room for a few frames, more than arrive between two polls.
+const RX_BUFFER_LEN: usize = 64;
+
+pub type SplitUsart = atmega_hal::usart::Usart1<DefaultClock>;
+
+/// Bytes received by [`receive`] which were not yet taken by the link.
+struct RxBuffer {
+    buf: [u8; RX_BUFFER_LEN],
+    start: usize,
+    len: usize,
+}
+
+impl RxBuffer {
+    const fn new() -> Self {
+        RxBuffer {
+            buf: [0; RX_BUFFER_LEN],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// Store a byte, dropping it if the buffer is full.  The frame it belongs to then fails its
+    /// checksum and the receiver resynchronizes.
+    fn push(&mut self, byte: u8) {
+        if self.len < RX_BUFFER_LEN {
+            self.buf[(self.start + self.len) % RX_BUFFER_LEN] = byte;
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.buf[self.start];
+        self.start = (self.start + 1) % RX_BUFFER_LEN;
+        self.len -= 1;
+        Some(byte)
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+static RX_BUFFER: Mutex<RefCell<RxBuffer>> = Mutex::new(RefCell::new(RxBuffer::new()));
+
+/// Move the byte received by USART1 into the receive buffer.  Must be called from the
+/// `USART1_RX` interrupt when the halves are linked with [`Split::serial`](super::Split::serial).
+pub fn receive() {
+    // SAFETY: The data register is only read here, once the link enabled the RX interrupt.
+    let usart = unsafe { &*crate::pac::USART1::ptr() };
+    let byte = usart.udr1.read().bits();
+    avr_device::interrupt::free(|cs| RX_BUFFER.borrow(cs).borrow_mut().push(byte));
+}
+
+fn take_byte() -> Option<u8> {
+    avr_device::interrupt::free(|cs| RX_BUFFER.borrow(cs).borrow_mut().pop())
+}
+
+pub(crate) struct SerialLink {
+    usart: SplitUsart,
+    requests: FrameReceiver<REQUEST_LEN>,
+    responses: FrameReceiver<RESPONSE_LEN>,
+}
+
+impl SerialLink {
+    pub fn new(mut usart: SplitUsart) -> Self {
+        usart.listen(Event::RxComplete);
+        SerialLink {
+            usart,
+            requests: FrameReceiver::new(),
+            responses: FrameReceiver::new(),
+        }
+    }
+
+    pub fn transfer(
+        &mut self,
+        request: &[u8; REQUEST_LEN],
+    ) -> Result<[u8; RESPONSE_LEN], LinkError> {
+        // Drop what is left of earlier responses which arrived too late
+        avr_device::interrupt::free(|cs| RX_BUFFER.borrow(cs).borrow_mut().clear());
+        self.responses.reset();
+
+        for &byte in request {
+            self.usart.write_byte(byte);
+        }
+
+        let mut delay = Delay::<DefaultClock>::new();
+        for _ in 0..TIMEOUT_US / POLL_INTERVAL_US {
+            while let Some(byte) = take_byte() {
+                if let Some(frame) = self.responses.push(byte) {
+                    return Ok(frame);
+                }
+            }
+            delay.delay_us(POLL_INTERVAL_US);
+        }
+        Err(LinkError::Timeout)
+    }
+
+    pub fn serve(&mut self, response: &[u8; RESPONSE_LEN]) -> Option<[u8; REQUEST_LEN]> {
+        while let Some(byte) = take_byte() {
+            if let Some(request) = self.requests.push(byte) {
+                for &byte in response {
+                    self.usart.write_byte(byte);
+                }
+                return Some(request);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,57 @@
+//! Millisecond timer based on `TC0`, following the `millis()` example of `arduino-hal`.
+//!
+//! Call [`init`] once and enable interrupts globally before polling the keyboard.  The firmware
+//! places the `TIMER0_COMPA` handler, so it stays free to use `TC0` for something else:
+//!
+//! ```ignore
+//! keyboard_hal::timer::init(dp.TC0);
+//! unsafe { avr_device::interrupt::enable() };
+//!
+//! #[avr_device::interrupt(atmega32u4)]
+//! fn TIMER0_COMPA() {
+//!     keyboard_hal::timer::tick();
+//! }
+//! ```
+use core::cell;
+
+// 16 MHz / 64 / 250 = 1 kHz
+const PRESCALER: u32 = 64;
+const TIMER_COUNTS: u32 = 250;
+
+static MILLIS_COUNTER: avr_device::interrupt::Mutex<cell::Cell<u32>> =
+    avr_device::interrupt::Mutex::new(cell::Cell::new(0));
+
+pub fn init(tc0: crate::pac::TC0) {
+    // Configure the timer for the above interval (in CTC mode) and enable its interrupt.
+    tc0.tccr0a.write(|w| w.wgm0().ctc());
+    tc0.ocr0a.write(|w| w.bits(TIMER_COUNTS as u8));
+    tc0.tccr0b.write(|w| match PRESCALER {
+        8 => w.cs0().prescale_8(),
+        64 => w.cs0().prescale_64(),
+        256 => w.cs0().prescale_256(),
+        1024 => w.cs0().prescale_1024(),
+        _ => panic!(),
+    });
+    tc0.timsk0.write(|w| w.ocie0a().set_bit());
+
+    // Reset the global millisecond counter
+    avr_device::interrupt::free(|cs| {
+        MILLIS_COUNTER.borrow(cs).set(0);
+    });
+}
+
+/// Advance the counter by one timer period.  Must be called from the `TIMER0_COMPA` interrupt.
+pub fn tick() {
+    const MILLIS_INCREMENT: u32 = PRESCALER * TIMER_COUNTS / 16000;
+
+    avr_device::interrupt::free(|cs| {
+        let counter_cell = MILLIS_COUNTER.borrow(cs);
+        let counter = counter_cell.get();
+        counter_cell.set(counter.wrapping_add(MILLIS_INCREMENT));
+    })
+}
+
+/// Milliseconds since [`init`] was called.
+pub fn millis() -> u32 {
+    avr_device::interrupt::free(|cs| MILLIS_COUNTER.borrow(cs).get())
+}
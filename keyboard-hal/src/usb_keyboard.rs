@@ -1,47 +1,159 @@
-use usb_device::{bus::UsbBus, device::UsbDevice};
-use usbd_hid::{descriptor::KeyboardReport, hid_class::HIDClass};
+use crate::cdc_acm::CdcAcmClass;
+use usb_device::{
+    bus::UsbBus,
+    device::{UsbDevice, UsbDeviceState},
+    UsbError,
+};
+use usbd_hid::{
+    descriptor::{KeyboardReport, MediaKeyboardReport},
+    hid_class::HIDClass,
+};
+
+/// Polls without progress after which a console write is dropped, a few milliseconds.
+const MAX_STALLED_POLLS: u16 = 500;
 
 pub struct UsbKeyboard<B: UsbBus + 'static> {
     usb_device: UsbDevice<'static, B>,
     hid_class: HIDClass<'static, B>,
-    last_report: KeyboardReport,
+    /// Consumer control interface for the media keys.
+    consumer_class: HIDClass<'static, B>,
+    serial: Option<CdcAcmClass<'static, B>>,
+    /// The host stopped reading the console, see [`UsbKeyboard::write_serial`].
+    serial_stalled: bool,
+    leds: u8,
 }
 
 impl<B: UsbBus> UsbKeyboard<B> {
-    pub fn new(usb_device: UsbDevice<'static, B>, hid_class: HIDClass<'static, B>) -> Self {
+    pub fn new(
+        usb_device: UsbDevice<'static, B>,
+        hid_class: HIDClass<'static, B>,
+        consumer_class: HIDClass<'static, B>,
+        serial: Option<CdcAcmClass<'static, B>>,
+    ) -> Self {
         UsbKeyboard {
             usb_device,
             hid_class,
-            last_report: KeyboardReport::default(),
+            consumer_class,
+            serial,
+            serial_stalled: false,
+            leds: 0,
         }
     }
 
-    pub fn handle_keypress(&mut self, keycode: u8, pressed: bool) {
-        if pressed {
-            // Add keycode to the report
-            if let Some(slot) = self
-                .last_report
-                .keycodes
-                .iter_mut()
-                .find(|slot| **slot == 0)
-            {
-                *slot = keycode;
+    /// Service the USB device and pick up LED reports from the host.  Must be called regularly.
+    pub fn poll(&mut self) {
+        let event = match self.serial.as_mut() {
+            Some(serial) => {
+                self.usb_device
+                    .poll(&mut [&mut self.hid_class, &mut self.consumer_class, serial])
             }
-        } else {
-            // Remove keycode from the report
-            for slot in self.last_report.keycodes.iter_mut() {
-                if *slot == keycode {
-                    *slot = 0;
+            None => self
+                .usb_device
+                .poll(&mut [&mut self.hid_class, &mut self.consumer_class]),
+        };
+        if !event {
+            return;
+        }
+
+        let mut report = [0; 1];
+        if let Ok(1) = self.hid_class.pull_raw_output(&mut report) {
+            self.leds = report[0];
+        }
+
+        if let Some(serial) = self.serial.as_mut() {
+            // Input on the console is not used, drop it so the host does not stall
+            let mut input = [0; 64];
+            let _ = serial.read_packet(&mut input);
+        }
+    }
+
+    /// Lock key LEDs as set by the host (bit 0: Num Lock, bit 1: Caps Lock, bit 2: Scroll Lock).
+    pub fn leds(&self) -> u8 {
+        self.leds
+    }
+
+    /// Send a report to the host, waiting for the previous one to be picked up first.
+    pub fn send_report(&mut self, report: &KeyboardReport) {
+        while self.usb_device.state() == UsbDeviceState::Configured {
+            match self.hid_class.push_input(report) {
+                Err(UsbError::WouldBlock) => {
+                    self.poll();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Send a consumer control report, waiting for the previous one to be picked up first.
+    pub fn send_consumer_report(&mut self, report: &MediaKeyboardReport) {
+        while self.usb_device.state() == UsbDeviceState::Configured {
+            match self.consumer_class.push_input(report) {
+                Err(UsbError::WouldBlock) => {
+                    self.poll();
                 }
+                _ => break,
             }
         }
+    }
 
-        self.send_report();
+    /// Whether there is a serial console and a terminal on the host has it open.
+    pub fn console_open(&self) -> bool {
+        self.usb_device.state() == UsbDeviceState::Configured
+            && self.serial.as_ref().is_some_and(|serial| serial.dtr())
     }
 
-    fn send_report(&mut self) {
-        if self.usb_device.poll(&mut [&mut self.hid_class]) {
-            self.hid_class.push_input(&self.last_report).ok();
+    /// Write to the serial console, waiting for the host to pick up the data.  Nothing is sent
+    /// while no terminal has the console open, or if the keyboard has no console.
+    ///
+    /// A terminal can keep the console open without reading it.  The rest of the data is dropped
+    /// when the host does not pick up a packet within a few milliseconds, and until it reads
+    /// again, later writes are dropped right away, so the console never holds up the keyboard.
+    pub fn write_serial(&mut self, mut data: &[u8]) {
+        let mut polls_left = if self.serial_stalled {
+            0
+        } else {
+            MAX_STALLED_POLLS
+        };
+        while !data.is_empty() && self.console_open() {
+            let Some(serial) = self.serial.as_mut() else {
+                break;
+            };
+            let len = data.len().min(serial.max_packet_size() as usize);
+            match serial.write_packet(&data[..len]) {
+                Ok(written) => {
+                    data = &data[written..];
+                    self.serial_stalled = false;
+                    polls_left = MAX_STALLED_POLLS;
+                }
+                Err(UsbError::WouldBlock) if polls_left > 0 => {
+                    polls_left -= 1;
+                    self.poll();
+                }
+                Err(UsbError::WouldBlock) => {
+                    self.serial_stalled = true;
+                    break;
+                }
+                Err(_) => break,
+            }
         }
     }
+
+    /// The serial console as a [`ufmt::uWrite`] target.
+    pub fn console(&mut self) -> Console<'_, B> {
+        Console { usb_keyboard: self }
+    }
+}
+
+/// Writes to the serial console of a [`UsbKeyboard`], see [`UsbKeyboard::write_serial`].
+pub struct Console<'a, B: UsbBus + 'static> {
+    usb_keyboard: &'a mut UsbKeyboard<B>,
+}
+
+impl<B: UsbBus> ufmt::uWrite for Console<'_, B> {
+    type Error = core::convert::Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.usb_keyboard.write_serial(s.as_bytes());
+        Ok(())
+    }
 }
@@ -0,0 +1,137 @@
+//! Bit-banged driver for WS2812/SK6812 addressable LEDs.
+//!
+//! The timing is cycle-counted for a 16 MHz clock: a `0` is high for 4 cycles (250ns) and takes 21
+//! cycles (1.31µs) in total, a `1` is high for 12 cycles (750ns) and takes 20 cycles (1.25µs).  Both
+//! periods are well within the ±600ns the LEDs tolerate.  Interrupts are only disabled while one
+//! LED is sent (30µs), so interrupts like the split link's `USART1_RX` are served between LEDs.
+//! Handlers must be short, the LEDs latch the frame if the line stays low for more than 50µs.
+//!
+//! The data pin must be a typed pin (not downgraded) so the driver can write its `PORTx`
+//! register directly:
+//!
+//! ```ignore
+//! let mut strip = Ws2812::new(pins.rgb.into_output());
+//! strip.write(&[Rgb::new(255, 0, 0); 16]);
+//! ```
+use atmega_hal::port::{self, mode::Output, Pin};
+
+#[cfg(target_arch = "avr")]
+use core::arch::asm;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r, g, b }
+    }
+}
+
+/// Pins which can drive a WS2812 strip.
+pub trait Ws2812Pin {
+    /// Returns the address of the pin's `PORTx` register and the bit mask of the pin in it.
+    fn port_and_mask() -> (*mut u8, u8);
+}
+
+macro_rules! impl_ws2812_pin {
+    ($($PXn:ident: $PORT:ident.$port:ident[$bit:literal],)+) => {
+        $(
+            impl Ws2812Pin for port::$PXn {
+                fn port_and_mask() -> (*mut u8, u8) {
+                    // SAFETY: Only the address of the register is taken here.
+                    let port = unsafe { (*crate::pac::$PORT::ptr()).$port.as_ptr() };
+                    (port, 1 << $bit)
+                }
+            }
+        )+
+    };
+}
+
+impl_ws2812_pin! {
+    PB0: PORTB.portb[0], PB1: PORTB.portb[1], PB2: PORTB.portb[2], PB3: PORTB.portb[3],
+    PB4: PORTB.portb[4], PB5: PORTB.portb[5], PB6: PORTB.portb[6], PB7: PORTB.portb[7],
+    PC6: PORTC.portc[6], PC7: PORTC.portc[7],
+    PD0: PORTD.portd[0], PD1: PORTD.portd[1], PD2: PORTD.portd[2], PD3: PORTD.portd[3],
+    PD4: PORTD.portd[4], PD5: PORTD.portd[5], PD6: PORTD.portd[6], PD7: PORTD.portd[7],
+    PE2: PORTE.porte[2], PE6: PORTE.porte[6],
+    PF0: PORTF.portf[0], PF1: PORTF.portf[1], PF4: PORTF.portf[4], PF5: PORTF.portf[5],
+    PF6: PORTF.portf[6], PF7: PORTF.portf[7],
+}
+
+pub struct Ws2812<PIN> {
+    pin: Pin<Output, PIN>,
+}
+
+impl<PIN: Ws2812Pin + port::PinOps> Ws2812<PIN> {
+    pub fn new(mut pin: Pin<Output, PIN>) -> Self {
+        pin.set_low();
+        Ws2812 { pin }
+    }
+
+    /// Send a frame of colors to the strip.  The LEDs latch the new colors once the line stays
+    /// low for more than 50µs after the frame.
+    pub fn write(&mut self, leds: &[Rgb]) {
+        let (port, mask) = PIN::port_and_mask();
+
+        for led in leds {
+            avr_device::interrupt::free(|_| {
+                // SAFETY: The port register is only written while interrupts are disabled, so no
+                // other code can modify it concurrently.  It is read again for every LED, as an
+                // interrupt handler may have changed other pins of the port in between.
+                let current = unsafe { core::ptr::read_volatile(port) };
+                let high = current | mask;
+                let low = current & !mask;
+
+                // WS2812 expect the colors in GRB order
+                for byte in [led.g, led.r, led.b] {
+                    write_byte(port, high, low, byte);
+                }
+            });
+        }
+
+        self.pin.set_low();
+    }
+}
+
+#[cfg(target_arch = "avr")]
+#[inline(always)]
+fn write_byte(port: *mut u8, high: u8, low: u8, byte: u8) {
+    // SAFETY: `port` points to a valid PORTx register and `high`/`low` only differ in the bit of
+    // the data pin.
+    unsafe {
+        asm!(
+            "ldi {count}, 8",
+            "1:",
+            // Rising edge at cycle 0
+            "st Z, {high}",
+            "nop",
+            // Skipping the store takes 2 cycles, not skipping it 1 + 2 cycles, so a `0` is one
+            // cycle longer from here on
+            "sbrs {byte}, 7",
+            // Falling edge at cycle 4 for a `0`
+            "st Z, {low}",
+            "lsl {byte}",
+            "nop", "nop", "nop", "nop", "nop", "nop",
+            // Falling edge at cycle 12 for a `1` (13 for a `0`, where the line is already low)
+            "st Z, {low}",
+            "nop", "nop", "nop",
+            "dec {count}",
+            // Next rising edge at cycle 20 for a `1` and 21 for a `0`
+            "brne 1b",
+            count = out(reg_upper) _,
+            byte = inout(reg) byte => _,
+            high = in(reg) high,
+            low = in(reg) low,
+            in("Z") port as u16,
+        );
+    }
+}
+
+#[cfg(not(target_arch = "avr"))]
+fn write_byte(_port: *mut u8, _high: u8, _low: u8, _byte: u8) {
+    unimplemented!("Implementation is only available for avr targets!")
+}
@@ -0,0 +1,125 @@
+// Key positions below refer to the DZ60 default keymap
+#![cfg(any(feature = "dz60-rev2", feature = "dz60-rev3"))]
+
+mod common;
+
+use common::{report, Harness, Step::*};
+use keyboard_hal::auto_shift::AutoShiftConfig;
+use keyboard_hal::keycodes::prelude::*;
+
+const KEY_1: (usize, usize) = (0, 1);
+const KEY_A: (usize, usize) = (2, 1);
+const KEY_S: (usize, usize) = (2, 2);
+const KEY_LSHIFT: (usize, usize) = (3, 0);
+const KEY_ENTER: (usize, usize) = (2, 12);
+
+fn press((row, col): (usize, usize)) -> common::Step {
+    Press(row, col)
+}
+
+fn release((row, col): (usize, usize)) -> common::Step {
+    Release(row, col)
+}
+
+fn harness(config: AutoShiftConfig) -> Harness {
+    let mut h = Harness::new();
+    h.pipeline = h.pipeline.with_auto_shift(config);
+    h
+}
+
+#[test]
+fn tap_sends_normal_key_on_release() {
+    let mut h = harness(AutoShiftConfig::default());
+    h.run(&[(0, press(KEY_A)), (100, release(KEY_A))]);
+
+    h.assert_reports(&[report(&[A]), report(&[])]);
+    assert_eq!(h.out.reports[0].0, 100);
+}
+
+#[test]
+fn hold_sends_shifted_key_at_timeout() {
+    let mut h = harness(AutoShiftConfig::default());
+    h.run(&[
+        (0, press(KEY_A)),
+        (174, Idle),
+        (175, Idle),
+        (300, release(KEY_A)),
+    ]);
+
+    h.assert_reports(&[
+        report(&[LShift]),
+        report(&[LShift, A]),
+        report(&[LShift]),
+        report(&[]),
+    ]);
+    assert!(h.out.reports.iter().all(|&(time, _)| time == 175));
+}
+
+#[test]
+fn other_key_resolves_pending_press() {
+    let mut h = harness(AutoShiftConfig::default());
+    h.run(&[
+        (0, press(KEY_A)),
+        (50, press(KEY_S)),
+        (100, release(KEY_A)),
+        (120, release(KEY_S)),
+    ]);
+
+    h.assert_reports(&[report(&[A]), report(&[]), report(&[S]), report(&[])]);
+}
+
+#[test]
+fn key_classes_can_be_disabled() {
+    let mut h = harness(AutoShiftConfig {
+        numbers: false,
+        ..AutoShiftConfig::default()
+    });
+    h.run(&[(0, press(KEY_1)), (300, release(KEY_1))]);
+
+    h.assert_reports(&[report(&[Num1]), report(&[])]);
+    assert_eq!(h.out.reports[0].0, 0);
+}
+
+#[test]
+fn other_keys_are_not_delayed() {
+    let mut h = harness(AutoShiftConfig::default());
+    h.run(&[(0, press(KEY_ENTER)), (300, release(KEY_ENTER))]);
+
+    h.assert_reports(&[report(&[Enter]), report(&[])]);
+    assert_eq!(h.out.reports[0].0, 0);
+}
+
+#[test]
+fn skipped_while_modifier_is_held() {
+    let mut h = harness(AutoShiftConfig::default());
+    h.run(&[
+        (0, press(KEY_LSHIFT)),
+        (10, press(KEY_A)),
+        (300, release(KEY_A)),
+        (310, release(KEY_LSHIFT)),
+    ]);
+
+    h.assert_reports(&[
+        report(&[LShift]),
+        report(&[LShift, A]),
+        report(&[LShift]),
+        report(&[]),
+    ]);
+}
+
+#[test]
+fn toggle_keycode() {
+    let mut h = Harness::with_keys(&[(KEY_ENTER, AutoShiftToggle)]);
+    h.pipeline = h.pipeline.with_auto_shift(AutoShiftConfig::default());
+
+    h.run(&[
+        (0, press(KEY_ENTER)),
+        (10, release(KEY_ENTER)),
+        (20, press(KEY_A)),
+        (300, release(KEY_A)),
+    ]);
+
+    assert!(!h.pipeline.auto_shift().unwrap().config().enabled);
+    h.assert_reports(&[report(&[A]), report(&[])]);
+    assert_eq!(h.out.reports[0].0, 20);
+}
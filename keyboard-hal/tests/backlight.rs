@@ -0,0 +1,73 @@
+use keyboard_hal::backlight::{breathing_curve, BacklightConfig, BACKLIGHT_LEVELS};
+
+fn at_level(level: u8) -> BacklightConfig {
+    BacklightConfig {
+        enabled: level != 0,
+        breathing: false,
+        level,
+    }
+}
+
+#[test]
+fn config_round_trips() {
+    for level in 0..=BACKLIGHT_LEVELS {
+        for (enabled, breathing) in [(false, false), (true, false), (false, true), (true, true)] {
+            let config = BacklightConfig {
+                enabled,
+                breathing,
+                level,
+            };
+            assert_eq!(BacklightConfig::from_byte(config.to_byte()), config);
+        }
+    }
+}
+
+#[test]
+fn stored_level_is_clamped() {
+    let config = BacklightConfig::from_byte(0x80 | 0x3F);
+    assert!(config.enabled);
+    assert_eq!(config.level, BACKLIGHT_LEVELS);
+}
+
+#[test]
+fn step_wraps_to_off() {
+    let mut config = at_level(0);
+    for level in 1..=BACKLIGHT_LEVELS {
+        config.step();
+        assert_eq!(config, at_level(level));
+    }
+    config.step();
+    assert_eq!(config, at_level(0));
+}
+
+#[test]
+fn increase_stops_at_max() {
+    let mut config = at_level(BACKLIGHT_LEVELS - 1);
+    config.increase();
+    config.increase();
+    assert_eq!(config, at_level(BACKLIGHT_LEVELS));
+}
+
+#[test]
+fn decrease_stops_at_off() {
+    let mut config = at_level(1);
+    config.decrease();
+    assert_eq!(config, at_level(0));
+    config.decrease();
+    assert_eq!(config, at_level(0));
+}
+
+#[test]
+fn toggle_on_restores_a_level() {
+    let mut config = at_level(0);
+    config.toggle();
+    assert_eq!(config, at_level(BACKLIGHT_LEVELS));
+}
+
+#[test]
+fn breathing_curve_endpoints() {
+    assert_eq!(breathing_curve(0, 4000), 0);
+    assert_eq!(breathing_curve(2000, 4000), 255);
+    assert_eq!(breathing_curve(4000, 4000), 0);
+    assert_eq!(breathing_curve(6000, 4000), 255);
+}
@@ -0,0 +1,31 @@
+use keyboard_hal::bootmagic::{check, Action};
+use keyboard_hal::keyboard_config::{
+    BOOTMAGIC_EEPROM_CLEAR_KEY, BOOTMAGIC_KEY, LOCAL_ROWS, MATRIX_COLS,
+};
+
+fn state(keys: &[(usize, usize)]) -> [[bool; MATRIX_COLS]; LOCAL_ROWS] {
+    let mut state = [[false; MATRIX_COLS]; LOCAL_ROWS];
+    for &(row, col) in keys {
+        state[row][col] = true;
+    }
+    state
+}
+
+#[test]
+fn nothing_held_boots_normally() {
+    assert_eq!(check(&state(&[])), Action::None);
+    assert_eq!(check(&state(&[(1, 1)])), Action::None);
+}
+
+#[test]
+fn bootmagic_key_enters_bootloader() {
+    assert_eq!(check(&state(&[BOOTMAGIC_KEY])), Action::Bootloader);
+    assert_eq!(check(&state(&[BOOTMAGIC_KEY, (1, 1)])), Action::Bootloader);
+}
+
+#[test]
+fn second_key_clears_eeprom() {
+    let clear = BOOTMAGIC_EEPROM_CLEAR_KEY.unwrap();
+    assert_eq!(check(&state(&[BOOTMAGIC_KEY, clear])), Action::ClearEeprom);
+    assert_eq!(check(&state(&[clear])), Action::None);
+}
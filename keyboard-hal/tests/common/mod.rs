@@ -0,0 +1,125 @@
+//! Test harness which feeds scripted key events into a [`Pipeline`] and records its output.
+#![allow(dead_code)]
+
+use keyboard_hal::encoder::Direction;
+use keyboard_hal::hooks::KeyboardHooks;
+use keyboard_hal::keycodes::Keycode;
+use keyboard_hal::keymaps::{ENCODER_MAPS, KEYMAPS};
+use keyboard_hal::layers::Layers;
+use keyboard_hal::pipeline::{KeyEvent, Output, Pipeline, Report};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Step {
+    Press(usize, usize),
+    Release(usize, usize),
+    Encoder(usize, Direction),
+    /// Only let the time pass.
+    Idle,
+}
+
+/// Everything the pipeline emitted, with the time of the event that caused it.
+#[derive(Default)]
+pub struct Recorder {
+    time: u32,
+    pub reports: Vec<(u32, Report)>,
+    pub consumer_reports: Vec<(u32, u16)>,
+    pub keycodes: Vec<(u32, Keycode, bool)>,
+}
+
+impl Output for Recorder {
+    fn send_report(&mut self, report: &Report) {
+        self.reports.push((self.time, *report));
+    }
+
+    fn send_consumer_report(&mut self, usage: u16) {
+        self.consumer_reports.push((self.time, usage));
+    }
+
+    fn process_keycode(&mut self, keycode: Keycode, pressed: bool, time: u32) {
+        self.keycodes.push((time, keycode, pressed));
+    }
+}
+
+pub struct Harness<H: KeyboardHooks = ()> {
+    pub pipeline: Pipeline<H>,
+    pub out: Recorder,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Harness {
+            pipeline: Pipeline::new(Layers::new()),
+            out: Recorder::default(),
+        }
+    }
+
+    /// Harness with the default keymap, changed at the given positions of layer 0.
+    pub fn with_keys(keys: &[((usize, usize), Keycode)]) -> Self {
+        let mut keymaps = KEYMAPS;
+        for &((row, col), keycode) in keys {
+            keymaps[0][row][col] = keycode;
+        }
+        Harness {
+            pipeline: Pipeline::new(Layers::from_keymaps(keymaps, ENCODER_MAPS)),
+            out: Recorder::default(),
+        }
+    }
+}
+
+impl<H: KeyboardHooks> Harness<H> {
+    pub fn with_hooks<H2: KeyboardHooks>(self, hooks: H2) -> Harness<H2> {
+        Harness {
+            pipeline: self.pipeline.with_hooks(hooks),
+            out: self.out,
+        }
+    }
+
+    /// Feed `(time, step)` pairs into the pipeline.  Timestamps must not go backwards.
+    pub fn run(&mut self, script: &[(u32, Step)]) -> &mut Self {
+        for &(time, step) in script {
+            assert!(
+                time >= self.out.time,
+                "script goes back in time at {time}ms"
+            );
+            self.out.time = time;
+            // Keyboard::poll ticks the pipeline before every scan
+            self.pipeline.tick(time, &mut self.out);
+            match step {
+                Step::Press(row, col) => self
+                    .pipeline
+                    .process_event(KeyEvent::press(row, col, time), &mut self.out),
+                Step::Release(row, col) => self
+                    .pipeline
+                    .process_event(KeyEvent::release(row, col, time), &mut self.out),
+                Step::Encoder(index, direction) => {
+                    self.pipeline
+                        .process_encoder(index, direction, time, &mut self.out)
+                }
+                Step::Idle => {}
+            }
+        }
+        self
+    }
+
+    /// Reports sent so far, without timestamps.
+    pub fn reports(&self) -> Vec<Report> {
+        self.out.reports.iter().map(|&(_, report)| report).collect()
+    }
+
+    #[track_caller]
+    pub fn assert_reports(&self, expected: &[Report]) {
+        assert_eq!(self.reports(), expected);
+    }
+}
+
+/// Build the report expected for the given keys being held.
+pub fn report(keys: &[Keycode]) -> Report {
+    let mut report = Report::default();
+    for &key in keys {
+        assert!(
+            report.press(key.hid_usage().unwrap()),
+            "{key:?} does not fit into the report"
+        );
+    }
+    report
+}
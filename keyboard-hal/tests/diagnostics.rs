@@ -0,0 +1,103 @@
+use keyboard_hal::diagnostics::{Diagnostics, REPORT_INTERVAL_MS};
+use keyboard_hal::keyboard_config::{MATRIX_COLS, MATRIX_ROWS};
+
+#[derive(Default)]
+struct Buffer(String);
+
+impl ufmt::uWrite for Buffer {
+    type Error = core::convert::Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.0.push_str(s);
+        Ok(())
+    }
+}
+
+fn state(keys: &[(usize, usize)]) -> [[bool; MATRIX_COLS]; MATRIX_ROWS] {
+    let mut state = [[false; MATRIX_COLS]; MATRIX_ROWS];
+    for &(row, col) in keys {
+        state[row][col] = true;
+    }
+    state
+}
+
+fn active() -> Diagnostics {
+    let mut diagnostics = Diagnostics::new();
+    diagnostics.toggle();
+    diagnostics
+}
+
+#[test]
+fn inactive_prints_nothing() {
+    let mut diagnostics = Diagnostics::new();
+    let mut out = Buffer::default();
+    diagnostics.scan(&state(&[(0, 1)]), 0, &mut out).unwrap();
+    diagnostics
+        .scan(&state(&[]), REPORT_INTERVAL_MS, &mut out)
+        .unwrap();
+
+    assert!(out.0.is_empty());
+}
+
+#[test]
+fn prints_matrix_on_change() {
+    let mut diagnostics = active();
+    let mut out = Buffer::default();
+    diagnostics.scan(&state(&[]), 0, &mut out).unwrap();
+    assert!(out.0.is_empty());
+
+    diagnostics.scan(&state(&[(1, 3)]), 10, &mut out).unwrap();
+    let lines: Vec<&str> = out.0.lines().collect();
+    assert_eq!(lines.len(), MATRIX_ROWS + 1);
+    assert!(lines[0].starts_with("r/c 012345"));
+    assert_eq!(lines[1], format!("00: {}", "0".repeat(MATRIX_COLS)));
+    assert_eq!(lines[2], format!("01: 0001{}", "0".repeat(MATRIX_COLS - 4)));
+}
+
+#[test]
+fn counts_bounces() {
+    let mut diagnostics = active();
+    let mut out = Buffer::default();
+    // Press at 100 with chatter, then a clean release much later
+    for (time, pressed) in [(100, true), (101, false), (102, true), (300, false)] {
+        let keys: &[(usize, usize)] = if pressed { &[(2, 2)] } else { &[] };
+        diagnostics.scan(&state(keys), time, &mut out).unwrap();
+    }
+
+    assert_eq!(diagnostics.bounces(2, 2), 2);
+    assert_eq!(diagnostics.bounces(0, 0), 0);
+}
+
+#[test]
+fn reports_scan_rate_and_bounces() {
+    let mut diagnostics = active();
+    let mut out = Buffer::default();
+    diagnostics.scan(&state(&[(0, 1)]), 0, &mut out).unwrap();
+    diagnostics.scan(&state(&[]), 1, &mut out).unwrap();
+    out.0.clear();
+
+    for time in 2..REPORT_INTERVAL_MS {
+        diagnostics.scan(&state(&[]), time, &mut out).unwrap();
+    }
+    assert!(out.0.is_empty());
+
+    diagnostics
+        .scan(&state(&[]), REPORT_INTERVAL_MS, &mut out)
+        .unwrap();
+    assert_eq!(out.0, "scan rate: 1001/s, bounces: r0c1=1\n");
+}
+
+#[test]
+fn toggling_on_again_resets_counters() {
+    let mut diagnostics = active();
+    let mut out = Buffer::default();
+    diagnostics.scan(&state(&[(0, 1)]), 10, &mut out).unwrap();
+    diagnostics.scan(&state(&[]), 11, &mut out).unwrap();
+    assert_eq!(diagnostics.bounces(0, 1), 1);
+
+    diagnostics.toggle();
+    assert!(!diagnostics.is_active());
+    diagnostics.toggle();
+    assert!(diagnostics.is_active());
+    assert_eq!(diagnostics.bounces(0, 1), 0);
+}
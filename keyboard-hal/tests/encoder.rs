@@ -0,0 +1,49 @@
+use keyboard_hal::encoder::{Decoder, Direction, MAX_RESOLUTION};
+
+/// Pin levels `(a, b)` of one detent turned clockwise, starting from the resting position.
+const CLOCKWISE: [(bool, bool); 4] = [(true, false), (true, true), (false, true), (false, false)];
+
+fn turn(decoder: &mut Decoder, steps: &[(bool, bool)]) -> Vec<Direction> {
+    steps
+        .iter()
+        .filter_map(|&(a, b)| decoder.update(a, b))
+        .collect()
+}
+
+#[test]
+fn full_detent_is_one_step() {
+    let mut decoder = Decoder::new(4);
+    assert_eq!(turn(&mut decoder, &CLOCKWISE), [Direction::Clockwise]);
+
+    let mut counter_clockwise = CLOCKWISE;
+    counter_clockwise.reverse();
+    counter_clockwise.rotate_left(1);
+    assert_eq!(
+        turn(&mut decoder, &counter_clockwise),
+        [Direction::CounterClockwise]
+    );
+}
+
+#[test]
+fn resolution_one_steps_on_every_transition() {
+    let mut decoder = Decoder::new(1);
+    assert_eq!(turn(&mut decoder, &CLOCKWISE), [Direction::Clockwise; 4]);
+}
+
+#[test]
+fn highest_resolution_does_not_wrap() {
+    let mut decoder = Decoder::new(MAX_RESOLUTION);
+    assert!(turn(&mut decoder, &CLOCKWISE).is_empty());
+}
+
+#[test]
+#[should_panic(expected = "encoder resolution must be between 1 and 127")]
+fn rejects_resolution_zero() {
+    Decoder::new(0);
+}
+
+#[test]
+#[should_panic(expected = "encoder resolution must be between 1 and 127")]
+fn rejects_resolution_above_i8() {
+    Decoder::new(128);
+}
@@ -0,0 +1,73 @@
+use keyboard_hal::keycodes::prelude::*;
+use keyboard_hal::keycodes::LayerAction;
+
+#[test]
+fn basic_keys_are_hid_usages() {
+    assert_eq!(A.code(), 0x0004);
+    assert_eq!(A.hid_usage(), Some(0x04));
+    assert_eq!(F24.hid_usage(), Some(0x73));
+    assert_eq!(KpHexadecimal.hid_usage(), Some(0xDD));
+    assert_eq!(RGui.hid_usage(), Some(0xE7));
+    assert_eq!(A.mods(), 0);
+
+    assert_eq!(No.hid_usage(), None);
+    assert_eq!(Trans.hid_usage(), None);
+    assert_eq!(RgbToggle.hid_usage(), None);
+    assert_eq!(Reset.hid_usage(), None);
+}
+
+#[test]
+fn media_keys_are_consumer_usages() {
+    assert_eq!(Mute.consumer_usage(), Some(0x00E2));
+    assert_eq!(VolumeUp.consumer_usage(), Some(0x00E9));
+    assert_eq!(VolumeDown.consumer_usage(), Some(0x00EA));
+    assert_eq!(MediaPlayPause.consumer_usage(), Some(0x00CD));
+    assert_eq!(VolumeUp.hid_usage(), None);
+
+    assert_eq!(KbVolumeUp.hid_usage(), Some(0x80));
+    assert_eq!(KbVolumeUp.consumer_usage(), None);
+    assert_eq!(A.consumer_usage(), None);
+}
+
+#[test]
+fn modifier_wrapped_keys() {
+    assert_eq!(lsft(Num1).code(), 0x021E);
+    assert_eq!(lsft(Num1).hid_usage(), Some(0x1E));
+    assert_eq!(lsft(Num1).mods(), 0x02);
+    assert_eq!(lctl(lalt(Delete)).mods(), 0x05);
+    assert_eq!(ralt(E).mods(), 0x40);
+    assert_eq!(rctl(rsft(Tab)).mods(), 0x30);
+    assert_eq!(lgui(No).hid_usage(), None);
+    assert_eq!(lgui(No).mods(), 0x08);
+}
+
+#[test]
+fn layer_actions() {
+    assert_eq!(mo(1), MomentaryLayer1);
+    assert_eq!(mo(3).layer_action(), Some(LayerAction::Momentary(3)));
+    assert_eq!(tg(2).layer_action(), Some(LayerAction::Toggle(2)));
+    assert_eq!(to(0).layer_action(), Some(LayerAction::To(0)));
+    assert_eq!(mo(1).hid_usage(), None);
+    assert_eq!(A.layer_action(), None);
+}
+
+#[test]
+fn user_keycodes() {
+    assert_eq!(user(0).user_index(), Some(0));
+    assert_eq!(user(5).user_index(), Some(5));
+    assert_eq!(user(5).hid_usage(), None);
+    assert_eq!(A.user_index(), None);
+}
+
+#[test]
+fn debug_output() {
+    assert_eq!(format!("{:?}", Num1), "Num1");
+    assert_eq!(format!("{:?}", lctl(lsft(A))), "lctl(lsft(A))");
+    assert_eq!(format!("{:?}", ralt(E)), "ralt(E)");
+    assert_eq!(format!("{:?}", tg(2)), "tg(2)");
+    assert_eq!(format!("{:?}", user(3)), "user(3)");
+    assert_eq!(
+        format!("{:?}", Keycode::from_code(0x00A5)),
+        "Keycode(0x00a5)"
+    );
+}
@@ -0,0 +1,107 @@
+// Key positions below refer to the DZ60 default keymap
+#![cfg(any(feature = "dz60-rev2", feature = "dz60-rev3"))]
+
+mod common;
+
+use common::{report, Harness, Step::*};
+use keyboard_hal::hooks::KeyboardHooks;
+use keyboard_hal::keycodes::prelude::*;
+use keyboard_hal::leader::{LeaderAction, LeaderConfig, LeaderSequence};
+
+const KEY_LEADER: (usize, usize) = (4, 3);
+const KEY_E: (usize, usize) = (1, 3);
+const KEY_M: (usize, usize) = (3, 7);
+const KEY_A: (usize, usize) = (2, 1);
+const KEY_S: (usize, usize) = (2, 2);
+
+static SEQUENCES: &[LeaderSequence] = &[
+    LeaderSequence {
+        keys: &[E, M],
+        action: LeaderAction::Tap(&[A, lsft(Num2), B]),
+    },
+    LeaderSequence {
+        keys: &[E],
+        action: LeaderAction::Tap(&[Escape]),
+    },
+    LeaderSequence {
+        keys: &[S],
+        action: LeaderAction::User(7),
+    },
+];
+
+#[derive(Default)]
+struct Hooks {
+    actions: Vec<u16>,
+}
+
+impl KeyboardHooks for Hooks {
+    fn leader_action(&mut self, id: u16) {
+        self.actions.push(id);
+    }
+}
+
+fn harness() -> Harness<Hooks> {
+    let mut h = Harness::with_keys(&[(KEY_LEADER, Leader)]).with_hooks(Hooks::default());
+    h.pipeline = h.pipeline.with_leader(LeaderConfig::new(SEQUENCES));
+    h
+}
+
+fn tap(time: u32, (row, col): (usize, usize)) -> [(u32, common::Step); 2] {
+    [(time, Press(row, col)), (time + 10, Release(row, col))]
+}
+
+#[test]
+fn sequence_types_macro() {
+    let mut h = harness();
+    h.run(&[tap(0, KEY_LEADER), tap(50, KEY_E), tap(100, KEY_M)].concat());
+
+    h.assert_reports(&[
+        report(&[A]),
+        report(&[]),
+        report(&[LShift, Num2]),
+        report(&[]),
+        report(&[B]),
+        report(&[]),
+    ]);
+    assert!(h.out.reports.iter().all(|&(time, _)| time == 100));
+    assert!(!h.pipeline.leader().unwrap().is_active());
+}
+
+#[test]
+fn prefix_of_longer_sequence_waits_for_timeout() {
+    let mut h = harness();
+    h.run(&[tap(0, KEY_LEADER), tap(50, KEY_E)].concat());
+    h.assert_reports(&[]);
+
+    h.run(&[(349, Idle), (350, Idle)]);
+    h.assert_reports(&[report(&[Escape]), report(&[])]);
+    assert_eq!(h.out.reports[0].0, 350);
+}
+
+#[test]
+fn user_action_goes_to_hooks() {
+    let mut h = harness();
+    h.run(&[tap(0, KEY_LEADER), tap(50, KEY_S)].concat());
+
+    h.assert_reports(&[]);
+    assert_eq!(h.pipeline.hooks().actions, [7]);
+}
+
+#[test]
+fn unknown_sequence_is_dropped() {
+    let mut h = harness();
+    h.run(&[tap(0, KEY_LEADER), tap(50, KEY_A), tap(100, KEY_A)].concat());
+
+    // The first `A` ends the sequence, the second one is typed as usual
+    h.assert_reports(&[report(&[A]), report(&[])]);
+    assert_eq!(h.out.reports[0].0, 100);
+}
+
+#[test]
+fn timeout_without_keys() {
+    let mut h = harness();
+    h.run(&tap(0, KEY_LEADER));
+    h.run(&[(300, Idle), (310, Press(KEY_E.0, KEY_E.1))]);
+
+    h.assert_reports(&[report(&[E])]);
+}
@@ -0,0 +1,353 @@
+// Key positions below refer to the DZ60 default keymap
+#![cfg(any(feature = "dz60-rev2", feature = "dz60-rev3"))]
+
+mod common;
+
+use common::{report, Harness, Step::*};
+use keyboard_hal::encoder::Direction;
+use keyboard_hal::hooks::KeyboardHooks;
+use keyboard_hal::keyboard_config::{MATRIX_COLS, MATRIX_ROWS};
+use keyboard_hal::keycodes::prelude::*;
+
+// Positions in the default keymap
+const KEY_1: (usize, usize) = (0, 1);
+const KEY_TAB: (usize, usize) = (1, 0);
+const KEY_A: (usize, usize) = (2, 1);
+const KEY_S: (usize, usize) = (2, 2);
+const KEY_LSHIFT: (usize, usize) = (3, 0);
+const KEY_FN: (usize, usize) = (3, 12);
+const KEY_CTRL_LAYER: (usize, usize) = (4, 11);
+
+fn press((row, col): (usize, usize)) -> common::Step {
+    Press(row, col)
+}
+
+fn release((row, col): (usize, usize)) -> common::Step {
+    Release(row, col)
+}
+
+#[test]
+fn tap_sends_press_and_release() {
+    let mut h = Harness::new();
+    h.run(&[(0, press(KEY_A)), (30, release(KEY_A))]);
+
+    h.assert_reports(&[report(&[A]), report(&[])]);
+    assert_eq!(h.out.reports[0].0, 0);
+    assert_eq!(h.out.reports[1].0, 30);
+}
+
+#[test]
+fn modifiers_go_into_modifier_byte() {
+    let mut h = Harness::new();
+    h.run(&[
+        (0, press(KEY_LSHIFT)),
+        (10, press(KEY_A)),
+        (20, release(KEY_A)),
+        (30, release(KEY_LSHIFT)),
+    ]);
+
+    let reports = h.reports();
+    assert_eq!(reports[0].modifier, 0x02);
+    assert_eq!(reports[0].keycodes, [0; 6]);
+    h.assert_reports(&[
+        report(&[LShift]),
+        report(&[LShift, A]),
+        report(&[LShift]),
+        report(&[]),
+    ]);
+}
+
+#[test]
+fn overlapping_keys() {
+    let mut h = Harness::new();
+    h.run(&[
+        (0, press(KEY_A)),
+        (5, press(KEY_S)),
+        (10, release(KEY_A)),
+        (15, release(KEY_S)),
+    ]);
+
+    h.assert_reports(&[report(&[A]), report(&[A, S]), report(&[S]), report(&[])]);
+}
+
+#[test]
+fn momentary_layer() {
+    let mut h = Harness::new();
+    h.run(&[
+        (0, press(KEY_FN)),
+        (10, press(KEY_1)),
+        (20, release(KEY_1)),
+        (30, release(KEY_FN)),
+        (40, press(KEY_1)),
+        (50, release(KEY_1)),
+    ]);
+
+    h.assert_reports(&[report(&[F1]), report(&[]), report(&[Num1]), report(&[])]);
+}
+
+#[test]
+fn transparent_key_falls_through() {
+    let mut h = Harness::new();
+    h.run(&[
+        (0, press(KEY_FN)),
+        (10, press(KEY_TAB)),
+        (20, release(KEY_TAB)),
+    ]);
+
+    h.assert_reports(&[report(&[Tab]), report(&[])]);
+}
+
+#[test]
+fn release_after_layer_change_releases_original_key() {
+    let mut h = Harness::new();
+    h.run(&[
+        (0, press(KEY_FN)),
+        (10, press(KEY_1)),
+        (20, release(KEY_FN)),
+        (30, release(KEY_1)),
+    ]);
+
+    h.assert_reports(&[report(&[F1]), report(&[])]);
+    assert_eq!(h.pipeline.layers().current_layer, 0);
+}
+
+#[test]
+fn firmware_keycodes_are_not_sent() {
+    let mut h = Harness::new();
+    h.run(&[
+        (0, press(KEY_FN)),
+        (10, press(KEY_CTRL_LAYER)),
+        (20, Press(2, 1)),
+        (30, Release(2, 1)),
+    ]);
+
+    h.assert_reports(&[]);
+    assert!(h.out.keycodes.contains(&(20, RgbToggle, true)));
+    assert!(h.out.keycodes.contains(&(30, RgbToggle, false)));
+}
+
+#[test]
+fn seventh_key_is_dropped() {
+    let keys = [(2, 1), (2, 2), (2, 3), (2, 4), (2, 5), (2, 6), (2, 7)];
+    let mut h = Harness::new();
+    let script: Vec<_> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, &key)| (i as u32, press(key)))
+        .collect();
+    h.run(&script);
+
+    assert_eq!(h.reports().len(), 6);
+    assert_eq!(
+        *h.pipeline.report(),
+        report(&[A, S, D, F, G, H]),
+        "J must not replace a held key"
+    );
+}
+
+#[test]
+fn repeated_events_are_ignored() {
+    let mut h = Harness::new();
+    h.run(&[
+        (0, press(KEY_A)),
+        (10, press(KEY_A)),
+        (20, release(KEY_A)),
+        (30, release(KEY_A)),
+    ]);
+
+    h.assert_reports(&[report(&[A]), report(&[])]);
+}
+
+#[test]
+fn encoder_step_taps_mapped_key() {
+    let mut h = Harness::new();
+    h.run(&[
+        (0, Encoder(0, Direction::Clockwise)),
+        (10, press(KEY_FN)),
+        (20, Encoder(0, Direction::CounterClockwise)),
+    ]);
+
+    // Volume goes through the consumer report, the page keys through the keyboard report
+    assert_eq!(h.out.consumer_reports, [(0, 0x00E9), (0, 0)]);
+    h.assert_reports(&[report(&[PgUp]), report(&[])]);
+}
+
+#[test]
+fn media_keys_use_consumer_report() {
+    let mut h = Harness::with_keys(&[(KEY_A, Mute), (KEY_S, MediaPlayPause)]);
+    h.run(&[
+        (0, press(KEY_A)),
+        (10, press(KEY_S)),
+        (20, release(KEY_A)),
+        (30, release(KEY_S)),
+    ]);
+
+    // The key pressed last wins, releasing the other one does not clear it
+    assert_eq!(h.out.consumer_reports, [(0, 0x00E2), (10, 0x00CD), (30, 0)]);
+    assert!(h.out.reports.is_empty());
+}
+
+#[test]
+fn grave_esc_follows_shift() {
+    let mut h = Harness::with_keys(&[(KEY_A, GraveEsc)]);
+    h.run(&[
+        (0, press(KEY_A)),
+        (10, release(KEY_A)),
+        (20, press(KEY_LSHIFT)),
+        (30, press(KEY_A)),
+        // Releasing Shift first still releases the Grave sent on press
+        (40, release(KEY_LSHIFT)),
+        (50, release(KEY_A)),
+    ]);
+
+    h.assert_reports(&[
+        report(&[Escape]),
+        report(&[]),
+        report(&[LShift]),
+        report(&[LShift, Grave]),
+        report(&[Grave]),
+        report(&[]),
+    ]);
+}
+
+#[test]
+fn matrix_scan_is_diffed() {
+    let mut h = Harness::new();
+    let mut state = [[false; MATRIX_COLS]; MATRIX_ROWS];
+
+    state[KEY_A.0][KEY_A.1] = true;
+    state[KEY_S.0][KEY_S.1] = true;
+    h.pipeline.process_matrix(&state, 0, &mut h.out);
+    // Same scan again, nothing changed
+    h.pipeline.process_matrix(&state, 1, &mut h.out);
+
+    state[KEY_A.0][KEY_A.1] = false;
+    h.pipeline.process_matrix(&state, 2, &mut h.out);
+
+    h.assert_reports(&[report(&[A]), report(&[A, S]), report(&[S])]);
+    assert_eq!(h.pipeline.state(), &state);
+}
+
+#[test]
+fn modifier_wrapped_key() {
+    let mut h = Harness::with_keys(&[(KEY_A, lsft(Num1))]);
+    h.run(&[(0, press(KEY_A)), (10, release(KEY_A))]);
+
+    h.assert_reports(&[report(&[LShift, Num1]), report(&[])]);
+}
+
+#[test]
+fn toggle_and_to_layer() {
+    let mut h = Harness::with_keys(&[(KEY_A, tg(1)), (KEY_TAB, to(0))]);
+    h.run(&[
+        (0, press(KEY_A)),
+        (10, release(KEY_A)),
+        (20, press(KEY_1)),
+        (30, release(KEY_1)),
+    ]);
+    assert_eq!(h.pipeline.layers().current_layer, 1);
+
+    // Tab is transparent on layer 1, so the `TO(0)` from layer 0 applies
+    h.run(&[
+        (40, press(KEY_TAB)),
+        (50, release(KEY_TAB)),
+        (60, press(KEY_1)),
+    ]);
+    assert_eq!(h.pipeline.layers().current_layer, 0);
+    h.assert_reports(&[report(&[F1]), report(&[]), report(&[Num1])]);
+}
+
+#[test]
+fn momentary_layer_returns_to_toggled_layer() {
+    let mut h = Harness::with_keys(&[(KEY_A, tg(2))]);
+    h.run(&[
+        (0, press(KEY_A)),
+        (10, release(KEY_A)),
+        (20, press(KEY_FN)),
+        (30, release(KEY_FN)),
+    ]);
+
+    assert_eq!(h.pipeline.layers().current_layer, 2);
+}
+
+/// Swallows `A` and records what it was called with.
+#[derive(Default)]
+struct Hooks {
+    records: Vec<(Keycode, bool, usize, usize)>,
+    layers: Vec<usize>,
+}
+
+impl KeyboardHooks for Hooks {
+    fn process_record(&mut self, keycode: Keycode, pressed: bool, row: usize, col: usize) -> bool {
+        self.records.push((keycode, pressed, row, col));
+        keycode != A
+    }
+
+    fn layer_state_changed(&mut self, layer: usize) {
+        self.layers.push(layer);
+    }
+}
+
+#[test]
+fn hook_sees_records_and_can_swallow_keys() {
+    let mut h = Harness::new().with_hooks(Hooks::default());
+    h.run(&[
+        (0, press(KEY_A)),
+        (10, press(KEY_S)),
+        (20, release(KEY_A)),
+        (30, release(KEY_S)),
+    ]);
+
+    h.assert_reports(&[report(&[S]), report(&[])]);
+    assert!(!h.out.keycodes.iter().any(|&(_, keycode, _)| keycode == A));
+    assert_eq!(
+        h.pipeline.hooks().records,
+        [
+            (A, true, KEY_A.0, KEY_A.1),
+            (S, true, KEY_S.0, KEY_S.1),
+            (A, false, KEY_A.0, KEY_A.1),
+            (S, false, KEY_S.0, KEY_S.1),
+        ]
+    );
+}
+
+#[test]
+fn hook_is_told_about_layer_changes() {
+    let mut h = Harness::new().with_hooks(Hooks::default());
+    h.run(&[
+        (0, press(KEY_FN)),
+        (10, press(KEY_1)),
+        (20, release(KEY_1)),
+        (30, release(KEY_FN)),
+    ]);
+
+    assert_eq!(h.pipeline.hooks().layers, [1, 0]);
+
+    // Setting the current layer again is not a change
+    h.pipeline.set_layer(0);
+    h.pipeline.set_layer(2);
+    assert_eq!(h.pipeline.hooks().layers, [1, 0, 2]);
+}
+
+#[test]
+fn hook_is_not_told_about_layer_keys_without_change() {
+    let mut h = Harness::with_keys(&[(KEY_A, to(0)), (KEY_S, tg(1))]).with_hooks(Hooks::default());
+    h.run(&[
+        (0, press(KEY_A)),
+        (10, release(KEY_A)),
+        (20, press(KEY_S)),
+        (30, release(KEY_S)),
+    ]);
+
+    assert_eq!(h.pipeline.hooks().layers, [1]);
+}
+
+#[test]
+fn hid_report_conversion() {
+    let hid = report(&[LCtrl, RAlt, Keycode::Z]).to_hid();
+    assert_eq!(hid.modifier, 0x41);
+    assert_eq!(
+        hid.keycodes,
+        [Keycode::Z.hid_usage().unwrap(), 0, 0, 0, 0, 0]
+    );
+}
@@ -0,0 +1,62 @@
+use keyboard_hal::rgb::{Hsv, RgbConfig, RgbMode};
+use keyboard_hal::ws2812::Rgb;
+
+const MODES: [RgbMode; 5] = [
+    RgbMode::Static,
+    RgbMode::Breathing,
+    RgbMode::Rainbow,
+    RgbMode::Swirl,
+    RgbMode::Reactive,
+];
+
+#[test]
+fn primary_hues() {
+    // Hues are 0-255 for the full circle, so green and blue are off by the integer rounding
+    assert_eq!(Hsv::new(0, 255, 255).to_rgb(), Rgb::new(255, 0, 0));
+    assert_eq!(Hsv::new(85, 255, 255).to_rgb(), Rgb::new(1, 255, 0));
+    assert_eq!(Hsv::new(170, 255, 255).to_rgb(), Rgb::new(0, 3, 255));
+}
+
+#[test]
+fn no_saturation_is_white() {
+    assert_eq!(Hsv::new(42, 0, 200).to_rgb(), Rgb::new(200, 200, 200));
+}
+
+#[test]
+fn no_value_is_off() {
+    for h in [0, 85, 170, 255] {
+        assert_eq!(Hsv::new(h, 255, 0).to_rgb(), Rgb::new(0, 0, 0));
+    }
+}
+
+#[test]
+fn config_round_trips() {
+    for mode in MODES {
+        for enabled in [false, true] {
+            let config = RgbConfig {
+                enabled,
+                mode,
+                hsv: Hsv::new(12, 34, 56),
+                speed: 2,
+            };
+            assert_eq!(RgbConfig::from_bytes(config.to_bytes()), config);
+        }
+    }
+}
+
+#[test]
+fn stored_speed_is_clamped() {
+    let config = RgbConfig::from_bytes([0x80 | RgbMode::Swirl as u8, 0, 0, 0, 0xFF]);
+    assert_eq!(config.mode, RgbMode::Swirl);
+    assert_eq!(config.speed, 3);
+}
+
+#[test]
+fn modes_wrap_around() {
+    assert_eq!(RgbMode::Reactive.next(), RgbMode::Static);
+    assert_eq!(RgbMode::Static.previous(), RgbMode::Reactive);
+    for (mode, next) in MODES.iter().zip(MODES.iter().cycle().skip(1)) {
+        assert_eq!(mode.next(), *next);
+        assert_eq!(next.previous(), *mode);
+    }
+}
@@ -0,0 +1,138 @@
+#![cfg(feature = "split")]
+
+use keyboard_hal::backlight::BacklightConfig;
+use keyboard_hal::keyboard_config::{LOCAL_ROWS, MATRIX_COLS};
+use keyboard_hal::rgb::RgbConfig;
+use keyboard_hal::split::{
+    crc8, decode_matrix, encode_matrix, FrameReceiver, LocalState, SyncState, REQUEST_LEN,
+    RESPONSE_LEN, SYNC,
+};
+
+fn sync_state() -> SyncState {
+    SyncState {
+        layer: 2,
+        host_leds: 0b10,
+        backlight: BacklightConfig::default(),
+        rgb: RgbConfig::default(),
+    }
+}
+
+fn local_state(keys: &[(usize, usize)]) -> LocalState {
+    let mut state = [[false; MATRIX_COLS]; LOCAL_ROWS];
+    for &(row, col) in keys {
+        state[row][col] = true;
+    }
+    state
+}
+
+/// Feed `bytes` into `receiver` and collect the frames it completed.
+fn receive<const N: usize>(receiver: &mut FrameReceiver<N>, bytes: &[u8]) -> Vec<[u8; N]> {
+    bytes
+        .iter()
+        .filter_map(|&byte| receiver.push(byte))
+        .collect()
+}
+
+#[test]
+fn crc8_matches_reference() {
+    // CRC-8/SMBUS check value
+    assert_eq!(crc8(b"123456789"), 0xF4);
+    assert_eq!(crc8(&[]), 0x00);
+    assert_eq!(crc8(&[0x00]), 0x00);
+    assert_eq!(crc8(&[0x01]), 0x07);
+}
+
+#[test]
+fn sync_state_round_trips() {
+    let frame = sync_state().encode();
+    assert_eq!(frame[0], SYNC);
+    assert_eq!(SyncState::decode(&frame), Some(sync_state()));
+}
+
+#[test]
+fn matrix_round_trips() {
+    let state = local_state(&[(0, 0), (1, MATRIX_COLS - 1), (LOCAL_ROWS - 1, 3)]);
+    let frame = encode_matrix(&state);
+    assert_eq!(frame[0], SYNC);
+    assert_eq!(decode_matrix(&frame), Some(state));
+}
+
+#[test]
+fn corrupted_frames_are_rejected() {
+    let mut frame = sync_state().encode();
+    frame[1] ^= 0x01;
+    assert_eq!(SyncState::decode(&frame), None);
+
+    let mut frame = encode_matrix(&local_state(&[(0, 0)]));
+    frame[0] = 0x00;
+    assert_eq!(decode_matrix(&frame), None);
+}
+
+#[test]
+fn receiver_skips_bytes_before_sync() {
+    let frame = sync_state().encode();
+    let mut receiver = FrameReceiver::<REQUEST_LEN>::new();
+
+    let mut bytes = vec![0x00, 0xFF, 0x12];
+    bytes.extend_from_slice(&frame);
+    assert_eq!(receive(&mut receiver, &bytes), [frame]);
+}
+
+#[test]
+fn receiver_resyncs_after_short_frame() {
+    let frame = encode_matrix(&local_state(&[(1, 2)]));
+    let mut receiver = FrameReceiver::<RESPONSE_LEN>::new();
+
+    // The start of a frame which was cut off, followed by a complete one
+    let mut bytes = frame[..2].to_vec();
+    bytes.extend_from_slice(&frame);
+    bytes.extend_from_slice(&frame);
+    assert_eq!(receive(&mut receiver, &bytes), [frame, frame]);
+}
+
+#[test]
+fn receiver_resyncs_after_corrupted_frame() {
+    let frame = sync_state().encode();
+    let mut corrupted = frame;
+    corrupted[REQUEST_LEN - 1] ^= 0xFF;
+    let mut receiver = FrameReceiver::<REQUEST_LEN>::new();
+
+    let mut bytes = corrupted.to_vec();
+    bytes.extend_from_slice(&frame);
+    assert_eq!(receive(&mut receiver, &bytes), [frame]);
+}
+
+#[test]
+fn receiver_resyncs_after_dropped_bytes() {
+    let frame = sync_state().encode();
+    let mut receiver = FrameReceiver::<REQUEST_LEN>::new();
+
+    // Two bytes of the first frame were overrun
+    let mut bytes = frame.to_vec();
+    bytes.drain(3..5);
+    bytes.extend_from_slice(&frame);
+    bytes.extend_from_slice(&frame);
+    assert_eq!(receive(&mut receiver, &bytes), [frame, frame]);
+}
+
+#[test]
+fn reset_drops_partial_frame() {
+    let frame = sync_state().encode();
+    let mut receiver = FrameReceiver::<REQUEST_LEN>::new();
+
+    assert!(receive(&mut receiver, &frame[..3]).is_empty());
+    receiver.reset();
+    assert!(receive(&mut receiver, &frame[3..]).is_empty());
+    assert_eq!(receive(&mut receiver, &frame), [frame]);
+}
+
+#[cfg(feature = "lets-split-rev2")]
+#[test]
+fn right_half_is_mirrored_in_keymap() {
+    use keyboard_hal::keycodes::prelude::*;
+    use keyboard_hal::keymaps::KEYMAPS;
+
+    assert_eq!(KEYMAPS[0][0][..], [Tab, Q, W, E, R, T]);
+    assert_eq!(KEYMAPS[0][LOCAL_ROWS][..], [BSpace, P, O, I, U, Y]);
+    assert_eq!(KEYMAPS[0][LOCAL_ROWS + 3][0], Right);
+}
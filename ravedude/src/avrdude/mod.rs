@@ -47,6 +47,8 @@ impl Avrdude {
         options: &BoardAvrdudeOptions,
         port: Option<impl AsRef<path::Path>>,
         bin: &path::Path,
+        eeprom: bool,
+        fuses: &[(&str, u8)],
         debug: bool,
     ) -> anyhow::Result<Self> {
         let avrdude_version = Self::get_avrdude_version()?;
@@ -109,6 +111,17 @@ impl Avrdude {
 
         command = command.arg("-D").arg("-U").arg(flash_instruction);
 
+        if eeprom {
+            let mut eeprom_instruction: std::ffi::OsString = "eeprom:w:".into();
+            eeprom_instruction.push(bin);
+            eeprom_instruction.push(":e");
+            command = command.arg("-U").arg(eeprom_instruction);
+        }
+
+        for (name, value) in fuses {
+            command = command.arg("-U").arg(format!("{name}:w:{value:#04x}:m"));
+        }
+
         if debug {
             crate::task_message!(
                 "Dbg.Command",
@@ -1,8 +1,10 @@
-use std::{collections::HashMap, path::Path};
+use anyhow::Context as _;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use crate::config;
 
-fn get_all_boards() -> anyhow::Result<HashMap<String, config::BoardConfig>> {
+pub fn get_builtin_boards() -> anyhow::Result<HashMap<String, config::BoardConfig>> {
     toml::from_str(include_str!("boards.toml")).map_err(|err| {
         if cfg!(test) {
             anyhow::anyhow!(
@@ -18,8 +20,157 @@ fn get_all_boards() -> anyhow::Result<HashMap<String, config::BoardConfig>> {
     })
 }
 
-pub fn get_board_from_name(board_name: &str) -> anyhow::Result<config::RavedudeConfig> {
-    let mut all_boards = get_all_boards()?;
+/// The board file of the user, `~/.config/ravedude/boards.toml`.
+fn user_board_file() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => PathBuf::from(std::env::var_os("APPDATA")?),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("ravedude").join("boards.toml"))
+}
+
+fn read_board_file(path: &Path) -> anyhow::Result<HashMap<String, config::BoardConfig>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read board file `{}`", path.display()))?;
+    toml::from_str(&contents)
+        .map_err(|err| anyhow::anyhow!("invalid board file `{}`:\n{}", path.display(), err))
+}
+
+/// Boards of several catalogs, with the catalog each one was defined in.
+#[derive(Default)]
+struct Catalog {
+    boards: HashMap<String, config::BoardConfig>,
+    sources: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Add the boards of another catalog.  They may inherit from boards of this or an earlier
+    /// catalog.  A board which is already defined can only be extended, by inheriting from its
+    /// own name.
+    fn add(
+        &mut self,
+        source: &str,
+        boards: HashMap<String, config::BoardConfig>,
+    ) -> anyhow::Result<()> {
+        let mut names: Vec<_> = boards.keys().cloned().collect();
+        names.sort();
+
+        let mut resolved = HashMap::new();
+        for name in &names {
+            if let Some(previous) = self.sources.get(name) {
+                if boards[name].inherit.as_deref() != Some(name) {
+                    anyhow::bail!(
+                        "board `{}` in {} is already defined in {}; set `inherit = \"{}\"` to extend that definition",
+                        name,
+                        source,
+                        previous,
+                        name
+                    );
+                }
+            }
+            self.resolve(source, name, &boards, &mut resolved, &mut Vec::new())?;
+        }
+
+        for name in names {
+            let board = resolved.remove(&name).unwrap();
+            self.boards.insert(name.clone(), board);
+            self.sources.insert(name, source.to_string());
+        }
+        Ok(())
+    }
+
+    /// Merge a board of the catalog being added with the boards it inherits from.  `chain` holds
+    /// the boards which inherit from this one, for reporting cycles and unknown boards.
+    fn resolve(
+        &self,
+        source: &str,
+        name: &str,
+        boards: &HashMap<String, config::BoardConfig>,
+        resolved: &mut HashMap<String, config::BoardConfig>,
+        chain: &mut Vec<String>,
+    ) -> anyhow::Result<config::BoardConfig> {
+        if let Some(board) = resolved.get(name) {
+            return Ok(board.clone());
+        }
+        chain.push(name.to_string());
+
+        let board = boards[name].clone();
+        let board = match board.inherit.clone() {
+            None => board,
+            // Extend the definition of an earlier catalog
+            Some(base) if base == name => match self.boards.get(name) {
+                Some(base_board) => board.merge(base_board.clone()),
+                None => anyhow::bail!(
+                    "board `{}` in {} inherits from itself, but no earlier definition of `{}` exists",
+                    name,
+                    source,
+                    name
+                ),
+            },
+            Some(base) if chain.contains(&base) => anyhow::bail!(
+                "boards in {} inherit from each other in a cycle: {} -> {}",
+                source,
+                chain.join(" -> "),
+                base
+            ),
+            Some(base) if boards.contains_key(&base) => {
+                let base_board = self.resolve(source, &base, boards, resolved, chain)?;
+                board.merge(base_board)
+            }
+            Some(base) => match self.boards.get(&base) {
+                Some(base_board) => board.merge(base_board.clone()),
+                None => anyhow::bail!(
+                    "board `{}` in {} inherits from unknown board `{}` ({} -> {})",
+                    name,
+                    source,
+                    base,
+                    chain.join(" -> "),
+                    base
+                ),
+            },
+        };
+
+        chain.pop();
+        resolved.insert(name.to_string(), board.clone());
+        Ok(board)
+    }
+}
+
+/// All known boards: the built-in ones, those of the user's board file and those of
+/// `board_files`, in this order.
+pub fn get_all_boards(
+    board_files: &[PathBuf],
+) -> anyhow::Result<HashMap<String, config::BoardConfig>> {
+    let mut catalog = Catalog::default();
+    catalog.add("the built-in boards", get_builtin_boards()?)?;
+
+    let user_board_file = user_board_file().filter(|path| path.exists());
+    for path in user_board_file.iter().chain(board_files) {
+        catalog.add(&format!("`{}`", path.display()), read_board_file(path)?)?;
+    }
+    Ok(catalog.boards)
+}
+
+/// The boards known to a project, including those of the board files listed in its
+/// `Ravedude.toml`.
+pub fn get_project_boards(
+    manifest_path: Option<&Path>,
+) -> anyhow::Result<HashMap<String, config::BoardConfig>> {
+    match manifest_path {
+        Some(manifest_path) => {
+            let manifest = read_manifest(manifest_path)?;
+            get_all_boards(&board_files(manifest_path, &manifest.general_options))
+        }
+        None => get_all_boards(&[]),
+    }
+}
+
+pub fn get_board_from_name(
+    board_name: &str,
+    board_files: &[PathBuf],
+) -> anyhow::Result<config::RavedudeConfig> {
+    let mut all_boards = get_all_boards(board_files)?;
 
     Ok(config::RavedudeConfig {
         board_config: Some(all_boards.remove(board_name).ok_or_else(|| {
@@ -27,9 +178,11 @@ pub fn get_board_from_name(board_name: &str) -> anyhow::Result<config::RavedudeC
 
             msg.push_str("valid boards:");
 
-            for board in all_boards.keys() {
+            let mut names: Vec<_> = all_boards.keys().collect();
+            names.sort();
+            for board in names {
                 msg.push('\n');
-                msg.push_str(&board);
+                msg.push_str(board);
             }
             anyhow::anyhow!(msg)
         })?),
@@ -37,13 +190,28 @@ pub fn get_board_from_name(board_name: &str) -> anyhow::Result<config::RavedudeC
     })
 }
 
+fn read_manifest(manifest_path: &Path) -> anyhow::Result<config::RavedudeConfig> {
+    let file_contents = std::fs::read_to_string(manifest_path)
+        .map_err(|err| anyhow::anyhow!("Ravedude.toml read error:\n{}", err))?;
+
+    toml::from_str(&file_contents).map_err(|err| anyhow::anyhow!("invalid Ravedude.toml:\n{}", err))
+}
+
+/// The board files listed in `Ravedude.toml`, relative to its directory.
+fn board_files(manifest_path: &Path, general: &config::RavedudeGeneralConfig) -> Vec<PathBuf> {
+    let manifest_dir = manifest_path.parent().unwrap_or(Path::new("."));
+    general
+        .board_files
+        .iter()
+        .flatten()
+        .map(|path| manifest_dir.join(path))
+        .collect()
+}
+
 pub fn get_board_from_manifest(manifest_path: &Path) -> anyhow::Result<config::RavedudeConfig> {
     Ok({
-        let file_contents = std::fs::read_to_string(manifest_path)
-            .map_err(|err| anyhow::anyhow!("Ravedude.toml read error:\n{}", err))?;
-
-        let mut board: config::RavedudeConfig = toml::from_str(&file_contents)
-            .map_err(|err| anyhow::anyhow!("invalid Ravedude.toml:\n{}", err))?;
+        let mut board = read_manifest(manifest_path)?;
+        let board_files = board_files(manifest_path, &board.general_options);
 
         if let Some(board_config) = board.board_config.as_ref() {
             if let Some(board_name) = board.general_options.board.as_deref() {
@@ -53,11 +221,15 @@ pub fn get_board_from_manifest(manifest_path: &Path) -> anyhow::Result<config::R
                 )
             }
             if let Some(inherit) = board_config.inherit.as_deref() {
-                let base_board = get_board_from_name(inherit)?.board_config.unwrap();
+                let base_board = get_board_from_name(inherit, &board_files)?
+                    .board_config
+                    .unwrap();
                 board.board_config = Some(board.board_config.take().unwrap().merge(base_board));
             }
         } else if let Some(board_name) = board.general_options.board.as_deref() {
-            let base_board = get_board_from_name(board_name)?.board_config.unwrap();
+            let base_board = get_board_from_name(board_name, &board_files)?
+                .board_config
+                .unwrap();
             board.board_config = Some(base_board);
         }
         board
@@ -66,11 +238,12 @@ pub fn get_board_from_manifest(manifest_path: &Path) -> anyhow::Result<config::R
 
 #[cfg(test)]
 mod tests {
-    use super::get_all_boards;
+    use super::*;
+    use crate::config::BoardUSBInfo;
 
     #[test]
     fn validate_board_list() -> anyhow::Result<()> {
-        let all_boards = get_all_boards()?;
+        let all_boards = get_builtin_boards()?;
 
         for (name, board) in all_boards.iter() {
             assert!(
@@ -89,6 +262,20 @@ mod tests {
                 board.avrdude.is_some(),
                 "Board {name:?} doesn't have an `avrdude` key"
             );
+            if let Some(bootloader_port_ids) = &board.reset.as_ref().unwrap().bootloader_port_ids {
+                let Some(BoardUSBInfo::PortIds(port_ids)) = &board.usb_info else {
+                    panic!("Board {name:?} has `bootloader-port-ids` but no `port-ids`");
+                };
+                assert!(
+                    bootloader_port_ids.iter().all(|id| port_ids.contains(id)),
+                    "Board {name:?}'s `bootloader-port-ids` are missing from its `port-ids`"
+                );
+            }
+            let memory = board.memory.as_ref();
+            assert!(
+                memory.is_some_and(|memory| memory.flash.is_some() && memory.ram.is_some()),
+                "Board {name:?} doesn't have `flash` and `ram` under `memory`"
+            );
             let avrdude = board.avrdude.as_ref().unwrap();
             assert!(
                 avrdude.programmer.is_some(),
@@ -110,4 +297,101 @@ mod tests {
 
         Ok(())
     }
+
+    fn parse(boards: &str) -> HashMap<String, config::BoardConfig> {
+        toml::from_str(boards).unwrap()
+    }
+
+    fn builtin_catalog() -> Catalog {
+        let mut catalog = Catalog::default();
+        catalog
+            .add("the built-in boards", get_builtin_boards().unwrap())
+            .unwrap();
+        catalog
+    }
+
+    #[test]
+    fn inherits_across_catalogs() {
+        let mut catalog = builtin_catalog();
+        catalog
+            .add(
+                "`team.toml`",
+                parse(
+                    r#"
+                    [sensor-v2]
+                    inherit = "sensor"
+                    name = "Sensor Node v2"
+
+                    [sensor]
+                    inherit = "nano"
+                    name = "Sensor Node"
+                    avrdude.baudrate = 57600
+                    "#,
+                ),
+            )
+            .unwrap();
+
+        let board = &catalog.boards["sensor-v2"];
+        assert_eq!(board.name.as_deref(), Some("Sensor Node v2"));
+        let avrdude = board.avrdude.as_ref().unwrap();
+        assert_eq!(avrdude.partno.as_deref(), Some("atmega328p"));
+        assert_eq!(avrdude.baudrate, Some(std::num::NonZeroU32::new(57600)));
+    }
+
+    #[test]
+    fn extends_board_of_earlier_catalog() {
+        let mut catalog = builtin_catalog();
+        catalog
+            .add(
+                "`team.toml`",
+                parse(
+                    r#"
+                    [uno]
+                    inherit = "uno"
+                    name = "Team Uno"
+                    "#,
+                ),
+            )
+            .unwrap();
+
+        let uno = &catalog.boards["uno"];
+        assert_eq!(uno.name.as_deref(), Some("Team Uno"));
+        assert!(uno.memory.is_some());
+    }
+
+    #[test]
+    fn refuses_redefinition() {
+        let error = builtin_catalog()
+            .add("`team.toml`", parse("[uno]\nname = \"Team Uno\"\n"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "board `uno` in `team.toml` is already defined in the built-in boards; set `inherit = \"uno\"` to extend that definition"
+        );
+    }
+
+    #[test]
+    fn reports_inheritance_chains() {
+        let error = builtin_catalog()
+            .add(
+                "`team.toml`",
+                parse("[a]\ninherit = \"b\"\n[b]\ninherit = \"c\"\n"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "board `b` in `team.toml` inherits from unknown board `c` (a -> b -> c)"
+        );
+
+        let error = builtin_catalog()
+            .add(
+                "`team.toml`",
+                parse("[a]\ninherit = \"b\"\n[b]\ninherit = \"a\"\n"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "boards in `team.toml` inherit from each other in a cycle: a -> b -> a"
+        );
+    }
 }
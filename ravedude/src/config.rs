@@ -48,6 +48,16 @@ impl RavedudeConfig {
                 port: args.port.clone(),
                 reset_delay: args.reset_delay,
                 board: args.legacy_board_name().clone(),
+                board_files: None,
+                console_port_ids: None,
+                serial_number: None,
+                flasher: args.flasher,
+                symbolize: args.symbolize,
+                console_timestamps: args.timestamps,
+                console_log: args.log_file.clone(),
+                console_hex: args.hex,
+                console_send_newline: args.send_newline,
+                console_receive_newline: args.receive_newline,
             },
             board_config: Default::default(),
         })
@@ -63,6 +73,28 @@ pub struct RavedudeGeneralConfig {
     pub port: Option<std::path::PathBuf>,
     pub reset_delay: Option<u64>,
     pub board: Option<String>,
+    /// Additional board catalogs in the format of the built-in `boards.toml`, relative to
+    /// `Ravedude.toml`.
+    pub board_files: Option<Vec<std::path::PathBuf>>,
+    /// USB ids of a serial console provided by the firmware itself (USB CDC-ACM), which shows up
+    /// only once the program runs.
+    pub console_port_ids: Option<Vec<BoardPortID>>,
+    /// USB serial number of the board to use when several are connected.
+    pub serial_number: Option<String>,
+    pub flasher: Option<Flasher>,
+    /// Resolve code addresses printed on the console to functions and source lines.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub symbolize: bool,
+    /// Prefix each line on the console with the time since it was opened.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub console_timestamps: bool,
+    /// Append everything shown on the console to this file.
+    pub console_log: Option<std::path::PathBuf>,
+    /// Show the received bytes as a hex dump instead of text.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub console_hex: bool,
+    pub console_send_newline: Option<SendNewline>,
+    pub console_receive_newline: Option<ReceiveNewline>,
 }
 
 impl RavedudeGeneralConfig {
@@ -83,17 +115,114 @@ impl RavedudeGeneralConfig {
         if let Some(reset_delay) = args.reset_delay {
             self.reset_delay = Some(reset_delay);
         }
+        if let Some(flasher) = args.flasher {
+            self.flasher = Some(flasher);
+        }
+        if args.symbolize {
+            self.symbolize = true;
+        }
+        if args.timestamps {
+            self.console_timestamps = true;
+        }
+        if let Some(log_file) = args.log_file.clone() {
+            self.console_log = Some(log_file);
+        }
+        if args.hex {
+            self.console_hex = true;
+        }
+        if let Some(send_newline) = args.send_newline {
+            self.console_send_newline = Some(send_newline);
+        }
+        if let Some(receive_newline) = args.receive_newline {
+            self.console_receive_newline = Some(receive_newline);
+        }
         Ok(())
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+/// What programs the board.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Flasher {
+    /// avrdude if it is installed, otherwise the built-in flasher if it supports the board.
+    #[default]
+    Auto,
+    Avrdude,
+    /// The built-in flasher, see [`crate::flasher`].
+    Builtin,
+}
+
+impl std::str::FromStr for Flasher {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Flasher::Auto),
+            "avrdude" => Ok(Flasher::Avrdude),
+            "builtin" => Ok(Flasher::Builtin),
+            _ => anyhow::bail!("invalid flasher `{}`, expected auto, avrdude or builtin", s),
+        }
+    }
+}
+
+/// What the console sends to the board for a newline typed on the terminal.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SendNewline {
+    #[default]
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl std::str::FromStr for SendNewline {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(SendNewline::Lf),
+            "crlf" => Ok(SendNewline::Crlf),
+            "cr" => Ok(SendNewline::Cr),
+            _ => anyhow::bail!("invalid newline `{}`, expected lf, crlf or cr", s),
+        }
+    }
+}
+
+/// How the console translates newlines received from the board.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReceiveNewline {
+    /// Pass them through unchanged.
+    #[default]
+    Raw,
+    /// Turn CRLF into LF.
+    Lf,
+    /// Turn a bare LF into CRLF.
+    Crlf,
+}
+
+impl std::str::FromStr for ReceiveNewline {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(ReceiveNewline::Raw),
+            "lf" => Ok(ReceiveNewline::Lf),
+            "crlf" => Ok(ReceiveNewline::Crlf),
+            _ => anyhow::bail!("invalid newline `{}`, expected raw, lf or crlf", s),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct BoardConfig {
     pub name: Option<String>,
     pub inherit: Option<String>,
     pub reset: Option<ResetOptions>,
     pub avrdude: Option<BoardAvrdudeOptions>,
+    pub memory: Option<BoardMemory>,
+    pub fuses: Option<BoardFuses>,
     pub usb_info: Option<BoardUSBInfo>,
 }
 
@@ -108,17 +237,29 @@ impl BoardConfig {
                 Some(avrdude) => base.avrdude.map(|base_avrdude| avrdude.merge(base_avrdude)),
                 None => base.avrdude,
             },
+            memory: match self.memory {
+                Some(memory) => Some(match base.memory {
+                    Some(base_memory) => memory.merge(base_memory),
+                    None => memory,
+                }),
+                None => base.memory,
+            },
+            fuses: self.fuses.or(base.fuses),
             usb_info: self.usb_info.or(base.usb_info),
         }
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
 pub struct ResetOptions {
     pub automatic: bool,
+    /// USB ids of the bootloader of boards with native USB (Caterina).  The board is reset by
+    /// opening its port at 1200 baud and programmed through the port with these ids.
+    pub bootloader_port_ids: Option<Vec<BoardPortID>>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct BoardAvrdudeOptions {
     pub programmer: Option<String>,
@@ -143,7 +284,38 @@ impl BoardAvrdudeOptions {
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+/// Memory sizes of the board in bytes.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub struct BoardMemory {
+    pub flash: Option<u32>,
+    pub ram: Option<u32>,
+    pub eeprom: Option<u32>,
+    /// Flash taken by the bootloader, which is not available to the program.
+    pub bootloader: Option<u32>,
+}
+
+impl BoardMemory {
+    pub fn merge(self, base: Self) -> Self {
+        Self {
+            flash: self.flash.or(base.flash),
+            ram: self.ram.or(base.ram),
+            eeprom: self.eeprom.or(base.eeprom),
+            bootloader: self.bootloader.or(base.bootloader),
+        }
+    }
+}
+
+/// Fuses are only written when the project opts in, because wrong fuses can lock out the
+/// bootloader or the programmer.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub struct BoardFuses {
+    /// Write the fuses declared in the `.fuse` section of the program, after confirmation.
+    pub write: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub enum BoardUSBInfo {
     PortIds(Vec<BoardPortID>),
@@ -151,33 +323,301 @@ pub enum BoardUSBInfo {
     Error(String),
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BoardPortID {
     pub vid: u16,
     pub pid: u16,
 }
 
 impl BoardConfig {
-    pub fn guess_port(&self) -> Option<anyhow::Result<std::path::PathBuf>> {
+    /// Find the port of the board by its USB ids, or by its serial number if one is given.
+    /// `Ok(None)` if no matching board is connected.
+    pub fn guess_port(
+        &self,
+        serial_number: Option<&str>,
+    ) -> Option<anyhow::Result<Option<std::path::PathBuf>>> {
+        if let Some(serial_number) = serial_number {
+            // The serial number identifies the board on its own, even without known USB ids
+            let ports = with_serial_number(usb_ports(), serial_number);
+            return Some(select_port(ports));
+        }
         match &self.usb_info {
             Some(BoardUSBInfo::Error(err)) => Some(Err(anyhow::anyhow!(err.clone()))),
-            Some(BoardUSBInfo::PortIds(ports)) => {
-                for serialport::SerialPortInfo {
-                    port_name,
-                    port_type,
-                } in serialport::available_ports().unwrap()
-                {
-                    if let serialport::SerialPortType::UsbPort(usb_info) = port_type {
-                        for &BoardPortID { vid, pid } in ports {
-                            if usb_info.vid == vid && usb_info.pid == pid {
-                                return Some(Ok(port_name.into()));
-                            }
-                        }
-                    }
-                }
-                Some(Err(anyhow::anyhow!("Serial port not found.")))
+            Some(BoardUSBInfo::PortIds(ids)) => {
+                let ports = usb_ports()
+                    .into_iter()
+                    .filter(|port| ids.contains(&port.id))
+                    .collect();
+                Some(select_port(ports))
             }
             None => None,
         }
     }
+
+    /// USB ids of the running program, i.e. the ids of the board without those of its bootloader.
+    pub fn application_port_ids(&self) -> Vec<BoardPortID> {
+        let bootloader_port_ids = self
+            .reset
+            .as_ref()
+            .and_then(|reset| reset.bootloader_port_ids.as_deref())
+            .unwrap_or_default();
+        match &self.usb_info {
+            Some(BoardUSBInfo::PortIds(port_ids)) => port_ids
+                .iter()
+                .filter(|id| !bootloader_port_ids.contains(id))
+                .copied()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Find a connected serial port with one of the given USB ids.
+pub fn find_port(ports: &[BoardPortID]) -> Option<std::path::PathBuf> {
+    for serialport::SerialPortInfo {
+        port_name,
+        port_type,
+    } in serialport::available_ports().unwrap_or_default()
+    {
+        if let serialport::SerialPortType::UsbPort(usb_info) = port_type {
+            for &BoardPortID { vid, pid } in ports {
+                if usb_info.vid == vid && usb_info.pid == pid {
+                    return Some(port_name.into());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Find the port a board came back under after it disappeared: a port with one of the given USB
+/// ids and, if one is given, the serial number of the board.
+pub fn find_renamed_port(
+    port_ids: &[BoardPortID],
+    serial_number: Option<&str>,
+) -> Option<std::path::PathBuf> {
+    renamed_port(usb_ports(), port_ids, serial_number)
+}
+
+fn renamed_port(
+    ports: Vec<UsbPort>,
+    port_ids: &[BoardPortID],
+    serial_number: Option<&str>,
+) -> Option<std::path::PathBuf> {
+    let ports = ports
+        .into_iter()
+        .filter(|port| port_ids.contains(&port.id))
+        .collect();
+    let ports = match serial_number {
+        Some(serial_number) => with_serial_number(ports, serial_number),
+        None => ports,
+    };
+    ports.into_iter().next().map(|port| port.path)
+}
+
+/// A connected USB serial port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbPort {
+    pub path: std::path::PathBuf,
+    pub id: BoardPortID,
+    pub serial_number: Option<String>,
+}
+
+fn usb_ports() -> Vec<UsbPort> {
+    serialport::available_ports()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|port| match port.port_type {
+            serialport::SerialPortType::UsbPort(info) => Some(UsbPort {
+                path: port.port_name.into(),
+                id: BoardPortID {
+                    vid: info.vid,
+                    pid: info.pid,
+                },
+                serial_number: info.serial_number,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn with_serial_number(ports: Vec<UsbPort>, serial_number: &str) -> Vec<UsbPort> {
+    ports
+        .into_iter()
+        .filter(|port| {
+            port.serial_number
+                .as_deref()
+                .is_some_and(|s| s.eq_ignore_ascii_case(serial_number))
+        })
+        .collect()
+}
+
+/// Pick one of the matching ports, asking the user if there are several.
+fn select_port(mut ports: Vec<UsbPort>) -> anyhow::Result<Option<std::path::PathBuf>> {
+    if ports.len() <= 1 {
+        return Ok(ports.pop().map(|port| port.path));
+    }
+    ports.sort_by(|a, b| a.path.cmp(&b.path));
+
+    if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        return Err(ambiguous_ports(&ports));
+    }
+    crate::warning!("several matching boards are connected:");
+    for (i, port) in ports.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, describe_port(port));
+    }
+    loop {
+        eprint!("Which one should be used? [1-{}] ", ports.len());
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Err(ambiguous_ports(&ports));
+        }
+        match answer.trim().parse::<usize>() {
+            Ok(n) if (1..=ports.len()).contains(&n) => {
+                return Ok(Some(ports.swap_remove(n - 1).path))
+            }
+            _ => continue,
+        }
+    }
+}
+
+fn describe_port(port: &UsbPort) -> String {
+    format!(
+        "{} ({:04X}:{:04X}, serial number {})",
+        port.path.display(),
+        port.id.vid,
+        port.id.pid,
+        port.serial_number.as_deref().unwrap_or("unknown")
+    )
+}
+
+fn ambiguous_ports(ports: &[UsbPort]) -> anyhow::Error {
+    let mut msg = String::from("several matching boards are connected:");
+    for port in ports {
+        msg.push_str("\n  ");
+        msg.push_str(&describe_port(port));
+    }
+    msg.push_str("\nselect one with -P, or with `serial-number` under [general] in Ravedude.toml");
+    anyhow::anyhow!(msg)
+}
+
+/// Wait up to `timeout` for a serial port with one of the given USB ids to show up.
+pub fn wait_for_port(
+    ports: &[BoardPortID],
+    timeout: std::time::Duration,
+) -> Option<std::path::PathBuf> {
+    let start = std::time::Instant::now();
+    loop {
+        if let Some(port) = find_port(ports) {
+            return Some(port);
+        }
+        if start.elapsed() > timeout {
+            return None;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port(path: &str, serial_number: Option<&str>) -> UsbPort {
+        UsbPort {
+            path: path.into(),
+            id: BoardPortID {
+                vid: 0x2341,
+                pid: 0x0043,
+            },
+            serial_number: serial_number.map(String::from),
+        }
+    }
+
+    #[test]
+    fn selects_single_port() {
+        assert_eq!(select_port(vec![]).unwrap(), None);
+        assert_eq!(
+            select_port(vec![port("/dev/ttyACM0", None)]).unwrap(),
+            Some("/dev/ttyACM0".into())
+        );
+    }
+
+    #[test]
+    fn filters_by_serial_number() {
+        let ports = vec![
+            port("/dev/ttyACM0", Some("95735353130351F0C1C1")),
+            port("/dev/ttyACM1", Some("85036313530351A02191")),
+            port("/dev/ttyUSB0", None),
+        ];
+        assert_eq!(
+            with_serial_number(ports, "85036313530351a02191"),
+            [port("/dev/ttyACM1", Some("85036313530351A02191"))]
+        );
+    }
+
+    #[test]
+    fn finds_renamed_port_by_serial_number() {
+        let ids = [BoardPortID {
+            vid: 0x2341,
+            pid: 0x0043,
+        }];
+        let other = UsbPort {
+            id: BoardPortID {
+                vid: 0x2341,
+                pid: 0x0001,
+            },
+            ..port("/dev/ttyACM0", Some("85036313530351A02191"))
+        };
+        let ports = vec![
+            other,
+            port("/dev/ttyACM1", Some("95735353130351F0C1C1")),
+            port("/dev/ttyACM2", Some("85036313530351A02191")),
+        ];
+        assert_eq!(
+            renamed_port(ports.clone(), &ids, Some("85036313530351A02191")),
+            Some("/dev/ttyACM2".into())
+        );
+        assert_eq!(
+            renamed_port(ports.clone(), &ids, None),
+            Some("/dev/ttyACM1".into())
+        );
+        assert_eq!(renamed_port(ports, &ids, Some("0000")), None);
+    }
+
+    #[test]
+    fn parses_flasher() {
+        assert_eq!("builtin".parse::<Flasher>().unwrap(), Flasher::Builtin);
+        assert_eq!(
+            "foo".parse::<Flasher>().unwrap_err().to_string(),
+            "invalid flasher `foo`, expected auto, avrdude or builtin"
+        );
+    }
+
+    #[test]
+    fn parses_newlines() {
+        assert_eq!("crlf".parse::<SendNewline>().unwrap(), SendNewline::Crlf);
+        assert_eq!(
+            "foo".parse::<SendNewline>().unwrap_err().to_string(),
+            "invalid newline `foo`, expected lf, crlf or cr"
+        );
+        assert_eq!(
+            "cr".parse::<ReceiveNewline>().unwrap_err().to_string(),
+            "invalid newline `cr`, expected raw, lf or crlf"
+        );
+    }
+
+    #[test]
+    fn lists_ambiguous_ports() {
+        let error = ambiguous_ports(&[
+            port("/dev/ttyACM0", Some("95735353130351F0C1C1")),
+            port("/dev/ttyACM1", None),
+        ]);
+        assert_eq!(
+            error.to_string(),
+            "several matching boards are connected:\n  \
+             /dev/ttyACM0 (2341:0043, serial number 95735353130351F0C1C1)\n  \
+             /dev/ttyACM1 (2341:0043, serial number unknown)\n\
+             select one with -P, or with `serial-number` under [general] in Ravedude.toml"
+        );
+    }
 }
@@ -1,13 +1,59 @@
 use anyhow::Context as _;
 use std::io::Read as _;
 use std::io::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-pub fn open(port: &std::path::Path, baudrate: u32) -> anyhow::Result<()> {
-    let mut rx = serialport::new(port.to_string_lossy(), baudrate)
-        .timeout(std::time::Duration::from_secs(2))
-        .open_native()
+use crate::config::{ReceiveNewline, SendNewline};
+
+/// How often to look for the port after it disappeared.
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait for the console of a firmware with native USB to show up.
+const USB_CONSOLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Wait for a serial port with one of the given USB ids to appear, e.g. the USB CDC-ACM console
+/// of a firmware which only enumerates once the program runs.
+pub fn wait_for_port(ports: &[crate::config::BoardPortID]) -> anyhow::Result<std::path::PathBuf> {
+    crate::config::wait_for_port(ports, USB_CONSOLE_TIMEOUT).with_context(|| {
+        format!(
+            "no serial port matching `console-port-ids` showed up within {} seconds",
+            USB_CONSOLE_TIMEOUT.as_secs()
+        )
+    })
+}
+
+/// How the console shows what it receives and translates what it sends.
+#[derive(Default)]
+pub struct Options {
+    pub timestamps: bool,
+    pub log: Option<std::path::PathBuf>,
+    pub hex: bool,
+    pub send_newline: SendNewline,
+    pub receive_newline: ReceiveNewline,
+    /// Not used in hex mode.
+    pub symbolizer: Option<crate::symbolize::Symbolizer>,
+    /// USB ids under which the port may come back after it disappeared, besides its old name.
+    pub reconnect_port_ids: Vec<crate::config::BoardPortID>,
+    /// Serial number the port must have to be taken for the old one under a new name.
+    pub reconnect_serial_number: Option<String>,
+}
+
+pub fn open(port: &std::path::Path, baudrate: u32, options: Options) -> anyhow::Result<()> {
+    let mut rx = open_port(port, baudrate)
         .with_context(|| format!("failed to open serial port `{}`", port.display()))?;
-    let mut tx = rx.try_clone_native()?;
+    let tx = Arc::new(Mutex::new(Some(rx.try_clone()?)));
+
+    let mut log = match &options.log {
+        Some(path) => Some(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("failed to open log file `{}`", path.display()))?,
+        ),
+        None => None,
+    };
 
     let mut stdin = std::io::stdin();
     let mut stdout = std::io::stdout();
@@ -20,6 +66,15 @@ pub fn open(port: &std::path::Path, baudrate: u32) -> anyhow::Result<()> {
     })
     .context("failed setting a CTRL+C handler")?;
 
+    let hex = options.hex;
+    let mut symbolizer = options.symbolizer.filter(|_| !hex);
+    let mut formatter = Formatter::new(options.timestamps, hex, options.receive_newline);
+    let start = std::time::Instant::now();
+    let mut port = port.to_owned();
+    let reconnect_port_ids = options.reconnect_port_ids;
+    let reconnect_serial_number = options.reconnect_serial_number;
+    let rx_tx = tx.clone();
+
     // Spawn a thread for the receiving end because stdio is not portably non-blocking...
     std::thread::spawn(move || loop {
         #[cfg(not(target_os = "windows"))]
@@ -29,10 +84,11 @@ pub fn open(port: &std::path::Path, baudrate: u32) -> anyhow::Result<()> {
         #[cfg(target_os = "windows")]
         let mut buf = [0u8; 1];
 
-        match rx.read(&mut buf) {
-            Ok(count) => {
+        let result = rx.read(&mut buf);
+        let output = match result {
+            Ok(count) if count > 0 => {
                 #[cfg(target_os = "windows")]
-                {
+                if !hex {
                     // On windows, we must ensure that we are not sending anything outside of the
                     // ASCII range.
                     for byte in &mut buf[..count] {
@@ -41,19 +97,299 @@ pub fn open(port: &std::path::Path, baudrate: u32) -> anyhow::Result<()> {
                         }
                     }
                 }
-                stdout.write(&buf[..count]).unwrap();
-                stdout.flush().unwrap();
+                let data = match symbolizer.as_mut() {
+                    Some(symbolizer) => symbolizer.feed(&buf[..count]),
+                    None => buf[..count].to_vec(),
+                };
+                formatter.feed(&data, start.elapsed())
             }
-            Err(e) => {
-                assert!(e.kind() == std::io::ErrorKind::TimedOut);
+            _ => {
+                // Show what was held back, nothing more is coming for a while
+                let data = symbolizer
+                    .as_mut()
+                    .map(|symbolizer| symbolizer.flush())
+                    .unwrap_or_default();
+                let mut output = formatter.feed(&data, start.elapsed());
+                output.extend(formatter.flush(start.elapsed()));
+                output
             }
+        };
+        if !output.is_empty() {
+            stdout.write_all(&output).unwrap();
+            stdout.flush().unwrap();
+            if let Some(file) = log.as_mut() {
+                if let Err(e) = file.write_all(&output) {
+                    crate::warning!("failed writing the log file, stopped logging: {e}");
+                    log = None;
+                }
+            }
+        }
+
+        if matches!(&result, Err(e) if e.kind() == std::io::ErrorKind::TimedOut) {
+            continue;
         }
+        if matches!(result, Ok(count) if count > 0) {
+            continue;
+        }
+
+        // The port is gone, e.g. because the board was reset or unplugged
+        *rx_tx.lock().unwrap() = None;
+        eprintln!();
+        crate::task_message!(
+            "Console",
+            "{} disconnected, waiting for it to come back",
+            port.display()
+        );
+        (port, rx) = reconnect(
+            &port,
+            &reconnect_port_ids,
+            reconnect_serial_number.as_deref(),
+            baudrate,
+        );
+        *rx_tx.lock().unwrap() = rx.try_clone().ok();
+        crate::task_message!("Console", "reconnected to {}", port.display());
     });
 
     loop {
         let mut buf = [0u8; 4098];
         let count = stdin.read(&mut buf)?;
-        tx.write(&buf[..count])?;
-        tx.flush()?;
+        let data = translate_newlines(&buf[..count], options.send_newline);
+        // Input is dropped while the board is disconnected
+        if let Some(tx) = tx.lock().unwrap().as_mut() {
+            tx.write_all(&data).and_then(|()| tx.flush()).ok();
+        }
+    }
+}
+
+fn open_port(
+    port: &std::path::Path,
+    baudrate: u32,
+) -> serialport::Result<Box<dyn serialport::SerialPort>> {
+    serialport::new(port.to_string_lossy(), baudrate)
+        .timeout(Duration::from_secs(2))
+        .open()
+}
+
+/// Wait until the port comes back, under the same name or as a port with one of the USB ids (and
+/// the serial number, if one is given).
+fn reconnect(
+    port: &std::path::Path,
+    port_ids: &[crate::config::BoardPortID],
+    serial_number: Option<&str>,
+    baudrate: u32,
+) -> (std::path::PathBuf, Box<dyn serialport::SerialPort>) {
+    loop {
+        // Give the device time to go away completely or to finish enumerating
+        std::thread::sleep(RECONNECT_INTERVAL);
+
+        if let Ok(serial) = open_port(port, baudrate) {
+            return (port.to_owned(), serial);
+        }
+        if let Some(renamed) = crate::config::find_renamed_port(port_ids, serial_number) {
+            if let Ok(serial) = open_port(&renamed, baudrate) {
+                return (renamed, serial);
+            }
+        }
+    }
+}
+
+/// Translate the newlines typed on the terminal into what the board expects.
+fn translate_newlines(data: &[u8], newline: SendNewline) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len());
+    for (i, &byte) in data.iter().enumerate() {
+        // Terminals on Windows send CRLF already
+        if byte == b'\r' && data.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        match (byte, newline) {
+            (b'\n', SendNewline::Crlf) => output.extend_from_slice(b"\r\n"),
+            (b'\n', SendNewline::Cr) => output.push(b'\r'),
+            _ => output.push(byte),
+        }
+    }
+    output
+}
+
+/// Turns the received bytes into what is shown on the console.
+struct Formatter {
+    timestamps: bool,
+    hex: bool,
+    receive_newline: ReceiveNewline,
+    /// Whether the next byte starts a new line, which gets a timestamp.
+    line_start: bool,
+    /// A received CR which may be the start of a CRLF.
+    pending_cr: bool,
+    previous: u8,
+    /// Bytes of the hex dump line which is not complete yet.
+    hex_line: Vec<u8>,
+    /// Number of bytes received before `hex_line`.
+    hex_offset: usize,
+}
+
+impl Formatter {
+    const HEX_LINE_LEN: usize = 16;
+
+    fn new(timestamps: bool, hex: bool, receive_newline: ReceiveNewline) -> Self {
+        Formatter {
+            timestamps,
+            hex,
+            receive_newline,
+            line_start: true,
+            pending_cr: false,
+            previous: 0,
+            hex_line: Vec::new(),
+            hex_offset: 0,
+        }
+    }
+
+    fn feed(&mut self, data: &[u8], now: Duration) -> Vec<u8> {
+        let mut output = Vec::new();
+        for &byte in data {
+            if self.hex {
+                self.hex_line.push(byte);
+                if self.hex_line.len() == Self::HEX_LINE_LEN {
+                    self.hex_dump_line(&mut output, now);
+                }
+                continue;
+            }
+
+            match self.receive_newline {
+                ReceiveNewline::Raw => self.text(&mut output, byte, now),
+                ReceiveNewline::Lf => {
+                    if std::mem::take(&mut self.pending_cr) && byte != b'\n' {
+                        self.text(&mut output, b'\r', now);
+                    }
+                    if byte == b'\r' {
+                        self.pending_cr = true;
+                    } else {
+                        self.text(&mut output, byte, now);
+                    }
+                }
+                ReceiveNewline::Crlf => {
+                    if byte == b'\n' && self.previous != b'\r' {
+                        self.text(&mut output, b'\r', now);
+                    }
+                    self.text(&mut output, byte, now);
+                }
+            }
+            self.previous = byte;
+        }
+        output
+    }
+
+    /// Output what is held back, like a CR or an incomplete hex dump line.
+    fn flush(&mut self, now: Duration) -> Vec<u8> {
+        let mut output = Vec::new();
+        if std::mem::take(&mut self.pending_cr) {
+            self.text(&mut output, b'\r', now);
+        }
+        if !self.hex_line.is_empty() {
+            self.hex_dump_line(&mut output, now);
+        }
+        output
+    }
+
+    fn text(&mut self, output: &mut Vec<u8>, byte: u8, now: Duration) {
+        if self.line_start && self.timestamps {
+            timestamp(output, now);
+        }
+        output.push(byte);
+        self.line_start = byte == b'\n';
+    }
+
+    /// Write `hex_line` in the format of `hexdump -C`.
+    fn hex_dump_line(&mut self, output: &mut Vec<u8>, now: Duration) {
+        if self.timestamps {
+            timestamp(output, now);
+        }
+        let mut line = format!("{:08x} ", self.hex_offset);
+        for i in 0..Self::HEX_LINE_LEN {
+            if i % 8 == 0 {
+                line.push(' ');
+            }
+            match self.hex_line.get(i) {
+                Some(byte) => line.push_str(&format!("{byte:02x} ")),
+                None => line.push_str("   "),
+            }
+        }
+        line.push_str(" |");
+        for &byte in &self.hex_line {
+            line.push(if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            });
+        }
+        line.push_str("|\n");
+        output.extend_from_slice(line.as_bytes());
+
+        self.hex_offset += self.hex_line.len();
+        self.hex_line.clear();
+    }
+}
+
+fn timestamp(output: &mut Vec<u8>, now: Duration) {
+    let stamp = format!("[{:>5}.{:03}] ", now.as_secs(), now.subsec_millis());
+    output.extend_from_slice(stamp.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T: Duration = Duration::from_millis(1234);
+
+    fn text(formatter: &mut Formatter, data: &[u8]) -> String {
+        String::from_utf8(formatter.feed(data, T)).unwrap()
+    }
+
+    #[test]
+    fn translates_sent_newlines() {
+        assert_eq!(translate_newlines(b"a\nb", SendNewline::Lf), b"a\nb");
+        assert_eq!(translate_newlines(b"a\nb", SendNewline::Crlf), b"a\r\nb");
+        assert_eq!(translate_newlines(b"a\r\nb", SendNewline::Crlf), b"a\r\nb");
+        assert_eq!(translate_newlines(b"a\nb", SendNewline::Cr), b"a\rb");
+    }
+
+    #[test]
+    fn timestamps_lines() {
+        let mut formatter = Formatter::new(true, false, ReceiveNewline::Raw);
+        assert_eq!(
+            text(&mut formatter, b"ab\nc"),
+            "[    1.234] ab\n[    1.234] c"
+        );
+        assert_eq!(text(&mut formatter, b"d\n"), "d\n");
+    }
+
+    #[test]
+    fn turns_crlf_into_lf() {
+        let mut formatter = Formatter::new(false, false, ReceiveNewline::Lf);
+        assert_eq!(text(&mut formatter, b"a\r\nb\r"), "a\nb");
+        assert_eq!(text(&mut formatter, b"\nc\rd"), "\nc\rd");
+        assert_eq!(formatter.flush(T), b"");
+
+        formatter.feed(b"e\r", T);
+        assert_eq!(formatter.flush(T), b"\r");
+    }
+
+    #[test]
+    fn turns_lf_into_crlf() {
+        let mut formatter = Formatter::new(false, false, ReceiveNewline::Crlf);
+        assert_eq!(text(&mut formatter, b"a\nb\r"), "a\r\nb\r");
+        assert_eq!(text(&mut formatter, b"\nc"), "\nc");
+    }
+
+    #[test]
+    fn dumps_hex() {
+        let mut formatter = Formatter::new(false, true, ReceiveNewline::Raw);
+        assert_eq!(text(&mut formatter, b"Hello, world!\r\n"), "");
+        assert_eq!(
+            text(&mut formatter, b"\x00\xff"),
+            "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0d 0a 00  |Hello, world!...|\n"
+        );
+        assert_eq!(
+            String::from_utf8(formatter.flush(T)).unwrap(),
+            "00000010  ff                                                |.|\n"
+        );
     }
 }
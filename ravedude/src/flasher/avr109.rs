@@ -0,0 +1,317 @@
+//! AVR109 ("butterfly") protocol, as spoken by Caterina on the boards with native USB.
+use anyhow::Context as _;
+use std::io::{Read as _, Write as _};
+use std::time::{Duration, Instant};
+
+const CMD_SOFTWARE_ID: u8 = b'S';
+const CMD_BLOCK_SUPPORT: u8 = b'b';
+const CMD_ENTER_PROGMODE: u8 = b'P';
+const CMD_LEAVE_PROGMODE: u8 = b'L';
+const CMD_EXIT: u8 = b'E';
+const CMD_CHIP_ERASE: u8 = b'e';
+const CMD_READ_SIGNATURE: u8 = b's';
+const CMD_SET_ADDRESS: u8 = b'A';
+const CMD_WRITE_BLOCK: u8 = b'B';
+const CMD_READ_BLOCK: u8 = b'g';
+
+const MEMTYPE_FLASH: u8 = b'F';
+const MEMTYPE_EEPROM: u8 = b'E';
+/// Acknowledge of the commands without a response.
+const ACK: u8 = b'\r';
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+/// Erasing the application section of an ATmega32U4 takes almost a second.
+const ERASE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to retry opening the port, which is not accessible right after it shows up.
+const OPEN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Highest byte address reachable with the 16-bit word addresses of the set address command.
+const MAX_ADDRESS: u32 = 0x1FFFF;
+
+pub struct Avr109 {
+    port: Box<dyn serialport::SerialPort>,
+    block_size: usize,
+}
+
+impl Avr109 {
+    /// Open the port of a bootloader which is already running and enter programming mode.
+    pub fn connect(port: &std::path::Path, baudrate: u32) -> anyhow::Result<Self> {
+        let start = Instant::now();
+        let port = loop {
+            match serialport::new(port.to_string_lossy(), baudrate)
+                .timeout(TIMEOUT)
+                .open()
+            {
+                Ok(port) => break port,
+                Err(_) if start.elapsed() < OPEN_TIMEOUT => {
+                    std::thread::sleep(Duration::from_millis(100))
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("failed to open serial port `{}`", port.display())
+                    })
+                }
+            }
+        };
+
+        let mut bootloader = Avr109 {
+            port,
+            block_size: 0,
+        };
+        bootloader.port.clear(serialport::ClearBuffer::Input).ok();
+
+        bootloader
+            .command(&[CMD_SOFTWARE_ID], 7)
+            .context("bootloader did not respond, is the board in its bootloader?")?;
+        let block_support = bootloader.command(&[CMD_BLOCK_SUPPORT], 3)?;
+        if block_support[0] != b'Y' {
+            anyhow::bail!("bootloader does not support block mode");
+        }
+        bootloader.block_size = (block_support[1] as usize) << 8 | block_support[2] as usize;
+        bootloader.acked(&[CMD_ENTER_PROGMODE])?;
+        Ok(bootloader)
+    }
+
+    fn command(&mut self, command: &[u8], response_len: usize) -> anyhow::Result<Vec<u8>> {
+        self.port.write_all(command)?;
+        let mut response = vec![0; response_len];
+        self.port
+            .read_exact(&mut response)
+            .with_context(|| format!("no response to command `{}`", command[0] as char))?;
+        Ok(response)
+    }
+
+    /// Send a command which is answered with a carriage return.
+    fn acked(&mut self, command: &[u8]) -> anyhow::Result<()> {
+        let response = self.command(command, 1)?;
+        if response[0] != ACK {
+            anyhow::bail!(
+                "protocol error in command `{}`: got {:#04x}",
+                command[0] as char,
+                response[0]
+            );
+        }
+        Ok(())
+    }
+
+    fn set_address(&mut self, address: u32) -> anyhow::Result<()> {
+        if address > MAX_ADDRESS {
+            anyhow::bail!("address {:#x} is out of range for AVR109", address);
+        }
+        let word = (address / 2) as u16;
+        self.acked(&[CMD_SET_ADDRESS, (word >> 8) as u8, word as u8])
+    }
+
+    fn check_block(&self, len: usize) -> anyhow::Result<()> {
+        if len > self.block_size {
+            anyhow::bail!(
+                "page of {len} bytes does not fit the block size {} of the bootloader",
+                self.block_size
+            );
+        }
+        Ok(())
+    }
+}
+
+impl super::Bootloader for Avr109 {
+    fn read_signature(&mut self) -> anyhow::Result<[u8; 3]> {
+        // The signature comes last byte first
+        let signature = self.command(&[CMD_READ_SIGNATURE], 3)?;
+        Ok([signature[2], signature[1], signature[0]])
+    }
+
+    fn erase(&mut self) -> anyhow::Result<()> {
+        self.port.set_timeout(ERASE_TIMEOUT)?;
+        let result = self.acked(&[CMD_CHIP_ERASE]);
+        self.port.set_timeout(TIMEOUT)?;
+        result
+    }
+
+    fn write_flash_page(&mut self, address: u32, data: &[u8]) -> anyhow::Result<()> {
+        // Flash is written in words, pad the last odd byte as erased
+        let len = data.len().next_multiple_of(2);
+        self.check_block(len)?;
+        self.set_address(address)?;
+
+        let mut command = vec![CMD_WRITE_BLOCK, (len >> 8) as u8, len as u8, MEMTYPE_FLASH];
+        command.extend_from_slice(data);
+        command.resize(4 + len, crate::image::ERASED);
+        self.acked(&command)
+    }
+
+    fn read_flash_page(&mut self, address: u32, len: usize) -> anyhow::Result<Vec<u8>> {
+        self.check_block(len)?;
+        self.set_address(address)?;
+        self.command(
+            &[CMD_READ_BLOCK, (len >> 8) as u8, len as u8, MEMTYPE_FLASH],
+            len,
+        )
+    }
+
+    fn supports_eeprom(&self) -> bool {
+        true
+    }
+
+    fn write_eeprom(&mut self, address: u32, data: &[u8]) -> anyhow::Result<()> {
+        self.check_block(data.len())?;
+        // The address command takes EEPROM addresses in bytes, not in words
+        self.set_address(address * 2)?;
+
+        let len = data.len();
+        let mut command = vec![CMD_WRITE_BLOCK, (len >> 8) as u8, len as u8, MEMTYPE_EEPROM];
+        command.extend_from_slice(data);
+        self.acked(&command)
+    }
+
+    fn read_eeprom(&mut self, address: u32, len: usize) -> anyhow::Result<Vec<u8>> {
+        self.check_block(len)?;
+        self.set_address(address * 2)?;
+        self.command(
+            &[CMD_READ_BLOCK, (len >> 8) as u8, len as u8, MEMTYPE_EEPROM],
+            len,
+        )
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.acked(&[CMD_LEAVE_PROGMODE])?;
+        self.acked(&[CMD_EXIT])
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::flasher::tests::{read, Pty};
+    use crate::flasher::{part, program};
+    use crate::image::Image;
+    use std::fs::File;
+
+    /// Caterina running on the other end of a pty.
+    struct MockCaterina {
+        flash: Vec<u8>,
+        eeprom: Vec<u8>,
+        erased: bool,
+    }
+
+    impl MockCaterina {
+        fn new() -> Self {
+            MockCaterina {
+                // Left over from the previous program
+                flash: vec![0x00; 512],
+                eeprom: vec![0xFF; 1024],
+                erased: false,
+            }
+        }
+
+        fn run(mut self, master: &mut File) -> Self {
+            let mut address = 0;
+            loop {
+                let Ok(command) = read(master, 1) else {
+                    return self;
+                };
+                let command = command[0];
+                let mut args = |n: usize| read(master, n).unwrap();
+                let reply = match command {
+                    CMD_SOFTWARE_ID => b"CATERIN".to_vec(),
+                    CMD_BLOCK_SUPPORT => vec![b'Y', 0x00, 0x80],
+                    CMD_READ_SIGNATURE => vec![0x87, 0x95, 0x1E],
+                    CMD_ENTER_PROGMODE | CMD_LEAVE_PROGMODE | CMD_EXIT => vec![ACK],
+                    CMD_CHIP_ERASE => {
+                        self.flash.fill(0xFF);
+                        self.erased = true;
+                        vec![ACK]
+                    }
+                    CMD_SET_ADDRESS => {
+                        let a = args(2);
+                        address = ((a[0] as usize) << 8 | a[1] as usize) * 2;
+                        vec![ACK]
+                    }
+                    CMD_WRITE_BLOCK => {
+                        let header = args(3);
+                        let len = (header[0] as usize) << 8 | header[1] as usize;
+                        let data = args(len);
+                        if header[2] == MEMTYPE_EEPROM {
+                            // Caterina counts EEPROM addresses in words as well
+                            let address = address / 2;
+                            self.eeprom[address..address + len].copy_from_slice(&data);
+                            master.write_all(&[ACK]).unwrap();
+                            continue;
+                        }
+                        assert_eq!(len % 2, 0, "odd block length");
+                        if self.flash.len() < address + len {
+                            self.flash.resize(address + len, 0xFF);
+                        }
+                        self.flash[address..address + len].copy_from_slice(&data);
+                        vec![ACK]
+                    }
+                    CMD_READ_BLOCK => {
+                        let header = args(3);
+                        let len = (header[0] as usize) << 8 | header[1] as usize;
+                        match header[2] {
+                            MEMTYPE_EEPROM => self.eeprom[address / 2..address / 2 + len].to_vec(),
+                            _ => self.flash[address..address + len].to_vec(),
+                        }
+                    }
+                    other => panic!("unexpected command {other:#04x}"),
+                };
+                master.write_all(&reply).unwrap();
+                if command == CMD_EXIT {
+                    return self;
+                }
+            }
+        }
+    }
+
+    fn flash_with(image: &Image, erase: bool) -> (anyhow::Result<()>, MockCaterina) {
+        let pty = Pty::new();
+        let mut master = pty.master.try_clone().unwrap();
+        let board = std::thread::spawn(move || MockCaterina::new().run(&mut master));
+
+        let result = Avr109::connect(&pty.path, 57600).and_then(|mut bootloader| {
+            program(&mut bootloader, &part("atmega32u4").unwrap(), image, erase)
+        });
+        drop(pty);
+        (result, board.join().unwrap())
+    }
+
+    #[test]
+    fn writes_and_verifies_flash() {
+        let image = Image {
+            flash: (0..300).map(|i| (i * 3) as u8).collect(),
+            ..Default::default()
+        };
+        let (result, board) = flash_with(&image, false);
+
+        result.unwrap();
+        assert!(!board.erased);
+        assert_eq!(board.flash[..300], image.flash);
+    }
+
+    #[test]
+    fn pads_odd_pages_and_erases() {
+        let image = Image {
+            flash: vec![0x11, 0x22, 0x33],
+            ..Default::default()
+        };
+        let (result, board) = flash_with(&image, true);
+
+        result.unwrap();
+        assert!(board.erased);
+        assert_eq!(board.flash[..4], [0x11, 0x22, 0x33, 0xFF]);
+        assert!(board.flash[4..].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn writes_eeprom() {
+        let image = Image {
+            flash: vec![0x0C, 0x94],
+            eeprom: (0..100).collect(),
+            ..Default::default()
+        };
+        let (result, board) = flash_with(&image, false);
+
+        result.unwrap();
+        assert_eq!(board.eeprom[..100], image.eeprom);
+        assert!(board.eeprom[100..].iter().all(|&b| b == 0xFF));
+    }
+}
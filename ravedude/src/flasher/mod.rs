@@ -0,0 +1,292 @@
+//! Built-in flasher which speaks the bootloader protocols directly, for machines without avrdude.
+use anyhow::Context as _;
+use std::io::Write as _;
+
+use crate::config::BoardAvrdudeOptions;
+use crate::image::Image;
+
+mod avr109;
+mod stk500v1;
+
+/// Baudrate of the `arduino` programmer when the board does not set one, same as avrdude.
+const STK500V1_DEFAULT_BAUDRATE: u32 = 115200;
+/// Baudrate of the `avr109` programmer when the board does not set one, same as avrdude.  USB
+/// bootloaders like Caterina ignore it.
+const AVR109_DEFAULT_BAUDRATE: u32 = 19200;
+
+/// EEPROM is written in blocks of this size, which every bootloader accepts.
+const EEPROM_BLOCK_SIZE: usize = 64;
+
+/// What the flasher needs to know about an MCU, looked up by its avrdude part number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Part {
+    pub signature: [u8; 3],
+    /// Size of a flash page in bytes.
+    pub page_size: usize,
+}
+
+pub fn part(partno: &str) -> Option<Part> {
+    let (signature, page_size) = match partno {
+        "atmega168" => ([0x1E, 0x94, 0x06], 128),
+        "atmega328p" => ([0x1E, 0x95, 0x0F], 128),
+        "atmega328pb" => ([0x1E, 0x95, 0x16], 128),
+        "atmega32u4" => ([0x1E, 0x95, 0x87], 128),
+        "atmega1280" => ([0x1E, 0x97, 0x03], 256),
+        "atmega2560" => ([0x1E, 0x98, 0x01], 256),
+        _ => return None,
+    };
+    Some(Part {
+        signature,
+        page_size,
+    })
+}
+
+/// Whether the built-in flasher can program boards using this avrdude programmer.
+pub fn supports(programmer: &str) -> bool {
+    matches!(programmer, "arduino" | "avr109")
+}
+
+/// A bootloader in programming mode.
+trait Bootloader {
+    fn read_signature(&mut self) -> anyhow::Result<[u8; 3]>;
+    /// Erase the whole flash, for bootloaders which can.
+    fn erase(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+    fn write_flash_page(&mut self, address: u32, data: &[u8]) -> anyhow::Result<()>;
+    fn read_flash_page(&mut self, address: u32, len: usize) -> anyhow::Result<Vec<u8>>;
+    /// Whether [`Bootloader::write_eeprom`] and [`Bootloader::read_eeprom`] are implemented.
+    fn supports_eeprom(&self) -> bool {
+        false
+    }
+    fn write_eeprom(&mut self, _address: u32, _data: &[u8]) -> anyhow::Result<()> {
+        anyhow::bail!("the bootloader cannot write the EEPROM, use avrdude with an ISP programmer")
+    }
+    fn read_eeprom(&mut self, _address: u32, _len: usize) -> anyhow::Result<Vec<u8>> {
+        anyhow::bail!("the bootloader cannot read the EEPROM")
+    }
+    /// Leave the bootloader and start the program.
+    fn finish(&mut self) -> anyhow::Result<()>;
+}
+
+/// Program `image` onto the board at `port`.
+pub fn flash(
+    options: &BoardAvrdudeOptions,
+    port: &std::path::Path,
+    image: &Image,
+) -> anyhow::Result<()> {
+    let programmer = options
+        .programmer
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("board has no programmer"))?;
+    let partno = options
+        .partno
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("board has no part number"))?;
+    let part = part(partno).ok_or_else(|| {
+        anyhow::anyhow!("the built-in flasher does not know the part `{}`", partno)
+    })?;
+    let erase = options.do_chip_erase.unwrap_or(false);
+
+    match programmer {
+        "arduino" => {
+            let baudrate = options
+                .baudrate
+                .flatten()
+                .map_or(STK500V1_DEFAULT_BAUDRATE, |b| b.get());
+            let mut bootloader = stk500v1::Stk500v1::connect(port, baudrate)?;
+            program(&mut bootloader, &part, image, erase)
+        }
+        "avr109" => {
+            let baudrate = options
+                .baudrate
+                .flatten()
+                .map_or(AVR109_DEFAULT_BAUDRATE, |b| b.get());
+            let mut bootloader = avr109::Avr109::connect(port, baudrate)?;
+            program(&mut bootloader, &part, image, erase)
+        }
+        _ => anyhow::bail!(
+            "the built-in flasher does not support the `{}` programmer, install avrdude instead",
+            programmer
+        ),
+    }
+}
+
+fn program(
+    bootloader: &mut dyn Bootloader,
+    part: &Part,
+    image: &Image,
+    erase: bool,
+) -> anyhow::Result<()> {
+    let signature = bootloader.read_signature()?;
+    if signature != part.signature {
+        anyhow::bail!(
+            "device signature {} does not match the expected {}, is the right board selected?",
+            hex(&signature),
+            hex(&part.signature)
+        );
+    }
+
+    if !image.eeprom.is_empty() && !bootloader.supports_eeprom() {
+        // Leave the old program running instead of the bootloader
+        bootloader.finish().ok();
+        anyhow::bail!(
+            "the program has an EEPROM section, which the bootloader cannot write; use avrdude with an ISP programmer"
+        );
+    }
+
+    if erase {
+        bootloader.erase().context("failed to erase the flash")?;
+    }
+
+    write_and_verify(bootloader, Memory::Flash, &image.flash, part.page_size)?;
+    if !image.eeprom.is_empty() {
+        write_and_verify(bootloader, Memory::Eeprom, &image.eeprom, EEPROM_BLOCK_SIZE)
+            .context("failed to write the EEPROM")?;
+    }
+
+    bootloader
+        .finish()
+        .context("failed to leave the bootloader")
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Memory {
+    Flash,
+    Eeprom,
+}
+
+/// Write `data` to `memory` in blocks of `block_size` and read it back.
+fn write_and_verify(
+    bootloader: &mut dyn Bootloader,
+    memory: Memory,
+    data: &[u8],
+    block_size: usize,
+) -> anyhow::Result<()> {
+    let blocks = data.chunks(block_size);
+    let total = blocks.len();
+
+    let label = match memory {
+        Memory::Flash => "Writing",
+        Memory::Eeprom => "EEPROM",
+    };
+    let mut progress = Progress::new(label, total);
+    for (i, block) in blocks.clone().enumerate() {
+        let address = (i * block_size) as u32;
+        match memory {
+            Memory::Flash => bootloader.write_flash_page(address, block)?,
+            Memory::Eeprom => bootloader.write_eeprom(address, block)?,
+        }
+        progress.step();
+    }
+    progress.done();
+
+    let mut progress = Progress::new("Verifying", total);
+    for (i, block) in blocks.enumerate() {
+        let address = i * block_size;
+        let read = match memory {
+            Memory::Flash => bootloader.read_flash_page(address as u32, block.len())?,
+            Memory::Eeprom => bootloader.read_eeprom(address as u32, block.len())?,
+        };
+        if let Some(offset) = block.iter().zip(&read).position(|(a, b)| a != b) {
+            anyhow::bail!(
+                "verification failed at {:#06x}: wrote {:#04x}, read back {:#04x}",
+                address + offset,
+                block[offset],
+                read[offset]
+            );
+        }
+        progress.step();
+    }
+    progress.done();
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+/// Progress bar in the style of avrdude.
+struct Progress {
+    label: &'static str,
+    total: usize,
+    done: usize,
+}
+
+impl Progress {
+    const WIDTH: usize = 50;
+
+    fn new(label: &'static str, total: usize) -> Self {
+        let progress = Progress {
+            label,
+            total,
+            done: 0,
+        };
+        progress.draw();
+        progress
+    }
+
+    fn step(&mut self) {
+        self.done += 1;
+        self.draw();
+    }
+
+    fn done(self) {
+        eprintln!();
+    }
+
+    fn draw(&self) {
+        let filled = (self.done * Self::WIDTH)
+            .checked_div(self.total)
+            .unwrap_or(Self::WIDTH);
+        let percent = (self.done * 100).checked_div(self.total).unwrap_or(100);
+        eprint!(
+            "\r{:>12} | {}{} | {}%",
+            self.label,
+            "#".repeat(filled),
+            " ".repeat(Self::WIDTH - filled),
+            percent
+        );
+        std::io::stderr().flush().ok();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs::File;
+    use std::os::unix::io::FromRawFd as _;
+
+    /// A pseudo terminal whose slave end is opened by the flasher like a serial port.
+    pub struct Pty {
+        pub master: File,
+        pub path: std::path::PathBuf,
+        // Keeps the slave open, so the pty does not hang up between opens
+        _slave: File,
+    }
+
+    impl Pty {
+        pub fn new() -> Self {
+            use nix::sys::termios;
+
+            let pty = nix::pty::openpty(None, None).unwrap();
+            let mut attrs = termios::tcgetattr(pty.slave).unwrap();
+            termios::cfmakeraw(&mut attrs);
+            termios::tcsetattr(pty.slave, termios::SetArg::TCSANOW, &attrs).unwrap();
+
+            Pty {
+                path: nix::unistd::ttyname(pty.slave).unwrap(),
+                // SAFETY: The descriptors were just opened and are owned by nobody else
+                master: unsafe { File::from_raw_fd(pty.master) },
+                _slave: unsafe { File::from_raw_fd(pty.slave) },
+            }
+        }
+    }
+
+    pub fn read(master: &mut File, n: usize) -> std::io::Result<Vec<u8>> {
+        use std::io::Read as _;
+
+        let mut buf = vec![0; n];
+        master.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+}
@@ -0,0 +1,292 @@
+//! STK500 version 1 protocol, as spoken by Optiboot and the other bootloaders of the `arduino`
+//! programmer boards.
+use anyhow::Context as _;
+use std::io::{Read as _, Write as _};
+use std::time::Duration;
+
+const STK_OK: u8 = 0x10;
+const STK_INSYNC: u8 = 0x14;
+const CRC_EOP: u8 = 0x20;
+
+const STK_GET_SYNC: u8 = 0x30;
+const STK_ENTER_PROGMODE: u8 = 0x50;
+const STK_LEAVE_PROGMODE: u8 = 0x51;
+const STK_LOAD_ADDRESS: u8 = 0x55;
+const STK_PROG_PAGE: u8 = 0x64;
+const STK_READ_PAGE: u8 = 0x74;
+const STK_READ_SIGN: u8 = 0x75;
+
+const MEMTYPE_FLASH: u8 = b'F';
+
+/// How often to try getting in sync with the bootloader after the reset.
+const SYNC_ATTEMPTS: usize = 10;
+const SYNC_TIMEOUT: Duration = Duration::from_millis(200);
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Highest byte address reachable with the 16-bit word addresses of `STK_LOAD_ADDRESS`.
+const MAX_ADDRESS: u32 = 0x1FFFF;
+
+pub struct Stk500v1 {
+    port: Box<dyn serialport::SerialPort>,
+}
+
+impl Stk500v1 {
+    /// Open the port, reset the board into its bootloader and get in sync with it.
+    pub fn connect(port: &std::path::Path, baudrate: u32) -> anyhow::Result<Self> {
+        let port = serialport::new(port.to_string_lossy(), baudrate)
+            .timeout(TIMEOUT)
+            .open()
+            .with_context(|| format!("failed to open serial port `{}`", port.display()))?;
+
+        let mut bootloader = Stk500v1 { port };
+        bootloader.reset();
+        bootloader.sync()?;
+        bootloader.command(&[STK_ENTER_PROGMODE], 0)?;
+        Ok(bootloader)
+    }
+
+    /// Pulse DTR and RTS, which resets boards with the auto-reset circuit.  Like avrdude, this
+    /// carries on for ports without modem control lines.
+    fn reset(&mut self) {
+        self.port.write_data_terminal_ready(false).ok();
+        self.port.write_request_to_send(false).ok();
+        std::thread::sleep(Duration::from_millis(250));
+        self.port.write_data_terminal_ready(true).ok();
+        self.port.write_request_to_send(true).ok();
+        std::thread::sleep(Duration::from_millis(50));
+        self.port.clear(serialport::ClearBuffer::Input).ok();
+    }
+
+    fn sync(&mut self) -> anyhow::Result<()> {
+        self.port.set_timeout(SYNC_TIMEOUT)?;
+        for _ in 0..SYNC_ATTEMPTS {
+            self.port.write_all(&[STK_GET_SYNC, CRC_EOP])?;
+            let mut response = [0; 2];
+            if self.port.read_exact(&mut response).is_ok() && response == [STK_INSYNC, STK_OK] {
+                self.port.set_timeout(TIMEOUT)?;
+                return Ok(());
+            }
+            // Drop anything left over, e.g. output of the previous program
+            self.port.clear(serialport::ClearBuffer::Input).ok();
+        }
+        anyhow::bail!(
+            "bootloader did not respond after {} attempts, is the board connected and the baudrate right?",
+            SYNC_ATTEMPTS
+        )
+    }
+
+    /// Send a command and return the `response_len` bytes of the response between `STK_INSYNC`
+    /// and `STK_OK`.
+    fn command(&mut self, command: &[u8], response_len: usize) -> anyhow::Result<Vec<u8>> {
+        let mut message = command.to_vec();
+        message.push(CRC_EOP);
+        self.port.write_all(&message)?;
+
+        let mut response = vec![0; response_len + 2];
+        self.port
+            .read_exact(&mut response)
+            .with_context(|| format!("no response to command {:#04x}", command[0]))?;
+        if response[0] != STK_INSYNC || response[response_len + 1] != STK_OK {
+            anyhow::bail!(
+                "protocol error in command {:#04x}: got {:02X?}",
+                command[0],
+                response
+            );
+        }
+        Ok(response[1..=response_len].to_vec())
+    }
+
+    fn load_address(&mut self, address: u32) -> anyhow::Result<()> {
+        if address > MAX_ADDRESS {
+            anyhow::bail!("address {:#x} is out of range for STK500v1", address);
+        }
+        let word = (address / 2) as u16;
+        self.command(&[STK_LOAD_ADDRESS, word as u8, (word >> 8) as u8], 0)?;
+        Ok(())
+    }
+}
+
+impl super::Bootloader for Stk500v1 {
+    fn read_signature(&mut self) -> anyhow::Result<[u8; 3]> {
+        let signature = self.command(&[STK_READ_SIGN], 3)?;
+        Ok([signature[0], signature[1], signature[2]])
+    }
+
+    fn write_flash_page(&mut self, address: u32, data: &[u8]) -> anyhow::Result<()> {
+        self.load_address(address)?;
+        let len = data.len() as u16;
+        let mut command = vec![STK_PROG_PAGE, (len >> 8) as u8, len as u8, MEMTYPE_FLASH];
+        command.extend_from_slice(data);
+        self.command(&command, 0)?;
+        Ok(())
+    }
+
+    fn read_flash_page(&mut self, address: u32, len: usize) -> anyhow::Result<Vec<u8>> {
+        self.load_address(address)?;
+        let len16 = len as u16;
+        self.command(
+            &[
+                STK_READ_PAGE,
+                (len16 >> 8) as u8,
+                len16 as u8,
+                MEMTYPE_FLASH,
+            ],
+            len,
+        )
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.command(&[STK_LEAVE_PROGMODE], 0)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::flasher::tests::{read, Pty};
+    use crate::flasher::{part, program};
+    use crate::image::Image;
+    use std::fs::File;
+
+    /// Optiboot running on the other end of a pty.
+    #[derive(Default)]
+    struct MockOptiboot {
+        flash: Vec<u8>,
+        /// Number of sync requests to ignore, like a board still running the old program.
+        ignore_syncs: usize,
+        /// Bits which are stuck at 0 in every flash byte.
+        stuck_low: u8,
+    }
+
+    impl MockOptiboot {
+        fn run(mut self, master: &mut File) -> Vec<u8> {
+            let mut address = 0;
+            loop {
+                let Ok(command) = read(master, 1) else {
+                    return self.flash;
+                };
+                let command = command[0];
+                let mut args = |n: usize| read(master, n).unwrap();
+                let reply = match command {
+                    STK_GET_SYNC if self.ignore_syncs > 0 => {
+                        args(1);
+                        self.ignore_syncs -= 1;
+                        None
+                    }
+                    STK_GET_SYNC | STK_ENTER_PROGMODE | STK_LEAVE_PROGMODE => {
+                        args(1);
+                        Some(vec![])
+                    }
+                    STK_READ_SIGN => {
+                        args(1);
+                        Some(vec![0x1E, 0x95, 0x0F])
+                    }
+                    STK_LOAD_ADDRESS => {
+                        let a = args(3);
+                        address = (a[0] as usize | (a[1] as usize) << 8) * 2;
+                        Some(vec![])
+                    }
+                    STK_PROG_PAGE => {
+                        let header = args(3);
+                        let len = (header[0] as usize) << 8 | header[1] as usize;
+                        let data = args(len + 1);
+                        if self.flash.len() < address + len {
+                            self.flash.resize(address + len, 0xFF);
+                        }
+                        for (i, b) in data[..len].iter().enumerate() {
+                            self.flash[address + i] = b & !self.stuck_low;
+                        }
+                        Some(vec![])
+                    }
+                    STK_READ_PAGE => {
+                        let header = args(4);
+                        let len = (header[0] as usize) << 8 | header[1] as usize;
+                        Some(self.flash[address..address + len].to_vec())
+                    }
+                    other => panic!("unexpected command {other:#04x}"),
+                };
+                if let Some(data) = reply {
+                    let mut response = vec![STK_INSYNC];
+                    response.extend_from_slice(&data);
+                    response.push(STK_OK);
+                    master.write_all(&response).unwrap();
+                }
+                if command == STK_LEAVE_PROGMODE {
+                    return self.flash;
+                }
+            }
+        }
+    }
+
+    fn flash_with(mock: MockOptiboot, image: &Image) -> (anyhow::Result<()>, Vec<u8>) {
+        let pty = Pty::new();
+        let mut master = pty.master.try_clone().unwrap();
+        let board = std::thread::spawn(move || mock.run(&mut master));
+
+        let result = Stk500v1::connect(&pty.path, 115200).and_then(|mut bootloader| {
+            program(&mut bootloader, &part("atmega328p").unwrap(), image, false)
+        });
+        drop(pty);
+        (result, board.join().unwrap())
+    }
+
+    fn test_image(len: usize) -> Image {
+        Image {
+            flash: (0..len).map(|i| (i * 7) as u8).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn writes_and_verifies_flash() {
+        let image = test_image(300);
+        let (result, flash) = flash_with(MockOptiboot::default(), &image);
+
+        result.unwrap();
+        assert_eq!(flash, image.flash);
+    }
+
+    #[test]
+    fn retries_sync() {
+        let image = test_image(10);
+        let mock = MockOptiboot {
+            ignore_syncs: 3,
+            ..Default::default()
+        };
+        let (result, flash) = flash_with(mock, &image);
+
+        result.unwrap();
+        assert_eq!(flash, image.flash);
+    }
+
+    #[test]
+    fn reports_verification_errors() {
+        let image = Image {
+            flash: vec![0x00, 0x01, 0x80, 0xFF],
+            ..Default::default()
+        };
+        let mock = MockOptiboot {
+            stuck_low: 0x80,
+            ..Default::default()
+        };
+        let (result, _) = flash_with(mock, &image);
+
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("verification failed at 0x0002"), "{error}");
+    }
+
+    #[test]
+    fn refuses_eeprom() {
+        let image = Image {
+            eeprom: vec![0x42],
+            ..test_image(10)
+        };
+        let (result, flash) = flash_with(MockOptiboot::default(), &image);
+
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(error.contains("cannot write"), "{error}");
+        // Refused before anything was written
+        assert!(flash.is_empty());
+    }
+}
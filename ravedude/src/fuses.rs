@@ -0,0 +1,105 @@
+//! Writing the fuses declared by the program.
+use std::io::IsTerminal as _;
+
+use crate::config::BoardFuses;
+
+/// avrdude names of the fuse bytes, in the order of the `.fuse` section.
+const FUSE_NAMES: [&str; 3] = ["lfuse", "hfuse", "efuse"];
+
+/// avrdude programmers which talk to a bootloader.  Bootloaders cannot write fuses, and the
+/// built-in flasher only supports these programmers.
+const BOOTLOADER_PROGRAMMERS: [&str; 5] = ["arduino", "avr109", "butterfly", "urclock", "wiring"];
+
+/// The fuse bytes with their avrdude memory names.
+pub fn named(fuses: &[u8]) -> anyhow::Result<Vec<(&'static str, u8)>> {
+    if fuses.len() > FUSE_NAMES.len() {
+        anyhow::bail!(
+            "`.fuse` has {} bytes, only {} fuse bytes are supported",
+            fuses.len(),
+            FUSE_NAMES.len()
+        );
+    }
+    Ok(FUSE_NAMES.into_iter().zip(fuses.iter().copied()).collect())
+}
+
+/// Decide whether to write the fuses of the program.  They are only written when `[board.fuses]`
+/// enables it, the board is programmed through an ISP programmer and the user confirms the values.
+pub fn confirm(
+    fuses: &[u8],
+    options: Option<&BoardFuses>,
+    programmer: Option<&str>,
+) -> anyhow::Result<bool> {
+    if fuses.is_empty() {
+        return Ok(false);
+    }
+    if !options.is_some_and(|options| options.write) {
+        crate::task_message!(
+            "",
+            "{}",
+            colored::Colorize::dimmed(
+                "(Skip the fuses of the program, set `write = true` under [board.fuses] in Ravedude.toml to write them)"
+            )
+        );
+        return Ok(false);
+    }
+    if let Some(programmer) = programmer.filter(|p| BOOTLOADER_PROGRAMMERS.contains(p)) {
+        anyhow::bail!(
+            "fuses cannot be written through the bootloader of the `{}` programmer, use avrdude with an ISP programmer",
+            programmer
+        );
+    }
+
+    let named = named(fuses)?;
+    crate::task_message!(
+        "Fuses",
+        "{}",
+        named
+            .iter()
+            .map(|(name, value)| format!("{name} {value:#04x}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("fuses are only written after confirmation, run ravedude in a terminal");
+    }
+    crate::warning!("wrong fuses can make the chip unusable without a high-voltage programmer.");
+    eprint!("Write these fuses? [y/N] ");
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_fuses_in_section_order() {
+        assert_eq!(
+            named(&[0xFF, 0xDE, 0xFD]).unwrap(),
+            [("lfuse", 0xFF), ("hfuse", 0xDE), ("efuse", 0xFD)]
+        );
+        assert_eq!(named(&[0x62]).unwrap(), [("lfuse", 0x62)]);
+        assert!(named(&[0; 4]).is_err());
+    }
+
+    #[test]
+    fn fuses_are_opt_in() {
+        assert!(!confirm(&[0xFF], None, Some("usbasp")).unwrap());
+        assert!(!confirm(&[0xFF], Some(&BoardFuses { write: false }), Some("usbasp")).unwrap());
+        assert!(!confirm(&[], Some(&BoardFuses { write: true }), Some("arduino")).unwrap());
+    }
+
+    #[test]
+    fn refuses_bootloader_programmers() {
+        for programmer in ["arduino", "avr109", "wiring"] {
+            let error = confirm(&[0xFF], Some(&BoardFuses { write: true }), Some(programmer))
+                .unwrap_err()
+                .to_string();
+            assert!(
+                error.contains(&format!("`{programmer}` programmer")),
+                "{error}"
+            );
+        }
+    }
+}
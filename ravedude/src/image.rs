@@ -0,0 +1,265 @@
+//! Memory images loaded from the ELF file of a program.
+//!
+//! AVR ELF files put all address spaces into one linear space, the flash starts at 0, the data
+//! memory at `0x800000`, the EEPROM at `0x810000` and the fuses at `0x820000`.  The contents of
+//! the memories are found in the loadable segments at their physical (load) address, which for
+//! flash also covers the initial values of `.data`.
+use anyhow::Context as _;
+use object::read::elf::{ElfFile32, ProgramHeader as _};
+use object::{Object as _, ObjectSection as _};
+
+/// Start of the data memory (RAM) in the linear address space.
+const DATA_START: u32 = 0x800000;
+const EEPROM_START: u32 = 0x810000;
+const FUSE_START: u32 = 0x820000;
+/// Start of the lock bits, the end of the fuses.
+const LOCK_START: u32 = 0x830000;
+
+/// Value of erased flash memory.
+pub const ERASED: u8 = 0xFF;
+
+#[derive(Debug, Default)]
+pub struct Image {
+    /// Contents of the flash from address 0, with gaps filled as erased.
+    pub flash: Vec<u8>,
+    /// Initial contents of the EEPROM from address 0, from `.eeprom`.
+    pub eeprom: Vec<u8>,
+    /// Fuse bytes declared in `.fuse`, low fuse first.
+    pub fuses: Vec<u8>,
+    pub sizes: Sizes,
+}
+
+/// Sizes of the sections which take up memory, in bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Sizes {
+    pub text: u64,
+    pub data: u64,
+    /// `.bss` and `.noinit`.
+    pub bss: u64,
+    pub eeprom: u64,
+}
+
+impl Sizes {
+    /// Flash taken by the program, which includes the initial values of `.data`.
+    pub fn flash(&self) -> u64 {
+        self.text + self.data
+    }
+
+    /// RAM taken by static variables, before any stack.
+    pub fn ram(&self) -> u64 {
+        self.data + self.bss
+    }
+}
+
+impl Image {
+    pub fn from_elf_file(path: &std::path::Path) -> anyhow::Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        Self::from_elf(&data).with_context(|| format!("failed to load `{}`", path.display()))
+    }
+
+    pub fn from_elf(data: &[u8]) -> anyhow::Result<Self> {
+        let elf = ElfFile32::<object::Endianness>::parse(data).context("not a 32-bit ELF file")?;
+        let endian = elf.endian();
+
+        let mut image = Image::default();
+        for segment in elf.elf_program_headers() {
+            if segment.p_type(endian) != object::elf::PT_LOAD || segment.p_filesz(endian) == 0 {
+                continue;
+            }
+
+            let address = segment.p_paddr(endian);
+            let bytes = segment
+                .data(endian, data)
+                .map_err(|()| anyhow::anyhow!("segment at {:#x} is out of bounds", address))?;
+            match address {
+                ..DATA_START => place(&mut image.flash, address as usize, bytes),
+                EEPROM_START..FUSE_START => {
+                    place(&mut image.eeprom, (address - EEPROM_START) as usize, bytes)
+                }
+                FUSE_START..LOCK_START => {
+                    place(&mut image.fuses, (address - FUSE_START) as usize, bytes)
+                }
+                _ => {}
+            }
+        }
+
+        for section in elf.sections() {
+            let size = section.size();
+            match section.name() {
+                Ok(".text") => image.sizes.text += size,
+                Ok(".data") => image.sizes.data += size,
+                Ok(".bss" | ".noinit") => image.sizes.bss += size,
+                Ok(".eeprom") => image.sizes.eeprom += size,
+                _ => {}
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+fn place(memory: &mut Vec<u8>, address: usize, bytes: &[u8]) {
+    let end = address + bytes.len();
+    if memory.len() < end {
+        memory.resize(end, ERASED);
+    }
+    memory[address..end].copy_from_slice(bytes);
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Image, Sizes};
+
+    /// Build a minimal AVR ELF file with one loadable segment per `(load address, data)`.
+    pub fn elf(segments: &[(u32, &[u8])]) -> Vec<u8> {
+        elf_with_sections(segments, &[])
+    }
+
+    /// Like [`elf`], with additional sections given as `(name, size)` and without contents.
+    pub fn elf_with_sections(segments: &[(u32, &[u8])], sections: &[(&str, u32)]) -> Vec<u8> {
+        const EHDR_SIZE: usize = 52;
+        const PHDR_SIZE: usize = 32;
+        const SHDR_SIZE: usize = 40;
+
+        let mut file = Vec::new();
+        file.extend_from_slice(b"\x7fELF");
+        // 32-bit, little endian, version 1
+        file.extend_from_slice(&[1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let half = |file: &mut Vec<u8>, v: u16| file.extend_from_slice(&v.to_le_bytes());
+        let word = |file: &mut Vec<u8>, v: u32| file.extend_from_slice(&v.to_le_bytes());
+        half(&mut file, object::elf::ET_EXEC);
+        half(&mut file, object::elf::EM_AVR);
+        word(&mut file, 1);
+        word(&mut file, 0); // e_entry
+        word(&mut file, EHDR_SIZE as u32); // e_phoff
+        let e_shoff = file.len();
+        word(&mut file, 0); // e_shoff, patched below
+        word(&mut file, 0); // e_flags
+        half(&mut file, EHDR_SIZE as u16);
+        half(&mut file, PHDR_SIZE as u16);
+        half(&mut file, segments.len() as u16);
+        half(&mut file, SHDR_SIZE as u16);
+        let shnum = if sections.is_empty() {
+            0
+        } else {
+            // Null section and the section name table
+            sections.len() + 2
+        };
+        half(&mut file, shnum as u16);
+        half(&mut file, shnum.saturating_sub(1) as u16); // e_shstrndx
+
+        let mut offset = EHDR_SIZE + PHDR_SIZE * segments.len();
+        for &(address, data) in segments {
+            word(&mut file, object::elf::PT_LOAD);
+            word(&mut file, offset as u32);
+            word(&mut file, address); // p_vaddr
+            word(&mut file, address); // p_paddr
+            word(&mut file, data.len() as u32);
+            word(&mut file, data.len() as u32);
+            word(&mut file, object::elf::PF_R | object::elf::PF_X);
+            word(&mut file, 1);
+            offset += data.len();
+        }
+        for &(_, data) in segments {
+            file.extend_from_slice(data);
+        }
+        if sections.is_empty() {
+            return file;
+        }
+
+        let mut names = vec![0];
+        let mut name_offsets = Vec::new();
+        for name in sections.iter().map(|(name, _)| *name).chain([".shstrtab"]) {
+            name_offsets.push(names.len() as u32);
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+        let names_offset = file.len();
+        file.extend_from_slice(&names);
+        file.resize(file.len().next_multiple_of(4), 0);
+
+        let shoff = file.len() as u32;
+        file[e_shoff..e_shoff + 4].copy_from_slice(&shoff.to_le_bytes());
+        let header = |file: &mut Vec<u8>, name, sh_type, offset, size| {
+            for value in [name, sh_type, 0, 0, offset, size, 0, 0, 1, 0] {
+                word(file, value);
+            }
+        };
+        header(&mut file, 0, object::elf::SHT_NULL, 0, 0);
+        for (&(_, size), &name) in sections.iter().zip(&name_offsets) {
+            header(&mut file, name, object::elf::SHT_NOBITS, 0, size);
+        }
+        header(
+            &mut file,
+            name_offsets[sections.len()],
+            object::elf::SHT_STRTAB,
+            names_offset as u32,
+            names.len() as u32,
+        );
+        file
+    }
+
+    #[test]
+    fn flash_from_segments() {
+        let image = Image::from_elf(&elf(&[
+            (0x0000, &[0x0C, 0x94, 0x34, 0x00]),
+            (0x0008, &[0x11, 0x22]),
+            // RAM is not part of the flash image
+            (0x800100, &[0x33]),
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            image.flash,
+            [0x0C, 0x94, 0x34, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x11, 0x22]
+        );
+    }
+
+    #[test]
+    fn eeprom_and_fuses_from_segments() {
+        let image = Image::from_elf(&elf(&[
+            (0x0000, &[0x0C, 0x94]),
+            (0x810002, &[0x42, 0x43]),
+            (0x820000, &[0xFF, 0xDE, 0xFD]),
+        ]))
+        .unwrap();
+
+        assert_eq!(image.flash, [0x0C, 0x94]);
+        assert_eq!(image.eeprom, [0xFF, 0xFF, 0x42, 0x43]);
+        assert_eq!(image.fuses, [0xFF, 0xDE, 0xFD]);
+    }
+
+    #[test]
+    fn rejects_non_elf() {
+        assert!(Image::from_elf(b"not an elf file").is_err());
+    }
+
+    #[test]
+    fn section_sizes() {
+        let image = Image::from_elf(&elf_with_sections(
+            &[(0x0000, &[0x0C, 0x94])],
+            &[
+                (".text", 1000),
+                (".data", 20),
+                (".bss", 30),
+                (".noinit", 4),
+                (".eeprom", 8),
+                (".debug_info", 5000),
+            ],
+        ))
+        .unwrap();
+
+        assert_eq!(
+            image.sizes,
+            Sizes {
+                text: 1000,
+                data: 20,
+                bss: 34,
+                eeprom: 8,
+            }
+        );
+        assert_eq!(image.sizes.flash(), 1020);
+        assert_eq!(image.sizes.ram(), 54);
+    }
+}
@@ -0,0 +1,240 @@
+//! `ravedude boards` and `ravedude ports`: the known boards and the connected serial ports.
+use std::collections::HashMap;
+
+use crate::config::{BoardConfig, BoardPortID, BoardUSBInfo};
+
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct BoardEntry {
+    /// Name for `board` under [general] in `Ravedude.toml`.
+    id: String,
+    name: Option<String>,
+    mcu: Option<String>,
+    programmer: Option<String>,
+    usb_ids: Vec<String>,
+}
+
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct PortEntry {
+    port: String,
+    usb_id: Option<String>,
+    serial_number: Option<String>,
+    product: Option<String>,
+    /// Boards whose program shows up with the USB id of this port.
+    boards: Vec<String>,
+    /// Boards whose bootloader shows up with the USB id of this port.
+    bootloaders: Vec<String>,
+}
+
+fn usb_id(BoardPortID { vid, pid }: BoardPortID) -> String {
+    format!("{vid:04X}:{pid:04X}")
+}
+
+fn sorted(boards: &HashMap<String, BoardConfig>) -> Vec<(&String, &BoardConfig)> {
+    let mut boards: Vec<_> = boards.iter().collect();
+    boards.sort_by_key(|(id, _)| *id);
+    boards
+}
+
+fn board_entries(boards: &HashMap<String, BoardConfig>) -> Vec<BoardEntry> {
+    sorted(boards)
+        .into_iter()
+        .map(|(id, board)| BoardEntry {
+            id: id.clone(),
+            name: board.name.clone(),
+            mcu: board.avrdude.as_ref().and_then(|a| a.partno.clone()),
+            programmer: board.avrdude.as_ref().and_then(|a| a.programmer.clone()),
+            usb_ids: match &board.usb_info {
+                Some(BoardUSBInfo::PortIds(port_ids)) => {
+                    port_ids.iter().copied().map(usb_id).collect()
+                }
+                _ => Vec::new(),
+            },
+        })
+        .collect()
+}
+
+/// The boards matching a USB id, split into those running a program and those in their bootloader.
+fn matching_boards(
+    id: BoardPortID,
+    boards: &HashMap<String, BoardConfig>,
+) -> (Vec<String>, Vec<String>) {
+    let mut programs = Vec::new();
+    let mut bootloaders = Vec::new();
+    for (name, board) in sorted(boards) {
+        let bootloader_port_ids = board
+            .reset
+            .as_ref()
+            .and_then(|reset| reset.bootloader_port_ids.as_deref())
+            .unwrap_or_default();
+        if bootloader_port_ids.contains(&id) {
+            bootloaders.push(name.clone());
+        } else if board.application_port_ids().contains(&id) {
+            programs.push(name.clone());
+        }
+    }
+    (programs, bootloaders)
+}
+
+fn port_entries(boards: &HashMap<String, BoardConfig>) -> anyhow::Result<Vec<PortEntry>> {
+    let mut ports = serialport::available_ports()?;
+    ports.sort_by(|a, b| a.port_name.cmp(&b.port_name));
+
+    Ok(ports
+        .into_iter()
+        .map(|port| match port.port_type {
+            serialport::SerialPortType::UsbPort(info) => {
+                let id = BoardPortID {
+                    vid: info.vid,
+                    pid: info.pid,
+                };
+                let (boards, bootloaders) = matching_boards(id, boards);
+                PortEntry {
+                    port: port.port_name,
+                    usb_id: Some(usb_id(id)),
+                    serial_number: info.serial_number,
+                    product: info.product,
+                    boards,
+                    bootloaders,
+                }
+            }
+            _ => PortEntry {
+                port: port.port_name,
+                usb_id: None,
+                serial_number: None,
+                product: None,
+                boards: Vec::new(),
+                bootloaders: Vec::new(),
+            },
+        })
+        .collect())
+}
+
+/// Print rows as columns aligned to the widest cell.
+fn print_table<const N: usize>(header: [&str; N], rows: Vec<[String; N]>) {
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let format_row = |row: [&str; N]| {
+        let cells: Vec<_> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+    println!("{}", colored::Colorize::bold(format_row(header).as_str()));
+    for row in &rows {
+        println!("{}", format_row(row.each_ref().map(String::as_str)));
+    }
+}
+
+fn or_dash(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "-".to_string())
+}
+
+pub fn boards(boards: &HashMap<String, BoardConfig>, json: bool) -> anyhow::Result<()> {
+    let entries = board_entries(boards);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    print_table(
+        ["BOARD", "MCU", "PROGRAMMER", "NAME"],
+        entries
+            .into_iter()
+            .map(|entry| {
+                [
+                    entry.id,
+                    or_dash(entry.mcu),
+                    or_dash(entry.programmer),
+                    or_dash(entry.name),
+                ]
+            })
+            .collect(),
+    );
+    Ok(())
+}
+
+pub fn ports(boards: &HashMap<String, BoardConfig>, json: bool) -> anyhow::Result<()> {
+    let entries = port_entries(boards)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        crate::warning!("no serial ports found.");
+        return Ok(());
+    }
+
+    print_table(
+        ["PORT", "USB ID", "SERIAL NUMBER", "BOARDS"],
+        entries
+            .into_iter()
+            .map(|entry| {
+                let boards: Vec<_> = entry
+                    .boards
+                    .into_iter()
+                    .chain(
+                        entry
+                            .bootloaders
+                            .into_iter()
+                            .map(|board| format!("{board} (bootloader)")),
+                    )
+                    .collect();
+                [
+                    entry.port,
+                    or_dash(entry.usb_id),
+                    or_dash(entry.serial_number),
+                    if boards.is_empty() {
+                        "-".to_string()
+                    } else {
+                        boards.join(", ")
+                    },
+                ]
+            })
+            .collect(),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_programs_and_bootloaders() {
+        let boards = crate::board::get_builtin_boards().unwrap();
+        let id = |vid, pid| BoardPortID { vid, pid };
+
+        let (programs, bootloaders) = matching_boards(id(0x2341, 0x0043), &boards);
+        assert_eq!(programs, ["uno"]);
+        assert!(bootloaders.is_empty());
+
+        let (programs, bootloaders) = matching_boards(id(0x2341, 0x0036), &boards);
+        assert!(programs.is_empty());
+        assert_eq!(bootloaders, ["leonardo"]);
+
+        assert_eq!(
+            matching_boards(id(0x1234, 0x5678), &boards),
+            (vec![], vec![])
+        );
+    }
+
+    #[test]
+    fn lists_boards_sorted_with_mcu() {
+        let entries = board_entries(&crate::board::get_builtin_boards().unwrap());
+        assert!(entries.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+        let uno = entries.iter().find(|entry| entry.id == "uno").unwrap();
+        assert_eq!(uno.mcu.as_deref(), Some("atmega328p"));
+        assert_eq!(uno.programmer.as_deref(), Some("arduino"));
+        assert!(uno.usb_ids.contains(&"2341:0043".to_string()));
+    }
+}
@@ -10,11 +10,26 @@ mod avrdude;
 mod board;
 mod config;
 mod console;
+mod flasher;
+mod fuses;
+mod image;
+mod list;
+mod reset;
+mod size;
+mod symbolize;
 mod ui;
 
 /// This represents the minimum (Major, Minor) version raverdude requires avrdude to meet.
 const MIN_VERSION_AVRDUDE: (u8, u8) = (6, 3);
 
+/// Baudrate used for USB CDC-ACM consoles when none is configured.
+const DEFAULT_USB_CONSOLE_BAUDRATE: std::num::NonZeroU32 = match std::num::NonZeroU32::new(115200) {
+    Some(baudrate) => baudrate,
+    None => unreachable!(),
+};
+
+const NO_PORT_HINT: &str = "no matching serial port found, use -P, add a serial-port entry under [general] in Ravedude.toml, or set RAVEDUDE_PORT in your environment";
+
 /// ravedude is a rust wrapper around avrdude for providing the smoothest possible development
 /// experience with rust on AVR microcontrollers.
 ///
@@ -54,6 +69,36 @@ struct Args {
     #[structopt(short = "d", long = "reset-delay")]
     reset_delay: Option<u64>,
 
+    /// How to program the board: `avrdude`, the `builtin` flasher, or `auto` to use avrdude if it
+    /// is installed and the built-in flasher otherwise.
+    #[structopt(long = "flasher")]
+    flasher: Option<config::Flasher>,
+
+    /// Resolve code addresses printed on the console, e.g. by a panic handler, to functions and
+    /// source lines of the flashed binary.
+    #[structopt(long = "symbolize")]
+    symbolize: bool,
+
+    /// Prefix each line on the console with the time since it was opened.
+    #[structopt(long = "timestamps")]
+    timestamps: bool,
+
+    /// Append everything shown on the console to this file.
+    #[structopt(long = "log-file", parse(from_os_str))]
+    log_file: Option<std::path::PathBuf>,
+
+    /// Show the bytes received on the console as a hex dump.
+    #[structopt(long = "hex")]
+    hex: bool,
+
+    /// What to send for a newline typed on the console: `lf`, `crlf` or `cr`.
+    #[structopt(long = "send-newline")]
+    send_newline: Option<config::SendNewline>,
+
+    /// How to translate received newlines: `raw`, `lf` (CRLF to LF) or `crlf` (LF to CRLF).
+    #[structopt(long = "receive-newline")]
+    receive_newline: Option<config::ReceiveNewline>,
+
     /// Print the avrdude command that is executed for flashing the binary.
     #[structopt(long = "debug-avrdude")]
     debug_avrdude: bool,
@@ -69,7 +114,27 @@ struct Args {
     /// Should not be used in newer configurations.
     #[structopt(name = "LEGACY BINARY", parse(from_os_str))]
     bin_legacy: Option<std::path::PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
+
+#[derive(structopt::StructOpt, Debug)]
+enum Command {
+    /// List the boards ravedude knows, with their MCU and programmer.
+    Boards {
+        /// Print the list as JSON.
+        #[structopt(long = "json")]
+        json: bool,
+    },
+    /// List the serial ports with their USB ids and the boards they match.
+    Ports {
+        /// Print the list as JSON.
+        #[structopt(long = "json")]
+        json: bool,
+    },
+}
+
 impl Args {
     /// Get the board name for legacy configurations.
     /// `None` if the configuration isn't a legacy configuration or the board name doesn't exist.
@@ -130,6 +195,16 @@ fn ravedude() -> anyhow::Result<()> {
 
     let manifest_path = find_manifest()?;
 
+    match args.command {
+        Some(Command::Boards { json }) => {
+            return list::boards(&board::get_project_boards(manifest_path.as_deref())?, json)
+        }
+        Some(Command::Ports { json }) => {
+            return list::ports(&board::get_project_boards(manifest_path.as_deref())?, json)
+        }
+        None => (),
+    }
+
     let mut ravedude_config = match (manifest_path.as_deref(), args.legacy_board_name()) {
         (Some(_), Some(board_name)) => {
             anyhow::bail!("can't pass board as command-line argument when Ravedude.toml is present; set `board = {:?}` under [general] in Ravedude.toml", board_name);
@@ -144,7 +219,7 @@ fn ravedude() -> anyhow::Result<()> {
                 toml::to_string(&config::RavedudeConfig::from_args(&args)?)?
             );
 
-            board::get_board_from_name(&board_name)?
+            board::get_board_from_name(&board_name, &[])?
         }
         (None, None) => {
             anyhow::bail!("couldn't find Ravedude.toml in project");
@@ -160,8 +235,6 @@ fn ravedude() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    avrdude::Avrdude::require_min_ver(MIN_VERSION_AVRDUDE)?;
-
     let Some(mut board) = ravedude_config.board_config else {
         anyhow::bail!("no named board given and no board options provided");
     };
@@ -177,19 +250,56 @@ fn ravedude() -> anyhow::Result<()> {
         &board.name.as_deref().unwrap_or("Unnamed Board")
     );
 
-    let port = match ravedude_config.general_options.port {
+    let bootloader_port_ids = board
+        .reset
+        .as_ref()
+        .and_then(|reset| reset.bootloader_port_ids.clone());
+
+    let serial_number = ravedude_config.general_options.serial_number.as_deref();
+    let port = match ravedude_config.general_options.port.clone() {
         Some(port) => Ok(Some(port)),
-        None => match board.guess_port() {
-            Some(Ok(port)) => Ok(Some(port)),
-            p @ Some(Err(_)) => p.transpose().context(
-                "no matching serial port found, use -P, add a serial-port entry under [general] in Ravedude.toml, or set RAVEDUDE_PORT in your environment",
-            ),
+        None => match board.guess_port(serial_number) {
+            Some(Ok(Some(port))) => Ok(Some(port)),
+            // Without a running program, the port only shows up once the bootloader runs
+            Some(Ok(None)) if bootloader_port_ids.is_some() && args.bin_or_legacy_bin().is_some() => {
+                Ok(None)
+            }
+            Some(Ok(None)) => match serial_number {
+                Some(serial_number) => Err(anyhow::anyhow!(
+                    "no board with serial number `{}` found, check `serial-number` under [general] in Ravedude.toml",
+                    serial_number
+                )),
+                None => Err(anyhow::anyhow!("Serial port not found.")).context(NO_PORT_HINT),
+            },
+            // The board cannot be recognized by its USB ids
+            Some(Err(e)) if matches!(board.usb_info, Some(config::BoardUSBInfo::Error(_))) => {
+                Err(e).context(NO_PORT_HINT)
+            }
+            // Several matching boards
+            Some(Err(e)) => Err(e),
             None => Ok(None),
         },
     }?;
 
     if let Some(bin) = args.bin_or_legacy_bin() {
-        if let Some(wait_time) = args.reset_delay {
+        let image = image::Image::from_elf_file(bin)?;
+        size::report(&image.sizes, board.memory.as_ref())?;
+
+        let builtin_flasher = use_builtin_flasher(
+            ravedude_config.general_options.flasher.unwrap_or_default(),
+            &board_avrdude_options,
+        )?;
+        let fuses = if fuses::confirm(
+            &image.fuses,
+            board.fuses.as_ref(),
+            board_avrdude_options.programmer.as_deref(),
+        )? {
+            fuses::named(&image.fuses)?
+        } else {
+            Vec::new()
+        };
+
+        let flash_port = if let Some(wait_time) = args.reset_delay {
             if wait_time > 0 {
                 println!("Waiting {} ms before proceeding", wait_time);
                 let wait_time = Duration::from_millis(wait_time);
@@ -197,14 +307,30 @@ fn ravedude() -> anyhow::Result<()> {
             } else {
                 println!("Assuming board has been reset");
             }
-        } else if matches!(board.reset, Some(config::ResetOptions { automatic: false })) {
-            warning!("this board cannot reset itself.");
-            eprintln!();
-            eprint!("Once reset, press ENTER here: ");
-            std::io::stdin().read_line(&mut String::new())?;
-        }
+            bootloader_port_ids
+                .as_deref()
+                .and_then(config::find_port)
+                .or_else(|| port.clone())
+        } else if let Some(bootloader_port_ids) = bootloader_port_ids.as_deref() {
+            task_message!("Resetting", "into the bootloader");
+            Some(reset::touch_1200bps(port.as_deref(), bootloader_port_ids)?)
+        } else {
+            if matches!(
+                board.reset,
+                Some(config::ResetOptions {
+                    automatic: false,
+                    ..
+                })
+            ) {
+                warning!("this board cannot reset itself.");
+                eprintln!();
+                eprint!("Once reset, press ENTER here: ");
+                std::io::stdin().read_line(&mut String::new())?;
+            }
+            port.clone()
+        };
 
-        if let Some(port) = port.as_ref() {
+        if let Some(port) = flash_port.as_ref() {
             task_message!(
                 "Programming",
                 "{} {} {}",
@@ -216,13 +342,22 @@ fn ravedude() -> anyhow::Result<()> {
             task_message!("Programming", "{}", bin.display(),);
         }
 
-        let mut avrdude = avrdude::Avrdude::run(
-            &board_avrdude_options,
-            port.as_ref(),
-            bin,
-            args.debug_avrdude,
-        )?;
-        avrdude.wait()?;
+        if builtin_flasher {
+            let port = flash_port
+                .as_ref()
+                .context("the built-in flasher needs the serial port of the board")?;
+            flasher::flash(&board_avrdude_options, port, &image)?;
+        } else {
+            let mut avrdude = avrdude::Avrdude::run(
+                &board_avrdude_options,
+                flash_port.as_ref(),
+                bin,
+                !image.eeprom.is_empty(),
+                &fuses,
+                args.debug_avrdude,
+            )?;
+            avrdude.wait()?;
+        }
 
         task_message!("Programmed", "{}", bin.display());
     } else {
@@ -234,25 +369,138 @@ fn ravedude() -> anyhow::Result<()> {
     }
 
     if ravedude_config.general_options.open_console {
-        let baudrate = ravedude_config
-            .general_options
-            .serial_baudrate
-            .context(if manifest_path.is_some() {
-                "`serial-baudrate` under [general] in Ravedude.toml is needed for the serial console"
-            }else{
-                "-b/--baudrate is needed for the serial console"
-            })?;
-
-        let port = port.context("console can only be opened for devices with USB-to-Serial")?;
+        let (port, baudrate) = match ravedude_config.general_options.console_port_ids.as_deref() {
+            Some(console_port_ids) => {
+                task_message!("Waiting", "for the USB console of the firmware");
+                let port = console::wait_for_port(console_port_ids)?;
+                // The baudrate has no meaning for USB CDC-ACM, any value works
+                let baudrate = ravedude_config
+                    .general_options
+                    .serial_baudrate
+                    .unwrap_or(DEFAULT_USB_CONSOLE_BAUDRATE);
+                (port, baudrate)
+            }
+            None => {
+                let baudrate = ravedude_config
+                    .general_options
+                    .serial_baudrate
+                    .context(if manifest_path.is_some() {
+                        "`serial-baudrate` under [general] in Ravedude.toml is needed for the serial console"
+                    }else{
+                        "-b/--baudrate is needed for the serial console"
+                    })?;
+
+                let port = if bootloader_port_ids.is_some()
+                    && args.bin_or_legacy_bin().is_some()
+                    && ravedude_config.general_options.port.is_none()
+                {
+                    // The port of the program comes back after the bootloader, maybe renamed
+                    task_message!("Waiting", "for the program to show up");
+                    reset::wait_for_application(&board)?
+                } else {
+                    port.context("console can only be opened for devices with USB-to-Serial, or with `console-port-ids` under [general] in Ravedude.toml")?
+                };
+                (port, baudrate)
+            }
+        };
+
+        let symbolizer = match (
+            ravedude_config.general_options.symbolize,
+            args.bin_or_legacy_bin(),
+        ) {
+            (true, Some(bin)) => Some(symbolize::Symbolizer::new(
+                symbolize::Symbols::from_elf_file(bin)?,
+            )),
+            (true, None) => {
+                warning!("addresses can only be symbolized when a binary is given.");
+                None
+            }
+            (false, _) => None,
+        };
 
         task_message!("Console", "{} at {} baud", port.display(), baudrate);
         task_message!("", "{}", "CTRL+C to exit.".dimmed());
         // Empty line for visual consistency
         eprintln!();
-        console::open(&port, baudrate.get())?;
+        let general = &ravedude_config.general_options;
+        // An explicitly selected port is only reconnected under its name
+        let reconnect_port_ids = match (&general.console_port_ids, &general.port) {
+            (Some(console_port_ids), _) => console_port_ids.clone(),
+            (None, None) => board.application_port_ids(),
+            (None, Some(_)) => Vec::new(),
+        };
+        console::open(
+            &port,
+            baudrate.get(),
+            console::Options {
+                timestamps: general.console_timestamps,
+                log: general.console_log.clone(),
+                hex: general.console_hex,
+                send_newline: general.console_send_newline.unwrap_or_default(),
+                receive_newline: general.console_receive_newline.unwrap_or_default(),
+                symbolizer,
+                reconnect_port_ids,
+                reconnect_serial_number: general.serial_number.clone(),
+            },
+        )?;
     } else if args.bin.is_none() && port.is_some() {
         warning!("you probably meant to add -c/--open-console?");
     }
 
     Ok(())
 }
+
+/// Decide between avrdude and the built-in flasher, checking that avrdude is recent enough when
+/// it is used.
+fn use_builtin_flasher(
+    selected: config::Flasher,
+    options: &config::BoardAvrdudeOptions,
+) -> anyhow::Result<bool> {
+    match selected {
+        config::Flasher::Builtin => Ok(true),
+        config::Flasher::Avrdude => {
+            avrdude::Avrdude::require_min_ver(MIN_VERSION_AVRDUDE)?;
+            Ok(false)
+        }
+        config::Flasher::Auto => match avrdude::Avrdude::require_min_ver(MIN_VERSION_AVRDUDE) {
+            Ok(()) => Ok(false),
+            Err(e)
+                if avrdude_missing(&e)
+                    && options.programmer.as_deref().is_some_and(flasher::supports) =>
+            {
+                task_message!(
+                    "",
+                    "{}",
+                    "(avrdude not found, using the built-in flasher)".dimmed()
+                );
+                Ok(true)
+            }
+            Err(e) => Err(e),
+        },
+    }
+}
+
+/// Whether the avrdude version check failed because avrdude is not installed, as opposed to
+/// being too old or printing an unexpected version.
+fn avrdude_missing(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_only_without_avrdude() {
+        let missing = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .context("Failed reading avrdude version information.");
+        assert!(avrdude_missing(&missing));
+
+        let too_old = anyhow::anyhow!("Avrdude does not meet minimum version requirements.");
+        assert!(!avrdude_missing(&too_old));
+    }
+}
@@ -0,0 +1,62 @@
+//! Resetting boards with native USB (ATmega32U4) into their bootloader.
+//!
+//! These boards have no auto-reset circuit.  Instead the USB stack of the running program resets
+//! into the bootloader when its port is opened at 1200 baud, the Arduino "1200 baud touch".  The
+//! bootloader then enumerates with its own USB ids, often under a different port name.
+use anyhow::Context as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::{self, BoardConfig, BoardPortID, BoardUSBInfo};
+
+/// Caterina shows up within a second or two after the reset and waits for 8 seconds.
+const BOOTLOADER_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for the bootloader when the board has to be reset by hand.
+const MANUAL_RESET_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long the program may take to enumerate again after it was flashed.
+const APPLICATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reset the board into its bootloader and return the port of the bootloader.
+///
+/// A board which is already in its bootloader is used as is.  Without a `port` to touch, e.g.
+/// because the running program has no USB console, the user is asked to press reset instead.
+pub fn touch_1200bps(
+    port: Option<&Path>,
+    bootloader_port_ids: &[BoardPortID],
+) -> anyhow::Result<PathBuf> {
+    if let Some(bootloader) = config::find_port(bootloader_port_ids) {
+        return Ok(bootloader);
+    }
+
+    let Some(port) = port else {
+        crate::warning!("no running program found to reset, press the reset button of the board.");
+        let timeout = MANUAL_RESET_TIMEOUT.as_secs();
+        return config::wait_for_port(bootloader_port_ids, MANUAL_RESET_TIMEOUT)
+            .with_context(|| format!("the bootloader did not show up within {timeout} seconds"));
+    };
+
+    let mut serial = serialport::new(port.to_string_lossy(), 1200)
+        .open()
+        .with_context(|| format!("failed to open serial port `{}`", port.display()))?;
+    // The program resets once the port is closed with DTR low
+    serial.write_data_terminal_ready(false).ok();
+    drop(serial);
+
+    config::wait_for_port(bootloader_port_ids, BOOTLOADER_TIMEOUT).with_context(|| {
+        format!(
+            "the bootloader did not show up within {} seconds after resetting `{}`, press the reset button and try again",
+            BOOTLOADER_TIMEOUT.as_secs(),
+            port.display()
+        )
+    })
+}
+
+/// Wait for the port of the program after the board was flashed through its bootloader.
+pub fn wait_for_application(board: &BoardConfig) -> anyhow::Result<PathBuf> {
+    let Some(BoardUSBInfo::PortIds(_)) = &board.usb_info else {
+        anyhow::bail!("the USB ids of the board are not known, use -P to select the console port");
+    };
+
+    config::wait_for_port(&board.application_port_ids(), APPLICATION_TIMEOUT)
+        .context("the program did not show up as a serial port, does it provide a USB console?")
+}
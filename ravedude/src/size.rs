@@ -0,0 +1,110 @@
+//! Size report of the program against the memory of the board.
+use crate::config::BoardMemory;
+use crate::image::Sizes;
+
+/// Print how much memory the program takes and refuse programs which do not fit into the flash
+/// next to the bootloader.
+pub fn report(sizes: &Sizes, memory: Option<&BoardMemory>) -> anyhow::Result<()> {
+    crate::task_message!(
+        "Size",
+        ".text {}, .data {}, .bss {}, .eeprom {} bytes",
+        sizes.text,
+        sizes.data,
+        sizes.bss,
+        sizes.eeprom
+    );
+    let Some(memory) = memory else {
+        return Ok(());
+    };
+
+    if let Some(flash) = available_flash(memory) {
+        crate::task_message!("Flash", "{}", usage(sizes.flash(), flash));
+    }
+    if let Some(ram) = memory.ram {
+        crate::task_message!("RAM", "{}", usage(sizes.ram(), ram.into()));
+        if sizes.ram() > ram.into() {
+            crate::warning!("static variables take more RAM than the board has.");
+        }
+    }
+    if let Some(eeprom) = memory.eeprom {
+        if sizes.eeprom > 0 {
+            crate::task_message!("EEPROM", "{}", usage(sizes.eeprom, eeprom.into()));
+        }
+        if sizes.eeprom > eeprom.into() {
+            crate::warning!(".eeprom is larger than the EEPROM of the board.");
+        }
+    }
+
+    check_flash(sizes, memory)
+}
+
+/// Flash left for the program next to the bootloader.
+fn available_flash(memory: &BoardMemory) -> Option<u64> {
+    let flash = u64::from(memory.flash?);
+    Some(flash.saturating_sub(memory.bootloader.unwrap_or(0).into()))
+}
+
+fn check_flash(sizes: &Sizes, memory: &BoardMemory) -> anyhow::Result<()> {
+    match available_flash(memory) {
+        Some(flash) if sizes.flash() > flash => anyhow::bail!(
+            "program is {} bytes too large for the flash of the board ({} bytes available)",
+            sizes.flash() - flash,
+            flash
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn usage(used: u64, available: u64) -> String {
+    let percent = if available == 0 {
+        100.0
+    } else {
+        used as f64 * 100.0 / available as f64
+    };
+    format!("{used} of {available} bytes ({percent:.1}%)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNO: BoardMemory = BoardMemory {
+        flash: Some(32768),
+        ram: Some(2048),
+        eeprom: Some(1024),
+        bootloader: Some(512),
+    };
+
+    fn sizes(text: u64, data: u64) -> Sizes {
+        Sizes {
+            text,
+            data,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_programs_up_to_the_bootloader() {
+        check_flash(&sizes(32000, 256), &UNO).unwrap();
+    }
+
+    #[test]
+    fn refuses_programs_overlapping_the_bootloader() {
+        let error = check_flash(&sizes(32000, 257), &UNO).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "program is 1 bytes too large for the flash of the board (32256 bytes available)"
+        );
+    }
+
+    #[test]
+    fn unknown_flash_size_is_not_checked() {
+        let memory = BoardMemory { flash: None, ..UNO };
+        check_flash(&sizes(1 << 20, 0), &memory).unwrap();
+    }
+
+    #[test]
+    fn formats_usage() {
+        assert_eq!(usage(1246, 32256), "1246 of 32256 bytes (3.9%)");
+    }
+}
@@ -0,0 +1,238 @@
+//! Resolve code addresses printed on the console, e.g. by panic handlers or stack dumps, to the
+//! functions and source lines of the program which was flashed.
+//!
+//! Addresses are recognized as hexadecimal numbers with a `0x` prefix and must be byte addresses
+//! like in `avr-objdump`.  Return addresses read from the stack are in words and have to be
+//! doubled by the firmware before printing them.
+use anyhow::Context as _;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use object::{Object as _, ObjectSection as _, ObjectSymbol as _};
+
+type Reader = gimli::EndianArcSlice<gimli::RunTimeEndian>;
+
+struct Symbol {
+    address: u64,
+    size: u64,
+    name: String,
+}
+
+pub struct Symbols {
+    /// Functions of the symbol table, sorted by address.
+    functions: Vec<Symbol>,
+    /// Line information, empty if the program was built without debug info.
+    lines: addr2line::Context<Reader>,
+}
+
+impl Symbols {
+    pub fn from_elf_file(path: &std::path::Path) -> anyhow::Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        let elf = object::File::parse(&*data)
+            .with_context(|| format!("failed to load `{}`", path.display()))?;
+
+        let mut functions: Vec<_> = elf
+            .symbols()
+            .filter(|symbol| symbol.kind() == object::SymbolKind::Text && symbol.size() > 0)
+            .filter_map(|symbol| {
+                Some(Symbol {
+                    address: symbol.address(),
+                    size: symbol.size(),
+                    name: addr2line::demangle_auto(Cow::Borrowed(symbol.name().ok()?), None)
+                        .into_owned(),
+                })
+            })
+            .collect();
+        functions.sort_by_key(|symbol| symbol.address);
+
+        let lines = load_lines(&elf)
+            .with_context(|| format!("invalid debug info in `{}`", path.display()))?;
+
+        Ok(Symbols { functions, lines })
+    }
+
+    /// Describe a code address as `function+offset at file:line`.
+    pub fn describe(&self, address: u64) -> Option<String> {
+        let symbol = lookup(&self.functions, address)?;
+        let mut description = format!("{}+{:#x}", symbol.name, address - symbol.address);
+
+        if let Ok(Some(addr2line::Location {
+            file: Some(file),
+            line: Some(line),
+            ..
+        })) = self.lines.find_location(address)
+        {
+            description.push_str(&format!(" at {}:{}", shorten(file), line));
+        }
+        Some(description)
+    }
+}
+
+fn load_lines(elf: &object::File) -> Result<addr2line::Context<Reader>, gimli::Error> {
+    let endian = if elf.is_little_endian() {
+        gimli::RunTimeEndian::Little
+    } else {
+        gimli::RunTimeEndian::Big
+    };
+    let dwarf = gimli::Dwarf::load(|id| -> Result<Reader, gimli::Error> {
+        let data = elf
+            .section_by_name(id.name())
+            .and_then(|section| section.uncompressed_data().ok())
+            .unwrap_or_default();
+        Ok(gimli::EndianArcSlice::new(Arc::from(&*data), endian))
+    })?;
+    addr2line::Context::from_dwarf(dwarf)
+}
+
+/// The function containing `address`.
+fn lookup(functions: &[Symbol], address: u64) -> Option<&Symbol> {
+    let index = functions.partition_point(|symbol| symbol.address <= address);
+    let symbol = functions.get(index.checked_sub(1)?)?;
+    (address < symbol.address + symbol.size).then_some(symbol)
+}
+
+/// Paths of the project relative to the working directory.
+fn shorten(file: &str) -> Cow<'_, str> {
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| {
+            let relative = std::path::Path::new(file).strip_prefix(dir).ok()?;
+            Some(Cow::Owned(relative.to_string_lossy().into_owned()))
+        })
+        .unwrap_or(Cow::Borrowed(file))
+}
+
+/// Console filter which appends the description of each address, as in `0x1a4 <main+0x12 at
+/// src/main.rs:42>`.
+pub struct Symbolizer {
+    describe: Box<dyn Fn(u64) -> Option<String> + Send>,
+    /// Received bytes which may be the start of an address.
+    pending: Vec<u8>,
+}
+
+impl Symbolizer {
+    pub fn new(symbols: Symbols) -> Self {
+        Self::with(move |address| symbols.describe(address))
+    }
+
+    fn with(describe: impl Fn(u64) -> Option<String> + Send + 'static) -> Self {
+        Symbolizer {
+            describe: Box::new(describe),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Annotate the addresses in received data.  An address at the end is held back until the
+    /// next call or [`Symbolizer::flush`], as it may continue.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(data);
+        self.process(false)
+    }
+
+    /// Output everything held back, e.g. when nothing was received for a while.
+    pub fn flush(&mut self) -> Vec<u8> {
+        self.process(true)
+    }
+
+    fn process(&mut self, flush: bool) -> Vec<u8> {
+        let input = std::mem::take(&mut self.pending);
+        let mut output = Vec::with_capacity(input.len());
+
+        let mut i = 0;
+        while i < input.len() {
+            let rest = &input[i..];
+            let boundary = i == 0 || !input[i - 1].is_ascii_alphanumeric();
+            if !boundary || !b"0x".starts_with(&rest[..rest.len().min(2)]) {
+                output.push(input[i]);
+                i += 1;
+                continue;
+            }
+
+            let digits = rest
+                .iter()
+                .skip(2)
+                .take_while(|b| b.is_ascii_hexdigit())
+                .count();
+            let end = i + (2 + digits).min(rest.len());
+            if end == input.len() && !flush {
+                self.pending = rest.to_vec();
+                break;
+            }
+
+            output.extend_from_slice(&input[i..end]);
+            if digits > 0 {
+                let address = std::str::from_utf8(&input[i + 2..end])
+                    .ok()
+                    .and_then(|hex| u64::from_str_radix(hex, 16).ok());
+                if let Some(description) = address.and_then(&self.describe) {
+                    output.extend_from_slice(format!(" <{description}>").as_bytes());
+                }
+            }
+            i = end;
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbolizer() -> Symbolizer {
+        Symbolizer::with(|address| (address == 0x1a4).then(|| "main+0x12".to_string()))
+    }
+
+    #[test]
+    fn looks_up_containing_function() {
+        let functions = [
+            Symbol {
+                address: 0x100,
+                size: 0x20,
+                name: "a".into(),
+            },
+            Symbol {
+                address: 0x200,
+                size: 0x10,
+                name: "b".into(),
+            },
+        ];
+
+        assert_eq!(lookup(&functions, 0x100).unwrap().name, "a");
+        assert_eq!(lookup(&functions, 0x11f).unwrap().name, "a");
+        assert!(lookup(&functions, 0x120).is_none());
+        assert_eq!(lookup(&functions, 0x208).unwrap().name, "b");
+        assert!(lookup(&functions, 0x80).is_none());
+    }
+
+    #[test]
+    fn annotates_addresses() {
+        let mut symbolizer = symbolizer();
+        let output = symbolizer.feed(b"panic at 0x1a4, r24=0x00\r\n");
+
+        assert_eq!(output, b"panic at 0x1a4 <main+0x12>, r24=0x00\r\n");
+    }
+
+    #[test]
+    fn holds_back_split_addresses() {
+        let mut symbolizer = symbolizer();
+        assert_eq!(symbolizer.feed(b"pc: 0"), b"pc: ");
+        assert_eq!(symbolizer.feed(b"x1"), b"");
+        assert_eq!(symbolizer.feed(b"a4\n"), b"0x1a4 <main+0x12>\n");
+    }
+
+    #[test]
+    fn flushes_held_back_addresses() {
+        let mut symbolizer = symbolizer();
+        assert_eq!(symbolizer.feed(b"pc: 0x1a4"), b"pc: ");
+        assert_eq!(symbolizer.flush(), b"0x1a4 <main+0x12>");
+        assert_eq!(symbolizer.feed(b"0"), b"");
+        assert_eq!(symbolizer.flush(), b"0");
+    }
+
+    #[test]
+    fn ignores_hex_inside_words() {
+        let mut symbolizer = symbolizer();
+        assert_eq!(symbolizer.feed(b"id10x1a4 \n"), b"id10x1a4 \n");
+    }
+}